@@ -0,0 +1,3 @@
+DROP TABLE served_quotes;
+
+ALTER TABLE quotes DROP COLUMN created_at;
@@ -0,0 +1,10 @@
+ALTER TABLE quotes ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT LOCALTIMESTAMP;
+
+CREATE TABLE served_quotes (
+    id SERIAL PRIMARY KEY,
+    quote_id INT NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    channel_id BIGINT NOT NULL,
+    served_at TIMESTAMP NOT NULL DEFAULT LOCALTIMESTAMP
+);
+
+CREATE INDEX served_quotes_channel_served_at_idx ON served_quotes (channel_id, served_at DESC);
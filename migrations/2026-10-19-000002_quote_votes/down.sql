@@ -0,0 +1,5 @@
+DROP TABLE quote_votes;
+
+DROP INDEX served_quotes_message_id_idx;
+
+ALTER TABLE served_quotes DROP COLUMN message_id;
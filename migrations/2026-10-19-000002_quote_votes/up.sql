@@ -0,0 +1,11 @@
+ALTER TABLE served_quotes ADD COLUMN message_id BIGINT;
+
+CREATE INDEX served_quotes_message_id_idx ON served_quotes (message_id);
+
+CREATE TABLE quote_votes (
+    quote_id INT NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    vote SMALLINT NOT NULL CHECK (vote IN (-1, 1)),
+    voted_at TIMESTAMP NOT NULL DEFAULT LOCALTIMESTAMP,
+    PRIMARY KEY (quote_id, user_id)
+);
@@ -0,0 +1,3 @@
+DROP TABLE daily_quotes;
+
+DROP TABLE quote_of_the_day_settings;
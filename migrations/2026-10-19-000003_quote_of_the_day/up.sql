@@ -0,0 +1,17 @@
+CREATE TABLE quote_of_the_day_settings (
+    id INT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    post_time TIME NOT NULL DEFAULT '09:00',
+    channel_id BIGINT,
+    repeat_window_days INT NOT NULL DEFAULT 365
+);
+
+INSERT INTO quote_of_the_day_settings DEFAULT VALUES;
+
+CREATE TABLE daily_quotes (
+    id SERIAL PRIMARY KEY,
+    quote_id INT NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    posted_on DATE NOT NULL UNIQUE,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX quotes_quoted_legacy_idx;
+
+ALTER TABLE quotes DROP COLUMN legacy;
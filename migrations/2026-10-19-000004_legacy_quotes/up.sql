@@ -0,0 +1,3 @@
+ALTER TABLE quotes ADD COLUMN legacy BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX quotes_quoted_legacy_idx ON quotes (quoted, legacy);
@@ -0,0 +1,4 @@
+-- Existing members keep a NULL date since when they were added isn't known
+ALTER TABLE members ADD COLUMN created_at TIMESTAMP;
+
+ALTER TABLE members ALTER COLUMN created_at SET DEFAULT LOCALTIMESTAMP;
@@ -0,0 +1,2 @@
+DROP INDEX members_lower_primary_name_key;
+DROP INDEX nicknames_lower_nickname_key;
@@ -0,0 +1,33 @@
+-- Duplicate nicknames made lookups pick an arbitrary member, keep the oldest
+DELETE FROM nicknames a
+    USING nicknames b
+    WHERE lower(a.nickname) = lower(b.nickname) AND a.id > b.id;
+
+-- Members whose names only differ in case are different people, so they
+-- can't be merged. The one with the lowest id keeps the name, the others
+-- get their id added to it and their quotes follow. Nicknames and events
+-- point at ids, so they stay put.
+DO $$
+DECLARE
+    dup RECORD;
+BEGIN
+    FOR dup IN
+        SELECT a.id, a.primary_name
+        FROM members a
+        WHERE EXISTS (
+            SELECT 1 FROM members b
+            WHERE lower(b.primary_name) = lower(a.primary_name) AND b.id < a.id
+        )
+    LOOP
+        RAISE NOTICE 'Member % is called % like another member, renaming them to %',
+            dup.id, dup.primary_name, dup.primary_name || '_' || dup.id;
+        UPDATE quotes SET quoted = dup.primary_name || '_' || dup.id
+            WHERE quoted = dup.primary_name;
+        UPDATE members SET primary_name = dup.primary_name || '_' || dup.id
+            WHERE id = dup.id;
+    END LOOP;
+END
+$$;
+
+CREATE UNIQUE INDEX nicknames_lower_nickname_key ON nicknames (lower(nickname));
+CREATE UNIQUE INDEX members_lower_primary_name_key ON members (lower(primary_name));
@@ -0,0 +1,41 @@
+-- Only the home guild's data fits back into the single guild layout
+DELETE FROM quote_of_the_day_settings
+    WHERE guild_id IS DISTINCT FROM NULLIF(current_setting('todd.home_guild_id', true), '')::BIGINT;
+ALTER TABLE quote_of_the_day_settings DROP CONSTRAINT quote_of_the_day_settings_guild_id_key;
+ALTER TABLE quote_of_the_day_settings ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE quote_of_the_day_settings_id_seq;
+UPDATE quote_of_the_day_settings SET id = 1;
+ALTER TABLE quote_of_the_day_settings ADD CONSTRAINT quote_of_the_day_settings_id_check CHECK (id = 1);
+ALTER TABLE quote_of_the_day_settings DROP COLUMN guild_id;
+INSERT INTO quote_of_the_day_settings (id) VALUES (1) ON CONFLICT DO NOTHING;
+
+ALTER TABLE daily_quotes DROP CONSTRAINT daily_quotes_guild_id_posted_on_key;
+DELETE FROM daily_quotes a USING daily_quotes b
+    WHERE a.posted_on = b.posted_on AND a.id > b.id;
+ALTER TABLE daily_quotes ADD CONSTRAINT daily_quotes_posted_on_key UNIQUE (posted_on);
+ALTER TABLE daily_quotes DROP COLUMN guild_id;
+
+DROP INDEX quotes_quoted_legacy_idx;
+CREATE INDEX quotes_quoted_legacy_idx ON quotes (quoted, legacy);
+ALTER TABLE quotes DROP COLUMN guild_id;
+
+ALTER TABLE reminders DROP COLUMN guild_id;
+
+ALTER TABLE events DROP CONSTRAINT events_guild_id_owned_by_fkey;
+ALTER TABLE events DROP COLUMN guild_id;
+
+ALTER TABLE nicknames DROP CONSTRAINT nicknames_guild_id_primary_name_fkey;
+DROP INDEX nicknames_lower_nickname_key;
+CREATE UNIQUE INDEX nicknames_lower_nickname_key ON nicknames (lower(nickname));
+ALTER TABLE nicknames DROP COLUMN guild_id;
+
+DROP INDEX members_lower_primary_name_key;
+ALTER TABLE members DROP CONSTRAINT members_pkey;
+ALTER TABLE members DROP COLUMN guild_id;
+ALTER TABLE members ADD PRIMARY KEY (id);
+CREATE UNIQUE INDEX members_lower_primary_name_key ON members (lower(primary_name));
+
+ALTER TABLE nicknames ADD CONSTRAINT nicknames_primary_name_fkey
+    FOREIGN KEY (primary_name) REFERENCES members (id);
+ALTER TABLE events ADD CONSTRAINT events_owned_by_fkey
+    FOREIGN KEY (owned_by) REFERENCES members (id);
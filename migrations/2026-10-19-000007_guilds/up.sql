@@ -0,0 +1,68 @@
+-- Everything that already exists belongs to the home guild. Set it before
+-- migrating a database that has data in it:
+--   ALTER DATABASE <database> SET todd.home_guild_id = '<guild id>';
+-- and reconnect so the setting is picked up.
+DO $$
+BEGIN
+    IF COALESCE(current_setting('todd.home_guild_id', true), '') = ''
+        AND (EXISTS (SELECT 1 FROM members) OR EXISTS (SELECT 1 FROM quotes)) THEN
+        RAISE EXCEPTION 'todd.home_guild_id is not set, see the comment in this migration';
+    END IF;
+END
+$$;
+
+CREATE FUNCTION pg_temp.home_guild_id() RETURNS BIGINT AS $$
+    SELECT NULLIF(current_setting('todd.home_guild_id', true), '')::BIGINT
+$$ LANGUAGE SQL;
+
+ALTER TABLE nicknames DROP CONSTRAINT nicknames_primary_name_fkey;
+ALTER TABLE events DROP CONSTRAINT events_owned_by_fkey;
+
+ALTER TABLE members ADD COLUMN guild_id BIGINT;
+UPDATE members SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE members ALTER COLUMN guild_id SET NOT NULL;
+-- The same person can be a member of several guilds
+ALTER TABLE members DROP CONSTRAINT members_pkey;
+ALTER TABLE members ADD PRIMARY KEY (guild_id, id);
+DROP INDEX members_lower_primary_name_key;
+CREATE UNIQUE INDEX members_lower_primary_name_key ON members (guild_id, lower(primary_name));
+
+ALTER TABLE nicknames ADD COLUMN guild_id BIGINT;
+UPDATE nicknames SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE nicknames ALTER COLUMN guild_id SET NOT NULL;
+ALTER TABLE nicknames ADD FOREIGN KEY (guild_id, primary_name) REFERENCES members (guild_id, id);
+DROP INDEX nicknames_lower_nickname_key;
+CREATE UNIQUE INDEX nicknames_lower_nickname_key ON nicknames (guild_id, lower(nickname));
+
+ALTER TABLE events ADD COLUMN guild_id BIGINT;
+UPDATE events SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE events ALTER COLUMN guild_id SET NOT NULL;
+ALTER TABLE events ADD FOREIGN KEY (guild_id, owned_by) REFERENCES members (guild_id, id);
+
+ALTER TABLE reminders ADD COLUMN guild_id BIGINT;
+UPDATE reminders SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE reminders ALTER COLUMN guild_id SET NOT NULL;
+
+ALTER TABLE quotes ADD COLUMN guild_id BIGINT;
+UPDATE quotes SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE quotes ALTER COLUMN guild_id SET NOT NULL;
+DROP INDEX quotes_quoted_legacy_idx;
+CREATE INDEX quotes_quoted_legacy_idx ON quotes (guild_id, quoted, legacy);
+
+ALTER TABLE daily_quotes ADD COLUMN guild_id BIGINT;
+UPDATE daily_quotes SET guild_id = pg_temp.home_guild_id();
+ALTER TABLE daily_quotes ALTER COLUMN guild_id SET NOT NULL;
+ALTER TABLE daily_quotes DROP CONSTRAINT daily_quotes_posted_on_key;
+ALTER TABLE daily_quotes ADD UNIQUE (guild_id, posted_on);
+
+-- One row of settings per guild instead of a single global row
+ALTER TABLE quote_of_the_day_settings ADD COLUMN guild_id BIGINT;
+UPDATE quote_of_the_day_settings SET guild_id = pg_temp.home_guild_id();
+DELETE FROM quote_of_the_day_settings WHERE guild_id IS NULL;
+ALTER TABLE quote_of_the_day_settings ALTER COLUMN guild_id SET NOT NULL;
+ALTER TABLE quote_of_the_day_settings DROP CONSTRAINT quote_of_the_day_settings_id_check;
+CREATE SEQUENCE quote_of_the_day_settings_id_seq OWNED BY quote_of_the_day_settings.id;
+SELECT setval('quote_of_the_day_settings_id_seq', 1);
+ALTER TABLE quote_of_the_day_settings
+    ALTER COLUMN id SET DEFAULT nextval('quote_of_the_day_settings_id_seq');
+ALTER TABLE quote_of_the_day_settings ADD UNIQUE (guild_id);
@@ -0,0 +1,11 @@
+CREATE TABLE guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    announcement_channel_id BIGINT,
+    prefixes TEXT[] NOT NULL DEFAULT '{"!", "Todd!", "todd!"}',
+    admin_role_id BIGINT,
+    -- A fixed UTC offset such as +02:00; NULL means the server's local time
+    timezone TEXT,
+    quote_approval BOOLEAN NOT NULL DEFAULT FALSE,
+    disabled_features TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
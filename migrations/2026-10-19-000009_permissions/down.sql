@@ -0,0 +1,2 @@
+ALTER TABLE quotes DROP COLUMN submitted_by;
+ALTER TABLE guild_settings DROP COLUMN moderator_role_id;
@@ -0,0 +1,4 @@
+ALTER TABLE guild_settings ADD COLUMN moderator_role_id BIGINT;
+-- Who added the quote, so they can remove it again. NULL for quotes added
+-- before anyone was keeping track and for imported ones.
+ALTER TABLE quotes ADD COLUMN submitted_by BIGINT;
@@ -0,0 +1,2 @@
+ALTER TABLE guild_settings DROP COLUMN audit_channel_id;
+DROP TABLE audit_log;
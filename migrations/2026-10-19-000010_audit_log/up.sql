@@ -0,0 +1,19 @@
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    -- NULL when the bot changed something on its own
+    actor_id BIGINT,
+    command TEXT NOT NULL,
+    action TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT LOCALTIMESTAMP,
+    mirrored BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE INDEX audit_log_guild_id_created_at_idx ON audit_log (guild_id, created_at);
+CREATE INDEX audit_log_entity_idx ON audit_log (guild_id, entity_type, entity_id);
+CREATE INDEX audit_log_unmirrored_idx ON audit_log (id) WHERE NOT mirrored;
+
+ALTER TABLE guild_settings ADD COLUMN audit_channel_id BIGINT;
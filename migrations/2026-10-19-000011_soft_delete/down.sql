@@ -0,0 +1,6 @@
+DELETE FROM reminders WHERE deleted_at IS NOT NULL;
+DELETE FROM events WHERE deleted_at IS NOT NULL;
+DELETE FROM quotes WHERE deleted_at IS NOT NULL;
+ALTER TABLE reminders DROP COLUMN deleted_at;
+ALTER TABLE events DROP COLUMN deleted_at;
+ALTER TABLE quotes DROP COLUMN deleted_at;
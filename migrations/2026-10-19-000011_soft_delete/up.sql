@@ -0,0 +1,8 @@
+-- Deleted quotes, events and reminders are kept for a while so `!undo` can
+-- bring them back, and purged once they're older than the retention period.
+ALTER TABLE quotes ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE events ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE reminders ADD COLUMN deleted_at TIMESTAMP;
+CREATE INDEX quotes_deleted_at_idx ON quotes (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX events_deleted_at_idx ON events (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX reminders_deleted_at_idx ON reminders (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,18 @@
+DELETE FROM reminders WHERE event_id IN (
+    SELECT e.id FROM events e
+    JOIN members m ON m.guild_id = e.guild_id AND m.id = e.owned_by
+    WHERE m.deleted_at IS NOT NULL
+);
+DELETE FROM events e USING members m
+    WHERE m.guild_id = e.guild_id AND m.id = e.owned_by AND m.deleted_at IS NOT NULL;
+DELETE FROM nicknames n USING members m
+    WHERE m.guild_id = n.guild_id AND m.id = n.primary_name AND m.deleted_at IS NOT NULL;
+DELETE FROM nicknames WHERE deleted_at IS NOT NULL;
+DELETE FROM members WHERE deleted_at IS NOT NULL;
+
+DROP INDEX nicknames_lower_nickname_key;
+CREATE UNIQUE INDEX nicknames_lower_nickname_key ON nicknames (guild_id, lower(nickname));
+DROP INDEX members_lower_primary_name_key;
+CREATE UNIQUE INDEX members_lower_primary_name_key ON members (guild_id, lower(primary_name));
+ALTER TABLE nicknames DROP COLUMN deleted_at;
+ALTER TABLE members DROP COLUMN deleted_at;
@@ -0,0 +1,14 @@
+-- Removed members are kept, along with their nicknames, so `!undo` can bring
+-- them back, and purged once they're older than the retention period. Their
+-- names are free for someone else in the meantime.
+ALTER TABLE members ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE nicknames ADD COLUMN deleted_at TIMESTAMP;
+CREATE INDEX members_deleted_at_idx ON members (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX nicknames_deleted_at_idx ON nicknames (deleted_at) WHERE deleted_at IS NOT NULL;
+
+DROP INDEX members_lower_primary_name_key;
+CREATE UNIQUE INDEX members_lower_primary_name_key ON members (guild_id, lower(primary_name))
+    WHERE deleted_at IS NULL;
+DROP INDEX nicknames_lower_nickname_key;
+CREATE UNIQUE INDEX nicknames_lower_nickname_key ON nicknames (guild_id, lower(nickname))
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,2 @@
+DELETE FROM quotes WHERE NOT approved;
+ALTER TABLE quotes DROP COLUMN approved;
@@ -0,0 +1,4 @@
+-- Quotes added while a guild has quote approval on wait here until a
+-- moderator approves them. Everything added before then counts as approved.
+ALTER TABLE quotes ADD COLUMN approved BOOLEAN NOT NULL DEFAULT true;
+CREATE INDEX quotes_pending_idx ON quotes (guild_id) WHERE NOT approved;
@@ -0,0 +1,4 @@
+DROP TRIGGER quotes_bump_generation ON quotes;
+DROP FUNCTION quotes_changed();
+DROP FUNCTION bump_quote_generation(BIGINT, VARCHAR);
+DROP TABLE quote_generations;
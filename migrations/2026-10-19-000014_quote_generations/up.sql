@@ -0,0 +1,33 @@
+-- Counts changes to each member's quotes, so anything built from them (the
+-- chains `imitate` caches) can tell when it's out of date. Kept up to date
+-- by the trigger below, whatever changes the quotes.
+CREATE TABLE quote_generations (
+    guild_id BIGINT NOT NULL,
+    quoted VARCHAR NOT NULL,
+    generation BIGINT NOT NULL DEFAULT 1,
+    PRIMARY KEY (guild_id, quoted)
+);
+
+CREATE FUNCTION bump_quote_generation(guild BIGINT, name VARCHAR) RETURNS VOID AS $$
+    INSERT INTO quote_generations (guild_id, quoted) VALUES (guild, name)
+    ON CONFLICT (guild_id, quoted)
+        DO UPDATE SET generation = quote_generations.generation + 1
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION quotes_changed() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM bump_quote_generation(OLD.guild_id, OLD.quoted);
+    END IF;
+    -- A quote moved to someone else changes both of them
+    IF TG_OP = 'INSERT'
+        OR (TG_OP = 'UPDATE' AND (OLD.guild_id, OLD.quoted) IS DISTINCT FROM (NEW.guild_id, NEW.quoted)) THEN
+        PERFORM bump_quote_generation(NEW.guild_id, NEW.quoted);
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER quotes_bump_generation
+    AFTER INSERT OR UPDATE OR DELETE ON quotes
+    FOR EACH ROW EXECUTE FUNCTION quotes_changed();
@@ -0,0 +1,2 @@
+DELETE FROM daily_quotes WHERE message_id IS NULL;
+ALTER TABLE daily_quotes ALTER COLUMN message_id SET NOT NULL;
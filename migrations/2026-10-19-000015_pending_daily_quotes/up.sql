@@ -0,0 +1,3 @@
+-- The day's quote is claimed before it's posted, and has no message until
+-- the post goes through
+ALTER TABLE daily_quotes ALTER COLUMN message_id DROP NOT NULL;
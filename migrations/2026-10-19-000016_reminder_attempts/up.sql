@@ -0,0 +1,3 @@
+-- Reminders are announced before they're removed, so a failed announcement
+-- is tried again; this is how many times it's been tried
+ALTER TABLE reminders ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
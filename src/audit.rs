@@ -0,0 +1,268 @@
+// audit.rs
+
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::models::AuditEntry;
+use crate::pages;
+use crate::permissions;
+use crate::{Context, Error};
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use serde_json::Value;
+use serenity::ChannelId;
+use std::collections::BTreeMap;
+
+const DEFAULT_RECENT: i64 = 10;
+const MAX_RECENT: i64 = 50;
+const MAX_JSON_LEN: usize = 300;
+/// How many entries each scheduler tick posts to audit channels.
+const MIRROR_BATCH: i64 = 100;
+
+/// What `!audit entity` can look up.
+pub const ENTITY_TYPES: [&str; 8] = [
+    "member",
+    "nickname",
+    "quote",
+    "legacy_quotes",
+    "event",
+    "reminder",
+    "guild_settings",
+    "qotd_settings",
+];
+
+/// Who is changing data, for the audit log. Every function in `databaser`
+/// that changes data takes one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    /// `None` when the bot changed something on its own.
+    pub user_id: Option<i64>,
+    /// The command that was run, or what the bot was doing.
+    pub command: String,
+}
+
+impl Actor {
+    pub fn from_ctx(ctx: Context<'_>) -> Actor {
+        Actor {
+            user_id: Some(i64::from(ctx.author().id)),
+            command: ctx.command().qualified_name.clone(),
+        }
+    }
+
+    /// The bot itself, doing `task`.
+    pub fn bot(task: &str) -> Actor {
+        Actor {
+            user_id: None,
+            command: task.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Update,
+    Delete,
+    /// Bringing back something that was deleted, with `!undo`.
+    Restore,
+}
+
+impl Action {
+    /// A change with nothing before it is a create, and one with nothing
+    /// after it a delete.
+    pub fn between(before: &Option<Value>, after: &Option<Value>) -> Action {
+        match (before, after) {
+            (None, _) => Action::Create,
+            (Some(_), None) => Action::Delete,
+            (Some(_), Some(_)) => Action::Update,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+        }
+    }
+
+    fn past_tense(name: &str) -> &str {
+        match name {
+            "create" => "created",
+            "update" => "updated",
+            "delete" => "deleted",
+            "restore" => "restored",
+            other => other,
+        }
+    }
+}
+
+/// One line saying who did what, for `!audit` and audit channels.
+pub fn describe(entry: &AuditEntry) -> String {
+    format!(
+        "`#{}` {}: {} {} {} {} with `{}`",
+        entry.id,
+        entry.created_at.format("%D %I:%M %P"),
+        match entry.actor_id {
+            Some(a) => format!("<@{}>", a),
+            None => "Todd".to_string(),
+        },
+        Action::past_tense(&entry.action),
+        entry.entity_type.replace('_', " "),
+        entry.entity_id,
+        entry.command
+    )
+}
+
+fn show_json(value: &Option<Value>) -> String {
+    let output = match value {
+        Some(v) => v.to_string(),
+        None => "nothing".to_string(),
+    };
+    if output.chars().count() <= MAX_JSON_LEN {
+        output
+    } else {
+        output.chars().take(MAX_JSON_LEN - 3).collect::<String>() + "..."
+    }
+}
+
+#[poise::command(
+    prefix_command,
+    category = "Admin",
+    subcommands("recent", "entity"),
+    subcommand_required
+)]
+pub async fn audit(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn recent(ctx: Context<'_>, count: Option<i64>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let count = count.unwrap_or(DEFAULT_RECENT).clamp(1, MAX_RECENT);
+    let mut conn = databaser::establish_connection()?;
+    let entries = databaser::get_recent_audit_entries(&mut conn, guild, count)?;
+
+    let title = format!("Last {} changes", entries.len());
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|e| format!("- {}", describe(e)))
+        .collect();
+    pages::reply_pages(ctx, &title, &lines, "Nothing has been changed yet").await?;
+    Ok(())
+}
+
+/// Every change to one thing, newest last, with what it looked like before
+/// and after.
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn entity(ctx: Context<'_>, entity_type: String, id: String) -> Result<(), Error> {
+    let entity_type = entity_type.to_lowercase();
+    if !ENTITY_TYPES.contains(&entity_type.as_str()) {
+        return Err(Error::from(ToddError::InvalidInput(format!(
+            "Error: *{}* is not something that's audited, try one of: {}",
+            entity_type,
+            ENTITY_TYPES.join(", ")
+        ))));
+    }
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let entries = databaser::get_audit_entries_for(&mut conn, guild, &entity_type, &id)?;
+    let title = format!("History of {} {}", entity_type, id);
+    let sections: Vec<String> = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{}\n```\nbefore: {}\nafter:  {}\n```",
+                describe(e),
+                show_json(&e.before),
+                show_json(&e.after)
+            )
+        })
+        .collect();
+    let empty = format!("No changes to {} {} were recorded", entity_type, id);
+    pages::reply_pages(ctx, &title, &sections, &empty).await?;
+    Ok(())
+}
+
+// Called from `calendar::check_events_loop` every tick. Posts new entries to
+// the audit channel of guilds that set one with `!config set audit_channel`.
+// Entries are marked as mirrored even when posting fails, so a channel the bot
+// can't write to doesn't make it retry forever.
+pub async fn mirror_new_entries(
+    ctx: &serenity::Context,
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    let entries = databaser::get_unmirrored_audit_entries(conn, MIRROR_BATCH)?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut by_guild: BTreeMap<i64, Vec<&AuditEntry>> = BTreeMap::new();
+    for e in &entries {
+        by_guild.entry(e.guild_id).or_default().push(e);
+    }
+
+    let mut result = Ok(());
+    for (guild, guild_entries) in by_guild {
+        let Some(channel) = databaser::get_guild_settings(conn, guild)?.audit_channel_id else {
+            continue;
+        };
+        let lines: Vec<String> = guild_entries.iter().map(|e| describe(e)).collect();
+        for message in pages::paginate(&lines, pages::PAGE_LEN) {
+            if let Err(e) = ChannelId(channel as u64).say(&ctx.http, message).await {
+                result = Err(Error::from(format!(
+                    "Failed to mirror audit log for guild {}: {}",
+                    guild, e
+                )));
+                break;
+            }
+        }
+    }
+    let ids: Vec<i32> = entries.iter().map(|e| e.id).collect();
+    databaser::mark_audit_entries_mirrored(conn, &ids)?;
+    result
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::*;
+    use chrono::prelude::*;
+    use serde_json::json;
+
+    fn sample_entry(action: &str, actor_id: Option<i64>) -> AuditEntry {
+        AuditEntry {
+            id: 7,
+            guild_id: -1,
+            actor_id,
+            command: "calendar remove".to_string(),
+            action: action.to_string(),
+            entity_type: "event".to_string(),
+            entity_id: "12".to_string(),
+            before: None,
+            after: None,
+            created_at: NaiveDate::from_ymd_opt(2026, 10, 19)
+                .unwrap()
+                .and_hms_opt(15, 4, 0)
+                .unwrap(),
+            mirrored: false,
+        }
+    }
+
+    #[test]
+    fn test_action_between() {
+        let row = Some(json!({ "id": 1 }));
+        assert_eq!(Action::between(&None, &row), Action::Create);
+        assert_eq!(Action::between(&row, &row), Action::Update);
+        assert_eq!(Action::between(&row, &None), Action::Delete);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(&sample_entry("delete", Some(42))),
+            "`#7` 10/19/26 03:04 pm: <@42> deleted event 12 with `calendar remove`"
+        );
+        assert!(describe(&sample_entry("create", None)).contains("Todd created event 12"));
+        assert!(describe(&sample_entry("restore", Some(42))).contains("restored event 12"));
+    }
+}
@@ -1,6 +1,18 @@
 // calendar.rs
+use crate::audit::{self, Actor};
+use crate::config;
 use crate::databaser;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::health;
+use crate::member_commands;
+use crate::metrics;
 use crate::models::{CalendarType, Reminder, ToCalendar, ToddEvent};
+use crate::pages;
+use crate::permissions;
+use crate::quote_of_the_day;
+use crate::settings;
+use crate::shutdown;
 use crate::todd_commands;
 use crate::{Context, Error, RemindersKey};
 use chrono::prelude::*;
@@ -9,125 +21,302 @@ use diesel::prelude::*;
 use poise::serenity_prelude as serenity;
 // use std::sync::{Arc};
 use tokio::time::{interval, Duration};
+use tracing::{debug, error, info, info_span, Instrument};
 // use tokio::sync::{Mutex};
-use serenity::{ChannelId, MessageBuilder};
-use std::env::var;
+use serenity::MessageBuilder;
+use std::collections::HashMap;
+
+/// How far ahead of UTC a guild's clock can be.
+const MAX_UTC_OFFSET_HOURS: i64 = 14;
 
 pub async fn fetch_reminders() -> Result<Vec<Reminder>, Error> {
     use crate::schema::reminders::dsl::*;
 
     let mut conn = databaser::establish_connection()?;
-    let now = Local::now().naive_local();
+    let _timer = metrics::query_timer("fetch_reminders");
+    // Reminders are in their guild's time, so look as far ahead as any
+    // guild's clock could be
+    let now = Utc::now().naive_utc() + chrono::Duration::hours(MAX_UTC_OFFSET_HOURS);
     let output = reminders
-        .filter(time_before.between(now, now + chrono::Duration::minutes(30)))
+        // Overdue ones too, they're what's left of a failed announcement
+        .filter(time_before.le(now + chrono::Duration::minutes(30)))
+        .filter(deleted_at.is_null())
         .load::<Reminder>(&mut conn)?;
     Ok(output)
 }
 pub async fn fetch_events_loop(ctx: serenity::Context) {
     let mut interval = interval(Duration::from_secs(1800));
-    loop {
-        let fetched_reminders = match fetch_reminders().await {
-            Ok(r) => r,
-            Err(err) => {
-                eprintln!("Failed to fetch events: {}", err);
-                continue;
-            }
-        };
-        let reminders_to_watch = ctx
-            .data
-            .read()
-            .await
-            .get::<RemindersKey>()
-            .cloned()
-            .unwrap_or_default();
-
-        let mut locked_reminders = reminders_to_watch.lock().await;
-
-        *locked_reminders = fetched_reminders;
-        drop(locked_reminders);
+    while !shutdown::is_shutting_down() {
+        fetch_events_tick(&ctx)
+            .instrument(info_span!("fetch_reminders"))
+            .await;
         interval.tick().await;
     }
 }
+async fn fetch_events_tick(ctx: &serenity::Context) {
+    let fetched_reminders = match fetch_reminders().await {
+        Ok(r) => r,
+        Err(err) => {
+            error!("Failed to fetch events: {}", err);
+            return;
+        }
+    };
+    debug!(
+        count = fetched_reminders.len(),
+        "fetched upcoming reminders"
+    );
+    let reminders_to_watch = ctx
+        .data
+        .read()
+        .await
+        .get::<RemindersKey>()
+        .cloned()
+        .unwrap_or_default();
+
+    *reminders_to_watch.lock().await = fetched_reminders;
+}
 
-pub fn is_within_one_minute(datetime: NaiveDateTime) -> bool {
-    let current_time = Local::now().naive_local();
-    let one_minute_later = current_time + chrono::Duration::minutes(1);
+/// Due by the next tick, or overdue.
+pub fn is_due(datetime: NaiveDateTime, now: NaiveDateTime) -> bool {
+    datetime < now + chrono::Duration::minutes(1)
+}
 
-    datetime >= current_time && datetime < one_minute_later
+/// Should have gone out on an earlier tick.
+fn is_late(datetime: NaiveDateTime, now: NaiveDateTime) -> bool {
+    datetime < now - chrono::Duration::minutes(1)
 }
 
 pub async fn check_events_loop(ctx: serenity::Context) {
     let mut interval = interval(Duration::from_secs(60));
-    loop {
-        // Error handling in loop is important
-        // This is a spot where errors will not reach userland
-        // and errors cannot be propigated
-        let mut conn = match databaser::establish_connection() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Could not establish conn: {}", e);
+    // Stops once shutting down, but never partway through a tick
+    while let Some(tick) = shutdown::start_tick().await {
+        check_events_tick(&ctx)
+            .instrument(info_span!("scheduler_tick"))
+            .await;
+        drop(tick);
+        interval.tick().await;
+    }
+}
+async fn check_events_tick(ctx: &serenity::Context) {
+    health::scheduler_ticked();
+    // Error handling in loop is important
+    // This is a spot where errors will not reach userland
+    // and errors cannot be propigated
+    let mut conn = match databaser::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish conn: {}", e);
+            return;
+        }
+    };
+    // The created  reminders
+    let reminders_to_watch = ctx
+        .data
+        .read()
+        .await
+        .get::<RemindersKey>()
+        .cloned()
+        .unwrap_or_default();
+
+    // A copy, so `!calendar` and the fetch loop aren't kept waiting while
+    // the announcements go out
+    let watched = reminders_to_watch.lock().await.clone();
+    let guild_now = guild_times(&mut conn, &watched);
+    let done = announce_due(
+        &mut conn,
+        &watched,
+        |guild| guild_now[&guild],
+        |event| send_event_message(ctx, event),
+    )
+    .await;
+    reminders_to_watch
+        .lock()
+        .await
+        .retain(|r| !done.contains(&r.id));
+    if let Err(err) = quote_of_the_day::post_quote_of_the_day(ctx, &mut conn).await {
+        error!("Error posting quote of the day: {}", err);
+    }
+    if let Err(err) = audit::mirror_new_entries(ctx, &mut conn).await {
+        error!("Error mirroring audit log: {}", err);
+    }
+}
+pub async fn purge_deleted_loop() {
+    let mut interval = interval(Duration::from_secs(3600));
+    while let Some(tick) = shutdown::start_tick().await {
+        purge_deleted_tick()
+            .instrument(info_span!("purge_deleted"))
+            .await;
+        drop(tick);
+        interval.tick().await;
+    }
+}
+/// Permanently removes what was deleted longer ago than `deletion.retention_days`,
+/// a guild at a time.
+async fn purge_deleted_tick() {
+    let mut conn = match databaser::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish conn: {}", e);
+            return;
+        }
+    };
+    let purge_before = Local::now().naive_local() - config::get().deleted_retention();
+    let guilds = match databaser::get_guilds_with_deleted(&mut conn, purge_before) {
+        Ok(g) => g,
+        Err(err) => {
+            error!("Error finding deleted rows to purge: {}", err);
+            return;
+        }
+    };
+    for guild in guilds {
+        match databaser::purge_deleted(&mut conn, guild, purge_before) {
+            Ok(purged) => info!(guild, purged, "purged deleted rows past retention"),
+            Err(err) => error!(guild, "Error purging deleted rows: {}", err),
+        }
+    }
+}
+/// The current time in each guild with a reminder in `watched`, which is
+/// what their reminders' times are in. Server time for guilds whose settings
+/// can't be read.
+fn guild_times(conn: &mut PgConnection, watched: &[Reminder]) -> HashMap<i64, NaiveDateTime> {
+    let mut output = HashMap::new();
+    for r in watched {
+        output.entry(r.guild_id).or_insert_with(|| {
+            settings::guild_now(conn, r.guild_id).unwrap_or_else(|err| {
+                error!(guild = r.guild_id, "Could not read guild settings: {}", err);
+                Local::now().naive_local()
+            })
+        });
+    }
+    output
+}
+/// How many times a reminder is tried, a tick apart, before it's given up on.
+const MAX_REMINDER_ATTEMPTS: i32 = 60;
+/// Announces the `watched` reminders that are due by `now` in their guild
+/// with `send`. Returns the ones that are done with, which shouldn't be
+/// watched any more. Ones that couldn't be announced are left, so they're
+/// tried again next tick, marked as late, until they've been tried
+/// `MAX_REMINDER_ATTEMPTS` times.
+async fn announce_due<N, F, Fut>(
+    conn: &mut PgConnection,
+    watched: &[Reminder],
+    now: N,
+    mut send: F,
+) -> Vec<i32>
+where
+    N: Fn(i64) -> NaiveDateTime,
+    F: FnMut(ToddEvent) -> Fut,
+    Fut: std::future::Future<Output = Result<(), Error>>,
+{
+    let mut done = vec![];
+    for r in watched
+        .iter()
+        .filter(|r| is_due(r.time_before, now(r.guild_id)))
+    {
+        let late = is_late(r.time_before, now(r.guild_id));
+        info!(
+            reminder = r.id,
+            event = r.event_id,
+            late,
+            "sending reminder"
+        );
+        let attempts = match databaser::record_reminder_attempt(conn, r.guild_id, r.id) {
+            Ok(a) => a,
+            Err(err) => {
+                error!(
+                    reminder = r.id,
+                    "Error counting reminder attempt, trying again next tick: {}", err
+                );
                 continue;
             }
         };
-        // The created  reminders
-        let reminders_to_watch = ctx
-            .data
-            .read()
-            .await
-            .get::<RemindersKey>()
-            .cloned()
-            .unwrap_or_default();
-
-        for r in reminders_to_watch.lock().await.clone() {
-            if is_within_one_minute(r.time_before) {
-                let mut desc_vec = "".to_string();
-                // initialized to be modified later
-                let mut event = ToddEvent {
-                    id: 0,
-                    title: "Reminder".to_string(),
-                    description: None,
-                    timedate: Local::now().naive_local(),
-                    is_recuring: false,
-                    owned_by: 0,
-                    recurring_by: None,
-                };
-                // try to get reminder's parent. if parent is not found
-                // something has gone wrong.
-                let parent = {
-                    let p = r.parent(&mut conn);
-                    handle_parentsome(&mut conn, p, r.clone())
-                };
-                if let Err(err) = parent {
-                    desc_vec.push_str(format!("\n{:?}", err).as_str());
-                } else {
-                    // Turn event into parent
-                    let parent = parent.unwrap();
-                    event.id = parent.id;
-                    event.title = parent.title;
-                    if let Some(d) = parent.description {
-                        desc_vec.push_str(format!("\n{}", d).as_str())
-                    }
-                    event.timedate = parent.timedate;
-                    event.owned_by = parent.owned_by;
-                    event.recurring_by = parent.recurring_by;
-                }
-                if !desc_vec.is_empty() {
-                    event.description = Some(desc_vec.to_string())
-                }
-
-                // if all is well, send the message to userland.
-                // The message might have errors in it, but the core should be
-                // there.
-                if let Err(err) = send_event_message(&ctx, event).await {
-                    eprintln!("Error sending reminder message for reminder: {:?}", r);
-                    eprintln!("Error: {}", err)
+        match send(announcement(conn, r, late)).await {
+            Ok(()) => metrics::reminder_fired(),
+            Err(err) if attempts >= MAX_REMINDER_ATTEMPTS => {
+                metrics::reminder_missed();
+                error!(
+                    reminder = r.id,
+                    attempts, "Error sending reminder message, giving up on it: {}", err
+                );
+            }
+            Err(err) => {
+                metrics::reminder_missed();
+                error!(
+                    reminder = r.id,
+                    attempts, "Error sending reminder message, trying again next tick: {}", err
+                );
+                continue;
+            }
+        }
+        finish_reminder(conn, r);
+        done.push(r.id);
+    }
+    done
+}
+/// What's announced for a reminder: its event, or a bare reminder if the
+/// event can't be found. Only reads, the reminder is finished once the
+/// announcement has gone out.
+fn announcement(conn: &mut PgConnection, r: &Reminder, late: bool) -> ToddEvent {
+    let mut event = match r.parent(conn) {
+        Some(parent) if parent.id == r.event_id => ToddEvent {
+            description: None,
+            ..parent
+        },
+        _ => ToddEvent {
+            id: 0,
+            title: "Reminder".to_string(),
+            description: Some(format!(
+                "\nError: the event for reminder {} is missing",
+                r.id
+            )),
+            timedate: Local::now().naive_local(),
+            is_recuring: false,
+            owned_by: 0,
+            recurring_by: None,
+            guild_id: r.guild_id,
+            deleted_at: None,
+        },
+    };
+    if late {
+        event.title.push_str(" (late)");
+    }
+    event
+}
+/// Removes a reminder that's been announced or given up on, and finishes its
+/// event or sets the next reminder for a recurring one, in one transaction.
+/// This happens after the announcement, so if the bot dies in between the
+/// reminder is fetched again as overdue: it might be announced twice, but
+/// it's never lost.
+fn finish_reminder(conn: &mut PgConnection, r: &Reminder) {
+    let actor = Actor::bot("reminder");
+    let finished = conn.transaction::<_, ToddError, _>(|conn| {
+        let parent = r.parent(conn);
+        match handle_parentsome(conn, &actor, parent, r.clone()) {
+            Ok(parent) => {
+                if let Some(notes) = parent.description {
+                    info!(reminder = r.id, notes = notes.trim(), "finished reminder");
                 }
             }
+            Err(err) => {
+                error!(
+                    reminder = r.id,
+                    "Error handling announced reminder: {}", err
+                );
+                // It's announced all the same, so it mustn't come round again
+                databaser::delete_reminder_by_id(conn, &actor, r.guild_id, r.id)?;
+            }
         }
-        interval.tick().await;
+        Ok(())
+    });
+    if let Err(err) = finished {
+        error!(
+            reminder = r.id,
+            "Error removing announced reminder: {}", err
+        );
     }
 }
 async fn send_event_message(ctx: &serenity::Context, event: ToddEvent) -> Result<(), Error> {
+    let channel = guilds::announcement_channel(ctx, event.guild_id).await?;
     let message = MessageBuilder::new()
         .push("# ")
         .push(event.title)
@@ -139,49 +328,61 @@ async fn send_event_message(ctx: &serenity::Context, event: ToddEvent) -> Result
             None => "".to_string(),
         })
         .build();
-    // Poise's old version of serenity doesn not have the ChannelId.new() method
-    let channel = ChannelId(var("DEFAULT_CHANNEL")?.parse::<u64>()?);
     channel.say(&ctx.http, message).await?;
     Ok(())
 }
 fn handle_parentsome(
     conn: &mut PgConnection,
+    actor: &Actor,
     parent: Option<ToddEvent>,
     child: Reminder,
-) -> Result<ToddEvent, Error> {
+) -> Result<ToddEvent, ToddError> {
     let mut desc = None;
     let mut desc_vec: String = "".to_string();
     if parent.is_none() {
-        return Err(Error::from("Warning: reminder has no parent."));
+        return Err(ToddError::Internal(format!(
+            "Reminder {} has no parent",
+            child.id
+        )));
     } else if parent.clone().unwrap().id != child.event_id {
-        return Err(Error::from(format!(
-            "Error: parent does not own child. Parent is: {:?}",
-            parent
+        return Err(ToddError::Internal(format!(
+            "Reminder {} does not belong to its parent {:?}",
+            child.id, parent
         )));
     }
 
     let mut parent = parent.unwrap().clone();
-    if let Err(err) = databaser::delete_reminder_by_id(conn, child.id) {
+    if let Err(err) = databaser::delete_reminder_by_id(conn, actor, child.guild_id, child.id) {
+        error!(
+            reminder = child.id,
+            "Error removing announced reminder: {}", err
+        );
         desc_vec.push_str(
             format!(
-                "\nError removing reminder: {:?}\n with Error message: {}
+                "\nError removing reminder {}: {}
 Make sure to manually remove the reminder later",
-                child, err
+                child.id,
+                err.user_message()
             )
             .as_str(),
         )
     }
     if !parent.is_recuring {
-        if let Err(err) = databaser::delete_event_by_id(conn, parent.id) {
+        if let Err(err) = databaser::delete_event_by_id(conn, actor, parent.guild_id, parent.id) {
+            error!(event = parent.id, "Error removing finished event: {}", err);
             desc_vec.push_str("\nWarning: event not recuring but was not deleted");
-            desc_vec.push_str(format!("\ndeletion failure casued by error: {:?}", err).as_str());
+            desc_vec
+                .push_str(format!("\ndeletion failure casued by: {}", err.user_message()).as_str());
             desc_vec.push_str("\nEvent may need to be modified/deleted manually")
         }
     } else {
-        let handled_recurrance = handle_recurrance(conn, parent.clone());
+        let handled_recurrance = handle_recurrance(conn, actor, parent.clone());
         if let Err(err) = handled_recurrance {
+            error!(
+                event = parent.id,
+                "Error setting recurring reminder: {}", err
+            );
             desc_vec.push_str("\nWarning: **recuring reminder failed to set**");
-            desc_vec.push_str(format!("Err msg: {:?}", err).as_str());
         } else if handled_recurrance.as_ref().unwrap().is_none() {
             desc_vec.push_str("\n**Warning**:");
             desc_vec
@@ -206,36 +407,41 @@ Make sure to manually remove the reminder later",
 }
 // remember that connections are established via:
 // databaser::establish_connection();
-fn handle_recurrance(conn: &mut PgConnection, event: ToddEvent) -> Result<Option<Reminder>, Error> {
+fn handle_recurrance(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    event: ToddEvent,
+) -> Result<Option<Reminder>, ToddError> {
     if !event.is_recuring {
         return Ok(None);
     }
     let output = match event.recurring_by {
         Some(0) => {
-            let r = create_daily_reminder(conn, event)?;
+            let r = create_next_reminder(conn, actor, event, 1, 0)?;
             Some(r)
         }
         Some(1) => {
-            let r = create_weekly_reminder(conn, event)?;
+            let r = create_next_reminder(conn, actor, event, 7, 0)?;
             Some(r)
         }
         Some(2) => {
-            let r = create_monthly_reminder(conn, event)?;
+            let r = create_next_reminder(conn, actor, event, 0, 1)?;
             Some(r)
         }
         Some(3) => {
-            let r = create_yearly_reminder(conn, event)?;
+            let r = create_next_reminder(conn, actor, event, 0, 12)?;
             Some(r)
         }
         None => {
-            return Err(Error::from(
-                "Warning: Event is set as recurring but does not have a timeframe",
-            ))
+            return Err(ToddError::Internal(format!(
+                "Event {} is set as recurring but does not have a timeframe",
+                event.id
+            )))
         }
         _ => {
-            return Err(Error::from(format!(
-                "Warning: `recurring_by` set to invalid value: {:?}",
-                event.recurring_by
+            return Err(ToddError::Internal(format!(
+                "Event {} has `recurring_by` set to invalid value: {:?}",
+                event.id, event.recurring_by
             )))
         }
     };
@@ -270,6 +476,8 @@ async fn add(
     arg7: Option<String>,
     arg8: Option<String>,
 ) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let actor = Actor::from_ctx(ctx);
     let mut conn = databaser::establish_connection()?;
     let mut args = vec![];
     let option_args = vec![arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8];
@@ -278,29 +486,37 @@ async fn add(
     }
     let mut created_event: CalendarType = match event_type.to_lowercase().as_str() {
         "event" => add_event(&mut conn, ctx, args)?,
-        "birthday" => add_birthday(&mut conn, args)?.to_calendar(),
-        "reminder" => add_reminder(&mut conn, args)?.to_calendar(),
+        "birthday" => {
+            // Settle who it's for first, so a typo gets a note or a choice
+            if let Some(name) = args.first_mut() {
+                let member = member_commands::resolve_member(ctx, &mut conn, name).await?;
+                *name = member.id.to_string();
+            }
+            add_birthday(&mut conn, &actor, guild, args)?.to_calendar()
+        }
+        "reminder" => add_reminder(&mut conn, &actor, guild, args)?.to_calendar(),
         _ => {
-            return Err(Error::from(
-                "Error parsing type, specify `event` `birthday` or `reminder`",
-            ))
+            return Err(Error::from(ToddError::InvalidInput(
+                "Error parsing type, specify `event` `birthday` or `reminder`".to_string(),
+            )))
         }
     };
     if let CalendarType::Tevent(t) = created_event.clone() {
-        let _reminder = databaser::create_reminder(&mut conn, t.timedate, t.id)?;
+        // Recurring events get theirs from `handle_recurrance`, which skips
+        // occurrences that have already passed
+        if !t.is_recuring {
+            databaser::create_reminder(&mut conn, &actor, guild, t.timedate, t.id)?;
+        }
         // handling recurrance:
         let mut new_desc = "".to_string();
-        let recur = handle_recurrance(&mut conn, t.clone());
+        let recur = handle_recurrance(&mut conn, &actor, t.clone());
         if let Err(err) = recur {
+            error!(event = t.id, "Error setting recurring reminder: {}", err);
             new_desc.push_str(
-                format!(
-                    "
-\n*Error when handling recurrance: **{:?}**
+                "
+\n*Error when handling recurrance*
 
 Event was still created, but reminders might need to be added manually",
-                    err
-                )
-                .as_str(),
             );
         } else if let Some(r) = recur.unwrap() {
             new_desc.push_str(
@@ -331,6 +547,8 @@ with id: {}",
                 is_recuring: t.is_recuring,
                 owned_by: t.owned_by,
                 recurring_by: t.recurring_by,
+                guild_id: t.guild_id,
+                deleted_at: t.deleted_at,
             }
             .to_calendar()
         }
@@ -356,9 +574,13 @@ fn add_event(
     ctx: Context<'_>,
     input: Vec<String>,
 ) -> Result<CalendarType, Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let actor = Actor::from_ctx(ctx);
     let new_event = parse_event_args(input.clone(), ctx)?;
     let mut created_event = databaser::create_event(
         conn,
+        &actor,
+        guild,
         &new_event.title,
         &new_event.description,
         new_event.timedate,
@@ -378,20 +600,26 @@ fn add_event(
     }
     if let Some(s) = option_reminder {
         let mut desc_append = "".to_string();
-        let mut r: Result<Reminder, Error> = Err(Error::from(""));
-        let tr = parse_reminder(conn, vec![created_event.title.clone(), s.to_string()]);
+        let mut r: Result<Reminder, ToddError> = Err(ToddError::Internal(String::new()));
+        let tr = parse_reminder(
+            conn,
+            guild,
+            vec![created_event.title.clone(), s.to_string()],
+        );
         if let Err(err) = tr {
             desc_append.push_str(
                 format!(
-                    "\nEvent's reminder could not be parsed with err:
-{:?}\n The event was still created successfully",
-                    err
+                    "\nEvent's reminder could not be parsed: {}
+The event was still created successfully",
+                    err.user_message()
                 )
                 .as_str(),
             )
         } else {
             r = databaser::create_reminder(
                 conn,
+                &actor,
+                guild,
                 tr.as_ref().unwrap().time_before,
                 tr.as_ref().unwrap().event_id,
             )
@@ -418,9 +646,9 @@ struct TempNewEvent {
     owned_by: i64,
     recurring_by: Option<i16>,
 }
-fn parse_event_args(input: Vec<String>, ctx: Context<'_>) -> Result<TempNewEvent, Error> {
+fn parse_event_args(input: Vec<String>, ctx: Context<'_>) -> Result<TempNewEvent, ToddError> {
     let mut description_in = None;
-    let title = input.get(0).map_or("", |s| s.as_str()).to_string();
+    let title = input.first().map_or("", |s| s.as_str()).to_string();
     for (i, s) in input.iter().enumerate() {
         match s.as_str() {
             "description:" | "--description" | "-d" | "description" => {
@@ -436,7 +664,11 @@ fn parse_event_args(input: Vec<String>, ctx: Context<'_>) -> Result<TempNewEvent
     };
     let timedate = match input.last() {
         Some(s) => parse_timedate(s.as_str())?,
-        None => return Err(Error::from("need at least 1 arg")),
+        None => {
+            return Err(ToddError::InvalidInput(
+                "Error: need at least 1 arg".to_string(),
+            ))
+        }
     };
     let is_recuring = input
         .iter()
@@ -486,10 +718,17 @@ fn check_recurrance(input: Vec<String>) -> Option<i16> {
     }
 }
 
-fn add_birthday(conn: &mut PgConnection, input: Vec<String>) -> Result<ToddEvent, Error> {
-    let new_event = parse_birthday_args(conn, input.clone())?;
+fn add_birthday(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    input: Vec<String>,
+) -> Result<ToddEvent, ToddError> {
+    let new_event = parse_birthday_args(conn, guild, input.clone())?;
     let created_event = databaser::create_event(
         conn,
+        actor,
+        guild,
         &new_event.title,
         &new_event.description,
         new_event.timedate,
@@ -499,15 +738,23 @@ fn add_birthday(conn: &mut PgConnection, input: Vec<String>) -> Result<ToddEvent
     )?;
     Ok(created_event)
 }
-fn parse_birthday_args(conn: &mut PgConnection, input: Vec<String>) -> Result<TempNewEvent, Error> {
+fn parse_birthday_args(
+    conn: &mut PgConnection,
+    guild: i64,
+    input: Vec<String>,
+) -> Result<TempNewEvent, ToddError> {
     if input.is_empty() {
-        return Err(Error::from("Error: not enough args"));
+        return Err(ToddError::InvalidInput(
+            "Error: not enough args".to_string(),
+        ));
     }
     if input[0].is_empty() {
-        return Err(Error::from("Error: not enough args"));
+        return Err(ToddError::InvalidInput(
+            "Error: not enough args".to_string(),
+        ));
     }
     let parsed_member = todd_commands::parse_member_or_return_lowercase(&input[0]);
-    let owner_member = databaser::get_member(conn, &parsed_member)?;
+    let owner_member = databaser::get_member(conn, guild, &parsed_member)?;
     let output = TempNewEvent {
         title: "Birthday".to_string(),
         description: format!(
@@ -516,84 +763,60 @@ fn parse_birthday_args(conn: &mut PgConnection, input: Vec<String>) -> Result<Te
         ),
         timedate: match input.last() {
             Some(s) => parse_timedate(s.as_str())?,
-            None => return Err(Error::from("need at least 1 arg")),
+            None => {
+                return Err(ToddError::InvalidInput(
+                    "Error: need at least 1 arg".to_string(),
+                ))
+            }
         },
         is_recuring: true,
-        owned_by: owner_member.id as i64,
+        owned_by: owner_member.id,
         recurring_by: Some(3), // yearly
     };
     Ok(output)
 }
 
-fn create_yearly_reminder(conn: &mut PgConnection, event: ToddEvent) -> Result<Reminder, Error> {
-    let current_datetime = Local::now().naive_local();
-    let diff: i32 = current_datetime.month() as i32 - event.timedate.month() as i32;
-    let new_time = if diff.is_positive()
-        || (diff == 0 && event.timedate.day() <= current_datetime.day())
-    // TODO:
-    // Reminders are not being created at the right year...
-    {
-        event.timedate.with_year(current_datetime.year() + 1)
-    } else {
-        event.timedate.with_year(current_datetime.year())
+/// The first time after `now` that an event at `timedate` comes round again,
+/// `days` days or `months` months apart. Reminders for recurring events go
+/// here, so they never land in the past and get announced as late.
+fn next_occurrence(
+    timedate: NaiveDateTime,
+    days: i64,
+    months: u32,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    if timedate > now {
+        return Some(timedate);
     }
-    .ok_or_else(|| Error::from("New datetime out of scope"))?;
-
-    // Expected End:
-    let new_reminder = databaser::create_reminder(conn, new_time, event.id)?;
-    Ok(new_reminder)
-}
-fn create_monthly_reminder(conn: &mut PgConnection, event: ToddEvent) -> Result<Reminder, Error> {
-    let current = Local::now().naive_local();
-    let timedate = event.timedate;
-    let diff: i64 = { current.month0() as i64 - timedate.month0() as i64 };
-    let new_time = if diff == 0 && timedate.day0() >= current.day0() || (diff.is_negative()) {
-        timedate.with_month0(current.month0())
-    } else {
-        timedate.with_month0(current.month0() + 1)
+    if months == 0 {
+        let periods = (now - timedate).num_days() / days + 1;
+        let mut next = timedate + chrono::Duration::days(periods * days);
+        while next <= now {
+            next += chrono::Duration::days(days);
+        }
+        return Some(next);
     }
-    .and_then(|dt| dt.with_year(current.year()))
-    .ok_or_else(|| Error::from("Invalid timedate"))?;
-
-    let new_reminder = databaser::create_reminder(conn, new_time, event.id)?;
-
-    Ok(new_reminder)
-}
-fn create_weekly_reminder(conn: &mut PgConnection, event: ToddEvent) -> Result<Reminder, Error> {
-    let current = Local::now().naive_local();
-    let timedate = event.timedate;
-    let diff: i64 = {
-        current.weekday().num_days_from_sunday() as i64
-            - timedate.weekday().num_days_from_sunday() as i64
-    };
-    let new_time = if diff == 0
-        && timedate.num_seconds_from_midnight() > current.num_seconds_from_midnight()
-    {
-        current
-    } else {
-        current - chrono::Duration::days(diff + 7)
+    // Counted from the event itself so the 31st doesn't drift to the 28th
+    let mut periods = 1;
+    loop {
+        let next = timedate.checked_add_months(chrono::Months::new(periods * months))?;
+        if next > now {
+            return Some(next);
+        }
+        periods += 1;
     }
-    .with_hour(timedate.hour())
-    .and_then(|dt| dt.with_minute(timedate.minute()))
-    .and_then(|dt| dt.with_second(timedate.second()))
-    .ok_or_else(|| Error::from("Invalid time components"))?;
-
-    let new_reminder = databaser::create_reminder(conn, new_time, event.id)?;
-    Ok(new_reminder)
 }
-fn create_daily_reminder(conn: &mut PgConnection, event: ToddEvent) -> Result<Reminder, Error> {
-    let current = Local::now().naive_local();
-    let timedate = event.timedate;
-    let diff: i64 = {
-        current.num_seconds_from_midnight() as i64 - timedate.num_seconds_from_midnight() as i64
-    };
-    let new_time = if !diff.is_negative() {
-        current - chrono::Duration::seconds(diff)
-    } else {
-        current - chrono::Duration::seconds(diff + 86400)
-    };
-
-    let new_reminder = databaser::create_reminder(conn, new_time, event.id)?;
+fn create_next_reminder(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    event: ToddEvent,
+    days: i64,
+    months: u32,
+) -> Result<Reminder, ToddError> {
+    let now = settings::guild_now(conn, event.guild_id)?;
+    let new_time = next_occurrence(event.timedate, days, months, now)
+        .ok_or_else(|| ToddError::Internal("New datetime out of scope".to_string()))?;
+    let new_reminder = databaser::create_reminder(conn, actor, event.guild_id, new_time, event.id)?;
     Ok(new_reminder)
 }
 
@@ -601,29 +824,50 @@ struct TempNewReminder {
     time_before: NaiveDateTime,
     event_id: i32,
 }
-fn add_reminder(conn: &mut PgConnection, input: Vec<String>) -> Result<Reminder, Error> {
-    let new_reminder = parse_reminder(conn, input.clone())?;
-    let created_reminder =
-        databaser::create_reminder(conn, new_reminder.time_before, new_reminder.event_id)?;
+fn add_reminder(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    input: Vec<String>,
+) -> Result<Reminder, ToddError> {
+    let new_reminder = parse_reminder(conn, guild, input.clone())?;
+    let created_reminder = databaser::create_reminder(
+        conn,
+        actor,
+        guild,
+        new_reminder.time_before,
+        new_reminder.event_id,
+    )?;
     Ok(created_reminder)
 }
-fn parse_reminder(conn: &mut PgConnection, input: Vec<String>) -> Result<TempNewReminder, Error> {
+fn parse_reminder(
+    conn: &mut PgConnection,
+    guild: i64,
+    input: Vec<String>,
+) -> Result<TempNewReminder, ToddError> {
     if input.is_empty() {
-        return Err(Error::from("Error: need at least 1 arg"));
+        return Err(ToddError::InvalidInput(
+            "Error: need at least 1 arg".to_string(),
+        ));
     }
     let last_input = if let Some(s) = input.last() {
         s.as_str()
     } else {
-        return Err(Error::from("need at least 1 arg"));
+        return Err(ToddError::InvalidInput(
+            "Error: need at least 1 arg".to_string(),
+        ));
     };
     let event_title = if input[0] == "for" {
         &input[1]
     } else {
         &input[0]
     };
-    let event = databaser::get_event_by_title(conn, event_title)?;
+    let event = databaser::get_event_by_title(conn, guild, event_title)?;
     if event.is_empty() {
-        return Err(Error::from("no events found"));
+        return Err(ToddError::NotFound(format!(
+            "Error: event *{}* not found",
+            event_title
+        )));
     }
     let output = TempNewReminder {
         time_before: if let Ok(p) = parse_timedate(last_input) {
@@ -632,11 +876,18 @@ fn parse_reminder(conn: &mut PgConnection, input: Vec<String>) -> Result<TempNew
             let strings = if let Some(t) = s.trim().split_once(' ') {
                 t
             } else {
-                return Err(Error::from("Must specify n timeunits before"));
+                return Err(ToddError::InvalidInput(
+                    "Error: must specify n timeunits before".to_string(),
+                ));
             };
-            let seconds_before = strings.0.parse::<i64>()? * {
-                if strings.0.parse::<i64>()?.is_negative() {
-                    return Err(Error::from("time must be positive"));
+            let count = strings.0.parse::<i64>().map_err(|_| {
+                ToddError::InvalidInput(format!("Error: *{}* is not a number", strings.0))
+            })?;
+            let seconds_before = count * {
+                if count.is_negative() {
+                    return Err(ToddError::InvalidInput(
+                        "Error: time must be positive".to_string(),
+                    ));
                 } else if strings.1 == "seconds" {
                     1
                 } else if strings.1 == "minutes" {
@@ -652,24 +903,30 @@ fn parse_reminder(conn: &mut PgConnection, input: Vec<String>) -> Result<TempNew
                 } else if strings.1 == "years" {
                     31449600
                 } else {
-                    return Err(Error::from("must specify unit of time"));
+                    return Err(ToddError::InvalidInput(
+                        "Error: must specify unit of time".to_string(),
+                    ));
                 }
             };
             let new_timestamp = event[0].timedate.timestamp() - seconds_before;
             if let Some(t) = NaiveDateTime::from_timestamp_opt(new_timestamp, 0) {
                 t
             } else {
-                return Err(Error::from("Error: invalid timestamp"));
+                return Err(ToddError::InvalidInput(
+                    "Error: that's too long before the event".to_string(),
+                ));
             }
         } else {
-            println!("Current inputs: {:?}\n", input);
-            return Err(Error::from("Error: wrong format"));
+            tracing::debug!("Could not parse reminder input: {:?}", input);
+            return Err(ToddError::InvalidInput(
+                "Error: wrong format, give a date or a time before the event".to_string(),
+            ));
         },
         event_id: event[0].id,
     };
     Ok(output)
 }
-fn parse_timedate(input: &str) -> Result<NaiveDateTime, Error> {
+fn parse_timedate(input: &str) -> Result<NaiveDateTime, ToddError> {
     let formats_to_try: Vec<&str> = vec!["%D %I:%M %P", "%D %I:%M %p", "%D %R", "%D"];
     for f in formats_to_try {
         if let Ok(d) = NaiveDateTime::parse_from_str(input, f) {
@@ -678,20 +935,50 @@ fn parse_timedate(input: &str) -> Result<NaiveDateTime, Error> {
             return Ok(n.and_hms_opt(7, 0, 0).unwrap());
         }
     }
-    Err(Error::from("Failed to parse input into a datetime"))
+    Err(ToddError::InvalidInput(format!(
+        "Error: *{}* isn't a date like 06/08/23 12:34 PM",
+        input
+    )))
 }
+/// Events and birthdays can be removed by whoever they belong to, reminders by
+/// whoever their event belongs to, and anything by moderators.
 #[poise::command(prefix_command/*, member_cooldown = 30*/)]
 async fn remove(ctx: Context<'_>, event_type: String, event: String) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let actor = Actor::from_ctx(ctx);
     let mut conn = databaser::establish_connection()?;
 
     let removed_event: CalendarType = match event_type.to_lowercase().as_str() {
-        "event" => remove_event(&mut conn, event)?.to_calendar(),
-        "birthday" => remove_birthday(&mut conn, event)?.to_calendar(),
-        "reminder" => remove_reminder(&mut conn, event)?.to_calendar(),
+        "event" => {
+            let e = find_event(&mut conn, guild, event)?;
+            let what = format!("the event *{}*", e.title);
+            permissions::require_owner(ctx, &[e.owned_by], &what).await?;
+            databaser::delete_event_by_id(&mut conn, &actor, guild, e.id)?;
+            e.to_calendar()
+        }
+        "birthday" => {
+            let member = member_commands::resolve_member(ctx, &mut conn, &event).await?;
+            let e = databaser::get_birthday(&mut conn, &member)?;
+            permissions::require_owner(ctx, &[e.owned_by], "that birthday").await?;
+            databaser::delete_event_by_id(&mut conn, &actor, guild, e.id)?;
+            e.to_calendar()
+        }
+        "reminder" => {
+            let r = find_reminder(&mut conn, guild, event)?;
+            let owners: Vec<i64> = r
+                .parent(&mut conn)
+                .map(|p| p.owned_by)
+                .into_iter()
+                .collect();
+            let what = format!("reminder {}", r.id);
+            permissions::require_owner(ctx, &owners, &what).await?;
+            databaser::delete_reminder_by_id(&mut conn, &actor, guild, r.id)?;
+            r.to_calendar()
+        }
         _ => {
-            return Err(Error::from(
-                "Error parsing type, specify `event` `birthday` or `reminder`",
-            ))
+            return Err(Error::from(ToddError::InvalidInput(
+                "Error parsing type, specify `event` `birthday` or `reminder`".to_string(),
+            )))
         }
     };
 
@@ -705,51 +992,60 @@ async fn remove(ctx: Context<'_>, event_type: String, event: String) -> Result<(
 
     Ok(())
 }
-fn remove_event(conn: &mut PgConnection, input: String) -> Result<ToddEvent, Error> {
+fn find_event(conn: &mut PgConnection, guild: i64, input: String) -> Result<ToddEvent, ToddError> {
     if input.is_empty() {
-        return Err(Error::from("Error: not enough args"));
+        return Err(ToddError::InvalidInput(
+            "Error: not enough args".to_string(),
+        ));
     }
-    let event = if let Ok(e) = databaser::get_event(conn, &input) {
+    let event = if let Ok(e) = databaser::get_event(conn, guild, &input) {
         if e.is_empty() {
-            return Err(Error::from(format!("Error: event *{}* not found", &input)));
+            return Err(ToddError::NotFound(format!(
+                "Error: event *{}* not found",
+                &input
+            )));
         } else if e.len() != 1 {
-            return Err(Error::from(format!(
-                "Error: more than one event found: {:?}
+            return Err(ToddError::Ambiguous(format!(
+                "Error: more than one event found: {}
 try removing event by it's `id`",
-                e
+                e.iter()
+                    .map(|e| format!("*{}* ({})", e.title, e.id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
             )));
         } else {
             e[0].clone()
         }
     } else {
-        return Err(Error::from(format!("Error: event *{}* not found", &input)));
+        return Err(ToddError::NotFound(format!(
+            "Error: event *{}* not found",
+            &input
+        )));
     };
-    databaser::delete_event_by_id(conn, event.id)?;
-    Ok(event)
-}
-fn remove_birthday(conn: &mut PgConnection, input: String) -> Result<ToddEvent, Error> {
-    if input.is_empty() {
-        return Err(Error::from("Error: not enough args"));
-    }
-    let poise_member = todd_commands::parse_member_or_return_lowercase(&input);
-    let member = databaser::get_member(conn, &poise_member)?;
-    let event = databaser::get_birthday(conn, member)?;
-    databaser::delete_event_by_id(conn, event.id)?;
     Ok(event)
 }
-fn remove_reminder(conn: &mut PgConnection, input: String) -> Result<Reminder, Error> {
+fn find_reminder(
+    conn: &mut PgConnection,
+    guild: i64,
+    input: String,
+) -> Result<Reminder, ToddError> {
     if input.is_empty() {
-        return Err(Error::from("Error: not enough args"));
+        return Err(ToddError::InvalidInput(
+            "Error: not enough args".to_string(),
+        ));
     }
     let output = databaser::get_reminder_from_id(
         conn,
+        guild,
         if let Ok(i) = input.parse::<i32>() {
             i
         } else {
-            return Err(Error::from(format!("Error: *{}* is not an id", &input)));
+            return Err(ToddError::InvalidInput(format!(
+                "Error: *{}* is not an id",
+                &input
+            )));
         },
     )?;
-    databaser::delete_reminder_by_id(conn, output.id)?;
 
     Ok(output)
 }
@@ -759,29 +1055,28 @@ fn remove_reminder(conn: &mut PgConnection, input: String) -> Result<Reminder, E
     subcommands("events", "reminders")
 )]
 async fn list(ctx: Context<'_>, input: String) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
     let mut conn = databaser::establish_connection()?;
-    let mut body = "".to_string();
-    let parent = databaser::get_event(&mut conn, &input)?;
+    let mut sections = vec![];
+    let parent = databaser::get_event(&mut conn, guild, &input)?;
     for e in parent {
-        let reminders_vec = databaser::get_reminders_from_event(&mut conn, &e);
-        body.push_str(
-            format!(
-                "\n### Event **{}:**\n```{:?}```{}'s reminders:\n```{:?}```",
-                e.title, e, e.title, reminders_vec
-            )
-            .as_str(),
-        )
+        let reminders_vec = databaser::get_reminders_from_event(&mut conn, guild, &e);
+        sections.push(format!(
+            "### Event **{}:**\n```{:?}```{}'s reminders:\n```{:?}```",
+            e.title, e, e.title, reminders_vec
+        ))
     }
-    ctx.reply(body).await?;
+    pages::reply_pages(ctx, "Events", &sections, "No events match that").await?;
     Ok(())
 }
 #[poise::command(prefix_command, member_cooldown = 30)]
 async fn events(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
     let mut conn = databaser::establish_connection()?;
-    let mut body = "".to_string();
-    let v = databaser::get_all_events(&mut conn)?;
+    let mut sections = vec![];
+    let v = databaser::get_all_events(&mut conn, guild)?;
     for e in v {
-        let owner = databaser::get_member(&mut conn, e.owned_by.to_string().as_str());
+        let owner = databaser::get_member(&mut conn, guild, e.owned_by.to_string().as_str());
         let mut check: Result<(), ()> = Err(());
         if owner.is_ok() {
             check = Ok(())
@@ -803,26 +1098,24 @@ async fn events(ctx: Context<'_>) -> Result<(), Error> {
             Ok(_) => owner.unwrap().primary_name,
             _ => "unknown".to_string(),
         };
-        body.push_str(
-            format!(
-                "\n## {}\n- id: {}\n- owned by: {}\n- is recurring?: {}",
-                e.title, e.id, owner, recurring
-            )
-            .as_str(),
-        )
+        sections.push(format!(
+            "## {}\n- id: {}\n- owned by: {}\n- is recurring?: {}",
+            e.title, e.id, owner, recurring
+        ))
     }
-    ctx.reply(body).await?;
+    pages::reply_pages(ctx, "Events", &sections, "No events yet").await?;
     Ok(())
 }
 #[poise::command(prefix_command, member_cooldown = 30)]
 async fn reminders(ctx: Context<'_>, input: Option<String>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
     let mut conn = databaser::establish_connection()?;
-    let mut body = "".to_string();
+    let mut sections = vec![];
     let mut v = vec![];
     if let Some(s) = input {
-        if let Ok(events) = databaser::get_event(&mut conn, &s) {
+        if let Ok(events) = databaser::get_event(&mut conn, guild, &s) {
             for e in events {
-                if let Ok(r) = databaser::get_reminders_from_event(&mut conn, &e) {
+                if let Ok(r) = databaser::get_reminders_from_event(&mut conn, guild, &e) {
                     for reminder in r {
                         v.push(reminder)
                     }
@@ -830,7 +1123,7 @@ async fn reminders(ctx: Context<'_>, input: Option<String>) -> Result<(), Error>
             }
         }
     } else {
-        let reminders = databaser::get_all_reminders(&mut conn)?;
+        let reminders = databaser::get_all_reminders(&mut conn, guild)?;
         for r in reminders {
             v.push(r)
         }
@@ -844,18 +1137,15 @@ async fn reminders(ctx: Context<'_>, input: Option<String>) -> Result<(), Error>
                 r.clone().to_calendar()
             }
         };
-        body.push_str(
-            format!(
-                "\n### {}\n- {:?}\n- id: {}",
-                parent.title(),
-                r.time_before,
-                r.id
-            )
-            .as_str(),
-        )
+        sections.push(format!(
+            "### {}\n- {:?}\n- id: {}",
+            parent.title(),
+            r.time_before,
+            r.id
+        ))
     }
 
-    ctx.reply(body).await?;
+    pages::reply_pages(ctx, "Reminders", &sections, "No reminders").await?;
     Ok(())
 }
 
@@ -907,10 +1197,13 @@ mod calendar_tests {
             vec!["Sample Event".to_string(), "15 foo bar before".to_string()],
             vec!["Sample Event".to_string(), "-15 minutes before".to_string()],
         ];
+        let guild = guilds::home_guild_id()?;
         let mut conn = databaser::establish_connection()?;
-        let member = databaser::get_member(&mut conn, "paddy")?;
+        let member = databaser::get_member(&mut conn, guild, "paddy")?;
         let sample_event = databaser::create_event(
             &mut conn,
+            &Actor::bot("test"),
+            guild,
             "Sample Event",
             "Foo Bar",
             NaiveDateTime::new(
@@ -919,29 +1212,41 @@ mod calendar_tests {
             ),
             false,
             member.id,
+            None,
         )?;
 
         for input in valid_inputs {
-            if let Err(e) = parse_reminder(&mut conn, input.clone()) {
-                databaser::delete_event_by_id(&mut conn, sample_event.id)?;
-                return Err(e);
+            if let Err(e) = parse_reminder(&mut conn, guild, input.clone()) {
+                databaser::delete_event_by_id(
+                    &mut conn,
+                    &Actor::bot("test"),
+                    guild,
+                    sample_event.id,
+                )?;
+                return Err(e.into());
             }
         }
         for input in invalid_inputs {
-            if let Err(e) = parse_reminder(&mut conn, input.clone()) {
-            } else {
-                databaser::delete_event_by_id(&mut conn, sample_event.id)?;
+            if parse_reminder(&mut conn, guild, input.clone()).is_ok() {
+                databaser::delete_event_by_id(
+                    &mut conn,
+                    &Actor::bot("test"),
+                    guild,
+                    sample_event.id,
+                )?;
                 return Err(Error::from(format!("Failed for input: {:?}", input)));
             }
         }
 
-        databaser::delete_event_by_id(&mut conn, sample_event.id)?;
+        databaser::delete_event_by_id(&mut conn, &Actor::bot("test"), guild, sample_event.id)?;
         Ok(())
     }
     #[test]
     fn test_add_birthday() -> Result<(), Error> {
+        let guild = guilds::home_guild_id()?;
         let mut conn = databaser::establish_connection()?;
-        let sample_member = databaser::create_member(&mut conn, 123, "sample", false)?;
+        let sample_member =
+            databaser::create_member(&mut conn, &Actor::bot("test"), guild, 123, "sample", false)?;
         println!("sample member: {:?}", sample_member);
         let valid_inputs = vec![
             vec!["Sample".to_string(), "12/01/99".to_string()],
@@ -955,25 +1260,197 @@ mod calendar_tests {
             vec!["Sample".to_string(), "Foo".to_string()],
         ];
         for input in valid_inputs {
-            if let Err(e) = parse_birthday_args(&mut conn, input.clone()) {
+            if let Err(e) = parse_birthday_args(&mut conn, guild, input.clone()) {
                 println!("Failed to parse valid input: {:?}", input);
-                databaser::remove_member(&mut conn, 123)?;
+                databaser::remove_member_and_data(
+                    &mut conn,
+                    &Actor::bot("test"),
+                    &sample_member,
+                    None,
+                )?;
                 println!("Returning Err:");
-                return Err(e);
+                return Err(e.into());
             }
         }
         for input in invalid_inputs {
-            if let Err(_) = parse_birthday_args(&mut conn, input.clone()) {
-            } else {
+            if parse_birthday_args(&mut conn, guild, input.clone()).is_ok() {
                 println!("Failed to parse invalid input: {:?}", input);
-                databaser::remove_member(&mut conn, 123)?;
+                databaser::remove_member_and_data(
+                    &mut conn,
+                    &Actor::bot("test"),
+                    &sample_member,
+                    None,
+                )?;
                 println!("Returning Err:");
                 return Err(Error::from(format!("Invalid Input failed: {:?}", input)));
             }
         }
-        databaser::remove_member(&mut conn, 123)?;
+        databaser::remove_member_and_data(&mut conn, &Actor::bot("test"), &sample_member, None)?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_failed_reminders_are_sent_next_tick() -> Result<(), Error> {
+        let guild = guilds::home_guild_id()?;
+        let mut conn = databaser::establish_connection()?;
+        let member = databaser::get_member(&mut conn, guild, "paddy")?;
+        let actor = Actor::bot("test");
+        let now = Local::now().naive_local();
+        let event = databaser::create_event(
+            &mut conn,
+            &actor,
+            guild,
+            "Unsent Event",
+            "Foo Bar",
+            now + chrono::Duration::days(1),
+            false,
+            member.id,
+            None,
+        )?;
+        let reminder = databaser::create_reminder(&mut conn, &actor, guild, now, event.id)?;
+        let watched = vec![reminder.clone()];
+
+        // Discord is down for the first tick
+        let done = announce_due(
+            &mut conn,
+            &watched,
+            |_| now,
+            |_| async { Err(Error::from("Discord is down")) },
+        )
+        .await;
+        assert!(done.is_empty());
+        let remaining = databaser::get_reminders_from_event(&mut conn, guild, &event)?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].attempts, 1);
+        // Even after a restart, it's fetched again
+        let fetched = fetch_reminders().await?;
+        assert!(fetched.iter().any(|f| f.id == reminder.id));
+
+        let mut sent = vec![];
+        let next_tick = now + chrono::Duration::minutes(2);
+        let done = announce_due(
+            &mut conn,
+            &watched,
+            |_| next_tick,
+            |event| {
+                sent.push(event);
+                async { Ok(()) }
+            },
+        )
+        .await;
+        assert_eq!(done, [reminder.id]);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].title, "Unsent Event (late)");
+        // The event was a one off, so it's finished along with the reminder
+        assert!(databaser::get_event_by_id(&mut conn, event.guild_id, event.id).is_err());
+        let fetched = fetch_reminders().await?;
+        assert!(!fetched.iter().any(|f| f.id == reminder.id));
         Ok(())
     }
+    #[tokio::test]
+    async fn test_reminders_are_given_up_on() -> Result<(), Error> {
+        use crate::schema::reminders;
+        let guild = guilds::home_guild_id()?;
+        let mut conn = databaser::establish_connection()?;
+        let member = databaser::get_member(&mut conn, guild, "paddy")?;
+        let actor = Actor::bot("test");
+        let now = Local::now().naive_local();
+        let event = databaser::create_event(
+            &mut conn,
+            &actor,
+            guild,
+            "Unsendable Event",
+            "Foo Bar",
+            now + chrono::Duration::days(1),
+            false,
+            member.id,
+            None,
+        )?;
+        let reminder = databaser::create_reminder(&mut conn, &actor, guild, now, event.id)?;
+        // Every tick but the last has failed already
+        diesel::update(reminders::table.find(reminder.id))
+            .set(reminders::attempts.eq(MAX_REMINDER_ATTEMPTS - 1))
+            .execute(&mut conn)?;
+
+        let done = announce_due(
+            &mut conn,
+            std::slice::from_ref(&reminder),
+            |_| now,
+            |_| async { Err(Error::from("Missing Access")) },
+        )
+        .await;
+        assert_eq!(done, [reminder.id]);
+        let fetched = fetch_reminders().await?;
+        assert!(!fetched.iter().any(|f| f.id == reminder.id));
+        Ok(())
+    }
+    #[test]
+    fn test_reminders_are_due_in_their_guilds_time() -> Result<(), Error> {
+        use crate::models::UpdateGuildSettings;
+        use crate::schema::{audit_log, guild_settings};
+        let guild = -41;
+        let mut conn = databaser::establish_connection()?;
+        let changes = UpdateGuildSettings {
+            timezone: Some(Some("+14:00".to_string())),
+            ..Default::default()
+        };
+        databaser::update_guild_settings(&mut conn, &Actor::bot("test"), guild, &changes)?;
+        let due_at = Utc::now().naive_utc() + chrono::Duration::hours(14);
+        let reminder = Reminder {
+            id: 0,
+            time_before: due_at,
+            event_id: 0,
+            guild_id: guild,
+            deleted_at: None,
+            attempts: 0,
+        };
+        let now = guild_times(&mut conn, &[reminder]);
+
+        diesel::delete(audit_log::table.filter(audit_log::guild_id.eq(guild)))
+            .execute(&mut conn)?;
+        diesel::delete(guild_settings::table.find(guild)).execute(&mut conn)?;
+        assert!(is_due(due_at, now[&guild]));
+        assert!(!is_late(due_at, now[&guild]));
+        Ok(())
+    }
+    #[test]
+    fn test_next_occurrence() {
+        let at = |y, m, d, h| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, 0, 0)
+                .unwrap()
+        };
+        let now = at(2026, 10, 19, 12);
+        // Daily, before and after today's time
+        assert_eq!(
+            next_occurrence(at(2026, 1, 1, 9), 1, 0, now),
+            Some(at(2026, 10, 20, 9))
+        );
+        assert_eq!(
+            next_occurrence(at(2026, 1, 1, 15), 1, 0, now),
+            Some(at(2026, 10, 19, 15))
+        );
+        // Weekly, 2026-10-12 was a Monday
+        assert_eq!(
+            next_occurrence(at(2026, 10, 12, 12), 7, 0, now),
+            Some(at(2026, 10, 26, 12))
+        );
+        // Monthly from the 31st doesn't drift
+        assert_eq!(
+            next_occurrence(at(2026, 8, 31, 9), 0, 1, now),
+            Some(at(2026, 10, 31, 9))
+        );
+        // A birthday
+        assert_eq!(
+            next_occurrence(at(1999, 12, 1, 0), 0, 12, now),
+            Some(at(2026, 12, 1, 0))
+        );
+        // Not happened yet
+        assert_eq!(
+            next_occurrence(at(2027, 1, 1, 0), 0, 12, now),
+            Some(at(2027, 1, 1, 0))
+        );
+    }
     // #[test]
     // fn test_yearly_recursive_reminders() -> Result<(), Error> {
     //     let current: NaiveDateTime = {
@@ -0,0 +1,76 @@
+// cli.rs
+
+use crate::audit::Actor;
+use crate::config;
+use crate::databaser;
+use crate::guilds;
+use crate::quote_commands;
+use crate::seeder;
+use crate::Error;
+use std::path::Path;
+
+const USAGE: &str =
+    "usage: todd-bot [export-quotes [dir] | import-legacy-quotes [dir] | seed [file]]";
+
+/// Runs a one-off maintenance command if one was given on the command line.
+/// Returns `None` when there isn't one and the bot should start as usual.
+pub fn run(args: &[String]) -> Option<Result<(), Error>> {
+    let command = args.get(1)?;
+    Some(match command.as_str() {
+        "export-quotes" => export_quotes(
+            args.get(2)
+                .map_or(quote_commands::EXPORT_DIR, |s| s.as_str()),
+        ),
+        "import-legacy-quotes" => import_legacy_quotes(args.get(2).cloned()),
+        "seed" => seed(
+            args.get(2)
+                .map_or(seeder::DEFAULT_SEED_FILE, |s| s.as_str()),
+        ),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(Error::from(format!(
+            "Unknown command `{}`\n{}",
+            command, USAGE
+        ))),
+    })
+}
+
+fn export_quotes(dir: &str) -> Result<(), Error> {
+    let guild = guilds::home_guild_id()?;
+    let mut conn = databaser::establish_connection()?;
+    for (name, count) in quote_commands::export_quotes(&mut conn, guild, Path::new(dir))? {
+        println!("{}: {} quotes", name, count);
+    }
+    Ok(())
+}
+
+fn import_legacy_quotes(dir: Option<String>) -> Result<(), Error> {
+    let dir = match &dir {
+        Some(d) => Path::new(d),
+        None => config::get().legacy_quotes_dir()?,
+    };
+    let guild = guilds::home_guild_id()?;
+    let mut conn = databaser::establish_connection()?;
+    let report = quote_commands::import_legacy_quotes(
+        &mut conn,
+        &Actor::bot("import-legacy-quotes"),
+        guild,
+        dir,
+    )?;
+    println!("{}", report);
+    Ok(())
+}
+
+fn seed(file: &str) -> Result<(), Error> {
+    let seed = seeder::Seed::from_file(Path::new(file))?;
+    let guild = guilds::home_guild_id()?;
+    let mut conn = databaser::establish_connection()?;
+    let report = seeder::seed(&mut conn, &Actor::bot("seed"), guild, &seed)?;
+    println!(
+        "Seeded from {}: {} members and {} nicknames added",
+        file, report.members_added, report.nicknames_added
+    );
+    Ok(())
+}
@@ -0,0 +1,479 @@
+// config.rs
+
+use crate::errors::ToddError;
+use crate::Error;
+use dotenv::dotenv;
+use serde::Deserialize;
+use std::env::var;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+pub const DEFAULT_CONFIG_FILE: &str = "todd.toml";
+const CONFIG_FILE_VAR: &str = "TODD_CONFIG";
+const DEFAULT_GIF_DIR: &str = "data/gifs";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Everything the bot reads from its environment. Loaded from `todd.toml`
+/// (or the file in `TODD_CONFIG`), then overridden by environment variables
+/// and `.env`, so existing `.env` setups keep working without a file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub discord_token: Option<String>,
+    pub database_url: Option<String>,
+    pub bot_id: Option<u64>,
+    pub home_guild_id: Option<i64>,
+    pub sync_members: bool,
+    pub channels: ChannelsConfig,
+    pub paths: PathsConfig,
+    pub logging: LoggingConfig,
+    pub deletion: DeletionConfig,
+    pub http: HttpConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelsConfig {
+    /// Where the home guild's announcements go unless `!config` says otherwise.
+    pub default: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub legacy_quotes_dir: Option<PathBuf>,
+    pub gif_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// A level like `info`, optionally followed by levels for other crates
+    /// like `info,serenity=warn`.
+    pub level: String,
+    pub format: LogFormat,
+    /// Also write logs to files in this directory.
+    pub dir: Option<PathBuf>,
+    /// How often a new log file is started in `dir`.
+    pub rotation: LogRotation,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+            dir: None,
+            rotation: LogRotation::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One readable line per event.
+    #[default]
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("expected pretty or json".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            "never" => Ok(LogRotation::Never),
+            _ => Err("expected hourly, daily or never".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Where the health checks, and `/metrics` with the `metrics` feature,
+    /// are served.
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeletionConfig {
+    /// How long after deleting something `!undo` can bring it back.
+    pub undo_window_minutes: u32,
+    /// How long deleted quotes, events and reminders are kept before they're
+    /// purged for good.
+    pub retention_days: u32,
+}
+
+impl Default for DeletionConfig {
+    fn default() -> Self {
+        DeletionConfig {
+            undo_window_minutes: 60,
+            retention_days: 30,
+        }
+    }
+}
+
+/// Every problem found while loading the config, so they can all be fixed in
+/// one go.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n- {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads and validates the config. `for_bot` also requires what's needed
+    /// to connect to Discord, which the command line tools can do without.
+    pub fn load(for_bot: bool) -> Result<Config, ConfigError> {
+        dotenv().ok();
+        let path = var(CONFIG_FILE_VAR).unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string());
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            // Without a file everything comes from the environment
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(ConfigError(vec![format!("Failed to read {}: {}", path, e)])),
+        };
+        Config::from_sources(&contents, |name| var(name).ok(), for_bot)
+    }
+
+    /// Builds the config from a TOML document and an environment lookup,
+    /// collecting every problem along the way.
+    pub fn from_sources(
+        toml: &str,
+        env: impl Fn(&str) -> Option<String>,
+        for_bot: bool,
+    ) -> Result<Config, ConfigError> {
+        let mut problems = vec![];
+        let mut config: Config = toml::from_str(toml).unwrap_or_else(|e| {
+            problems.push(format!("Failed to parse config file: {}", e));
+            Config::default()
+        });
+
+        override_with(
+            &env,
+            "DISCORD_TOKEN",
+            &mut config.discord_token,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "DATABASE_URL",
+            &mut config.database_url,
+            &mut problems,
+        );
+        override_with(&env, "BOT_ID", &mut config.bot_id, &mut problems);
+        override_with(
+            &env,
+            "HOME_GUILD_ID",
+            &mut config.home_guild_id,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "DEFAULT_CHANNEL",
+            &mut config.channels.default,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "LEGACY_QUOTES_DIR",
+            &mut config.paths.legacy_quotes_dir,
+            &mut problems,
+        );
+        override_with(&env, "GIF_DIR", &mut config.paths.gif_dir, &mut problems);
+        if let Some(v) = env("SYNC_MEMBERS") {
+            config.sync_members = v == "true" || v == "1";
+        }
+        if let Some(v) = env("LOG_LEVEL") {
+            config.logging.level = v;
+        }
+        override_value(
+            &env,
+            "LOG_FORMAT",
+            &mut config.logging.format,
+            &mut problems,
+        );
+        override_with(&env, "LOG_DIR", &mut config.logging.dir, &mut problems);
+        override_value(
+            &env,
+            "LOG_ROTATION",
+            &mut config.logging.rotation,
+            &mut problems,
+        );
+        override_value(
+            &env,
+            "UNDO_WINDOW_MINUTES",
+            &mut config.deletion.undo_window_minutes,
+            &mut problems,
+        );
+        override_value(
+            &env,
+            "DELETED_RETENTION_DAYS",
+            &mut config.deletion.retention_days,
+            &mut problems,
+        );
+        override_with(&env, "HTTP_LISTEN", &mut config.http.listen, &mut problems);
+
+        if config.database_url.is_none() {
+            problems.push("database_url (DATABASE_URL) is not set".to_string());
+        }
+        if for_bot && config.discord_token.is_none() {
+            problems.push("discord_token (DISCORD_TOKEN) is not set".to_string());
+        }
+        if for_bot && config.bot_id.is_none() {
+            problems.push("bot_id (BOT_ID) is not set".to_string());
+        }
+        if !valid_log_level(&config.logging.level) {
+            problems.push(format!(
+                "logging.level *{}* is not a level like info, or like info,serenity=warn",
+                config.logging.level
+            ));
+        }
+        if config.undo_window() > config.deleted_retention() {
+            problems.push(format!(
+                "deletion.undo_window_minutes ({} minutes) is longer than deletion.retention_days ({} days)",
+                config.deletion.undo_window_minutes, config.deletion.retention_days
+            ));
+        }
+        for (name, dir) in [
+            ("paths.legacy_quotes_dir", &config.paths.legacy_quotes_dir),
+            ("paths.gif_dir", &config.paths.gif_dir),
+            ("logging.dir", &config.logging.dir),
+        ] {
+            if let Some(dir) = dir {
+                if !dir.is_dir() {
+                    problems.push(format!("{} {} is not a directory", name, dir.display()));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    pub fn database_url(&self) -> &str {
+        self.database_url.as_deref().unwrap_or_default()
+    }
+
+    pub fn gif_dir(&self) -> &Path {
+        self.paths
+            .gif_dir
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_GIF_DIR))
+    }
+
+    pub fn log_filter(&self) -> EnvFilter {
+        EnvFilter::try_new(&self.logging.level).unwrap_or_else(|_| EnvFilter::new("info"))
+    }
+
+    pub fn undo_window(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.deletion.undo_window_minutes.into())
+    }
+
+    pub fn deleted_retention(&self) -> chrono::Duration {
+        chrono::Duration::days(self.deletion.retention_days.into())
+    }
+
+    pub fn legacy_quotes_dir(&self) -> Result<&Path, Error> {
+        self.paths.legacy_quotes_dir.as_deref().ok_or_else(|| {
+            Error::from(ToddError::NotFound(
+                "Error: paths.legacy_quotes_dir (LEGACY_QUOTES_DIR) is not set".to_string(),
+            ))
+        })
+    }
+}
+
+/// Every comma separated part is a level, or a target and its level like
+/// `serenity=warn`.
+fn valid_log_level(level: &str) -> bool {
+    let parts_valid = level.split(',').all(|part| {
+        let level = part.rsplit('=').next().unwrap_or_default();
+        LevelFilter::from_str(level.trim()).is_ok()
+    });
+    parts_valid && EnvFilter::try_new(level).is_ok()
+}
+
+fn override_with<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    field: &mut Option<T>,
+    problems: &mut Vec<String>,
+) where
+    T::Err: fmt::Display,
+{
+    if let Some(value) = env(name) {
+        match value.parse::<T>() {
+            Ok(v) => *field = Some(v),
+            Err(e) => problems.push(format!("{} *{}* is invalid: {}", name, value, e)),
+        }
+    }
+}
+
+fn override_value<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    field: &mut T,
+    problems: &mut Vec<String>,
+) where
+    T::Err: fmt::Display,
+{
+    let mut value = None;
+    override_with(env, name, &mut value, problems);
+    if let Some(v) = value {
+        *field = v;
+    }
+}
+
+/// Loads the config for the bot and keeps it for the rest of the run.
+pub fn init(for_bot: bool) -> Result<&'static Config, ConfigError> {
+    let config = Config::load(for_bot)?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The config loaded by `init`. Code that runs without `init`, like the
+/// tests, loads it on first use.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load(false).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn test_env_overrides_file() -> Result<(), Error> {
+        let toml = r#"
+            database_url = "postgres://file"
+            bot_id = 1
+            [channels]
+            default = 2
+            [logging]
+            level = "debug"
+        "#;
+        let env = env_from(&[("DISCORD_TOKEN", "token"), ("BOT_ID", "3")]);
+        let config = Config::from_sources(toml, env, true)?;
+        assert_eq!(config.database_url(), "postgres://file");
+        assert_eq!(config.discord_token.as_deref(), Some("token"));
+        assert_eq!(config.bot_id, Some(3));
+        assert_eq!(config.channels.default, Some(2));
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.logging.format, LogFormat::Pretty);
+        assert_eq!(config.gif_dir(), Path::new(DEFAULT_GIF_DIR));
+        assert_eq!(config.undo_window(), chrono::Duration::hours(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let toml = r#"
+            [logging]
+            level = "loud"
+        "#;
+        let env = env_from(&[("BOT_ID", "todd"), ("GIF_DIR", "/no/such/dir")]);
+        let problems = match Config::from_sources(toml, env, true) {
+            Err(ConfigError(p)) => p,
+            Ok(_) => panic!("Invalid config was accepted"),
+        };
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+        assert!(problems[0].starts_with("BOT_ID"));
+        // Only the command line tools can do without Discord
+        assert!(Config::from_sources("database_url = \"x\"", env_from(&[]), false).is_ok());
+    }
+
+    #[test]
+    fn test_undo_window_must_fit_in_retention() {
+        let toml = r#"
+            database_url = "x"
+            [deletion]
+            undo_window_minutes = 2880
+            retention_days = 1
+        "#;
+        let result = Config::from_sources(toml, env_from(&[]), false);
+        assert!(matches!(result, Err(ConfigError(p)) if p[0].contains("undo_window_minutes")));
+        let env = env_from(&[("DELETED_RETENTION_DAYS", "2")]);
+        assert!(Config::from_sources(toml, env, false).is_ok());
+    }
+
+    #[test]
+    fn test_logging_options() {
+        assert!(valid_log_level("warn"));
+        assert!(valid_log_level("info,serenity=warn,todd_bot=debug"));
+        assert!(!valid_log_level("loud"));
+        assert!(!valid_log_level("info,serenity=loud"));
+
+        let toml = r#"
+            database_url = "x"
+            [logging]
+            format = "json"
+            rotation = "hourly"
+        "#;
+        let config = Config::from_sources(toml, env_from(&[]), false).unwrap();
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.logging.rotation, LogRotation::Hourly);
+        let env = env_from(&[("LOG_FORMAT", "pretty"), ("LOG_ROTATION", "weekly")]);
+        let result = Config::from_sources(toml, env, false);
+        assert!(
+            matches!(result, Err(ConfigError(p)) if p.len() == 1 && p[0].starts_with("LOG_ROTATION"))
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let result = Config::from_sources("databse_url = \"x\"", env_from(&[]), false);
+        assert!(matches!(result, Err(ConfigError(p)) if p[0].contains("databse_url")));
+    }
+}
@@ -1,15 +1,25 @@
 // databaser.rs
 
+use crate::audit::{Action, Actor};
+use crate::config;
+use crate::errors::{NameError, ToddError};
+use crate::metrics;
 use crate::models::{
-    NewEvent, NewMember, NewNickname, NewQuote, NewReminder, Nickname, Quote, Reminder,
-    SchlonghouseMember, ToddEvent,
+    AuditEntry, DailyQuote, GuildSettings, NewAuditEntry, NewDailyQuote, NewEvent,
+    NewGuildSettings, NewMember, NewNickname, NewQuote, NewQuoteOfTheDaySettings, NewQuoteVote,
+    NewReminder, NewServedQuote, Nickname, Quote, QuoteOfTheDaySettings, QuoteScore, QuoteVote,
+    Reminder, SchlonghouseMember, ServedQuote, ToddEvent, UpdateGuildSettings,
+    UpdateQuoteOfTheDaySettings,
 };
-use crate::Error;
 use chrono::prelude::*;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use dotenv::dotenv;
-use std::env::var;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
 
 // My perception is that this code could really be cleaned up
 // via fixing the queries. My understanding of the diesel api
@@ -17,162 +27,1681 @@ use std::env::var;
 // `get_member_from_id_name_or_nickname` that would prolly
 // just be called `get_member`
 
-pub fn establish_connection() -> Result<PgConnection, Error> {
-    dotenv().ok();
+pub fn establish_connection() -> Result<PgConnection, ToddError> {
+    let _timer = metrics::query_timer("connect");
+    // Not the URL, it has the password in it
+    Ok(PgConnection::establish(config::get().database_url())?)
+}
+
+/// Records a change to the audit log. Everything below that creates, changes
+/// or deletes someone's data calls this in the same transaction as the
+/// change. Votes and the bot's own bookkeeping (served quotes, quotes of the
+/// day, purging old deleted rows) aren't recorded.
+pub fn record_audit(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    entity_type: &str,
+    entity_id: impl ToString,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<AuditEntry, ToddError> {
+    let action = Action::between(&before, &after);
+    record_audit_action(
+        conn,
+        actor,
+        guild,
+        action,
+        entity_type,
+        entity_id,
+        before,
+        after,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_audit_action(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    action: Action,
+    entity_type: &str,
+    entity_id: impl ToString,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<AuditEntry, ToddError> {
+    let _timer = metrics::query_timer("record_audit_action");
+    use crate::schema::audit_log;
+    let output = diesel::insert_into(audit_log::table)
+        .values(&NewAuditEntry {
+            guild_id: guild,
+            actor_id: actor.user_id,
+            command: &actor.command,
+            action: action.name(),
+            entity_type,
+            entity_id: &entity_id.to_string(),
+            before,
+            after,
+        })
+        .get_result(conn)?;
+    Ok(output)
+}
+
+/// A row as it goes in the audit log.
+fn snapshot(row: &impl Serialize) -> Option<Value> {
+    serde_json::to_value(row).ok()
+}
+
+/// A soft deleted row as it was before it was deleted.
+fn deleted_snapshot(row: &impl Serialize) -> Option<Value> {
+    let mut output = snapshot(row)?;
+    output["deleted_at"] = Value::Null;
+    Some(output)
+}
 
-    let database_url = var("DATABASE_URL")
-        .map_err(|e| Error::from(format!("Failed to load DATABASE_URL: {}", e)))?;
-    PgConnection::establish(&database_url)
-        .map_err(|e| Error::from(format!("Error connecting to {}: {}", database_url, e)))
+/// The newest `limit` entries for `guild`, newest first.
+pub fn get_recent_audit_entries(
+    conn: &mut PgConnection,
+    guild: i64,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, ToddError> {
+    let _timer = metrics::query_timer("get_recent_audit_entries");
+    use crate::schema::audit_log::dsl::*;
+    let output = audit_log
+        .filter(guild_id.eq(guild))
+        .order(id.desc())
+        .limit(limit)
+        .load(conn)?;
+    Ok(output)
+}
+
+/// Everything that happened to one entity, oldest first.
+pub fn get_audit_entries_for(
+    conn: &mut PgConnection,
+    guild: i64,
+    entity: &str,
+    entity_key: &str,
+) -> Result<Vec<AuditEntry>, ToddError> {
+    let _timer = metrics::query_timer("get_audit_entries_for");
+    use crate::schema::audit_log::dsl::*;
+    let output = audit_log
+        .filter(guild_id.eq(guild))
+        .filter(entity_type.eq(entity))
+        .filter(entity_id.eq(entity_key))
+        .order(id)
+        .load(conn)?;
+    Ok(output)
+}
+
+/// Entries that haven't been posted to an audit channel yet, oldest first.
+pub fn get_unmirrored_audit_entries(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, ToddError> {
+    let _timer = metrics::query_timer("get_unmirrored_audit_entries");
+    use crate::schema::audit_log::dsl::*;
+    let output = audit_log
+        .filter(mirrored.eq(false))
+        .order(id)
+        .limit(limit)
+        .load(conn)?;
+    Ok(output)
+}
+
+pub fn mark_audit_entries_mirrored(conn: &mut PgConnection, ids: &[i32]) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("mark_audit_entries_mirrored");
+    use crate::schema::audit_log::dsl::*;
+    diesel::update(audit_log.filter(id.eq_any(ids)))
+        .set(mirrored.eq(true))
+        .execute(conn)?;
+    Ok(())
 }
 
 pub fn create_member(
     conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
     member_id: i64,
     member_primary_name: &str,
     member_is_member: bool,
-) -> Result<SchlonghouseMember, Error> {
+) -> Result<SchlonghouseMember, ToddError> {
+    let _timer = metrics::query_timer("create_member");
     use crate::schema::members;
 
     let new_member = NewMember {
         id: member_id,
         primary_name: member_primary_name,
         is_member: member_is_member,
+        guild_id: guild,
     };
 
-    let output = diesel::insert_into(members::table)
-        .values(&new_member)
-        .get_result(conn)?;
+    conn.transaction(|conn| {
+        if let Ok(existing) = get_member_from_id(conn, guild, member_id) {
+            return Err(ToddError::from(NameError::MemberExists {
+                id: member_id,
+                primary_name: existing.primary_name,
+            }));
+        }
+        check_name_available(conn, guild, member_primary_name, None)?;
+        purge_removed_member(conn, guild, member_id)?;
+        let output: SchlonghouseMember = diesel::insert_into(members::table)
+            .values(&new_member)
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "member",
+            output.id,
+            None,
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
+    .map_err(|e| name_conflict(conn, guild, member_primary_name, None, e))
+}
 
-    Ok(output)
+/// The unique indexes behind `check_name_available`. Another insert can get
+/// in between the check and ours, and then these are what catch it.
+const NAME_INDEXES: [&str; 2] = [
+    "members_lower_primary_name_key",
+    "nicknames_lower_nickname_key",
+];
+
+/// Gives a name that lost that race the error `check_name_available` would
+/// have, instead of an internal one. Anything else is passed through.
+fn name_conflict(
+    conn: &mut PgConnection,
+    guild: i64,
+    name: &str,
+    except_member: Option<i64>,
+    error: ToddError,
+) -> ToddError {
+    match &error {
+        ToddError::Database(DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            info,
+        )) if info
+            .constraint_name()
+            .is_some_and(|c| NAME_INDEXES.contains(&c)) =>
+        {
+            check_name_available(conn, guild, name, except_member)
+                .err()
+                .unwrap_or(error)
+        }
+        _ => error,
+    }
 }
 
-pub fn create_quote(conn: &mut PgConnection, quoted: &str, quote: &str) -> Result<Quote, Error> {
+/// Fails with who owns `name` if it's already a primary name or nickname,
+/// other than one of `except_member`'s own.
+pub fn check_name_available(
+    conn: &mut PgConnection,
+    guild: i64,
+    name: &str,
+    except_member: Option<i64>,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("check_name_available");
+    if let Ok(owner) = get_member_from_primary_name(conn, guild, name) {
+        if Some(owner.id) != except_member {
+            return Err(ToddError::from(NameError::NameTaken {
+                name: name.to_string(),
+                owner: owner.primary_name,
+            }));
+        }
+    }
+    if let Ok(owner) = get_member_from_nickname(conn, guild, name) {
+        if Some(owner.id) != except_member {
+            return Err(ToddError::from(NameError::NicknameTaken {
+                nickname: name.to_string(),
+                owner: owner.primary_name,
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// The actor is recorded as whoever submitted the quote. When the guild has
+/// quote approval on, it waits for a moderator before it's served.
+pub fn create_quote(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    quoted: &str,
+    quote: &str,
+) -> Result<Quote, ToddError> {
+    let _timer = metrics::query_timer("create_quote");
     use crate::schema::quotes;
 
-    let new_quote = NewQuote { quoted, quote };
+    conn.transaction(|conn| {
+        let new_quote = NewQuote {
+            quoted,
+            quote,
+            legacy: false,
+            guild_id: guild,
+            submitted_by: actor.user_id,
+            approved: !get_guild_settings(conn, guild)?.quote_approval,
+        };
+        let output: Quote = diesel::insert_into(quotes::table)
+            .values(&new_quote)
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "quote",
+            output.id,
+            None,
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
+}
+
+/// Looks up the member and adds the quote in one transaction, so the quote
+/// can't be attached to a member that was removed in between.
+pub fn create_quote_for_member(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    member_id: &str,
+    quote: &str,
+) -> Result<(SchlonghouseMember, Quote), ToddError> {
+    let _timer = metrics::query_timer("create_quote_for_member");
+    conn.transaction(|conn| {
+        let member = get_member(conn, guild, member_id)?;
+        let created = create_quote(conn, actor, guild, &member.primary_name, quote)?;
+        Ok((member, created))
+    })
+}
 
-    let output = diesel::insert_into(quotes::table)
-        .values(&new_quote)
-        .get_result(conn)?;
+/// Quotes waiting for a moderator, oldest first.
+pub fn get_pending_quotes(conn: &mut PgConnection, guild: i64) -> Result<Vec<Quote>, ToddError> {
+    let _timer = metrics::query_timer("get_pending_quotes");
+    use crate::schema::quotes::dsl::*;
+    let output = quotes
+        .filter(guild_id.eq(guild))
+        .filter(approved.eq(false))
+        .filter(deleted_at.is_null())
+        .order(id)
+        .load::<Quote>(conn)?;
+    Ok(output)
+}
 
+pub fn approve_quote(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    quote_id: i32,
+) -> Result<Quote, ToddError> {
+    let _timer = metrics::query_timer("approve_quote");
+    use crate::schema::quotes;
+    conn.transaction(|conn| {
+        let output: Quote = diesel::update(
+            quotes::table
+                .filter(quotes::id.eq(quote_id))
+                .filter(quotes::guild_id.eq(guild))
+                .filter(quotes::approved.eq(false))
+                .filter(quotes::deleted_at.is_null()),
+        )
+        .set(quotes::approved.eq(true))
+        .get_result(conn)
+        .optional()?
+        .ok_or_else(|| {
+            ToddError::NotFound(format!(
+                "Error: quote {} isn't waiting for approval",
+                quote_id
+            ))
+        })?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "quote",
+            output.id,
+            Some(json!({ "approved": false })),
+            Some(json!({ "approved": true })),
+        )?;
+        Ok(output)
+    })
+}
+
+pub fn get_all_quotes(conn: &mut PgConnection, guild: i64) -> Result<Vec<Quote>, ToddError> {
+    let _timer = metrics::query_timer("get_all_quotes");
+    use crate::schema::quotes::dsl::*;
+    let output = quotes
+        .filter(guild_id.eq(guild))
+        .filter(legacy.eq(false))
+        .filter(deleted_at.is_null())
+        .filter(approved.eq(true))
+        .order((quoted, id))
+        .load::<Quote>(conn)?;
     Ok(output)
 }
 
 pub fn create_nickname(
     conn: &mut PgConnection,
+    actor: &Actor,
     member: &SchlonghouseMember,
     new_nickname: &str,
-) -> Result<Nickname, Error> {
+) -> Result<Nickname, ToddError> {
+    let _timer = metrics::query_timer("create_nickname");
     use crate::schema::nicknames;
 
-    let new_nickname = NewNickname {
-        nickname: new_nickname,
-        primary_name: member.id,
+    conn.transaction(|conn| {
+        check_name_available(conn, member.guild_id, new_nickname, None)?;
+        let output: Nickname = diesel::insert_into(nicknames::table)
+            .values(&NewNickname {
+                nickname: new_nickname,
+                primary_name: member.id,
+                guild_id: member.guild_id,
+            })
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            member.guild_id,
+            "nickname",
+            output.id,
+            None,
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
+    .map_err(|e| name_conflict(conn, member.guild_id, new_nickname, None, e))
+}
+
+/// Inserts the member unless one with the same id already exists, replacing
+/// a removed one. Returns whether it was inserted.
+pub fn create_member_if_missing(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    member_id: i64,
+    member_primary_name: &str,
+    member_is_member: bool,
+) -> Result<bool, ToddError> {
+    let _timer = metrics::query_timer("create_member_if_missing");
+    use crate::schema::members;
+
+    let new_member = NewMember {
+        id: member_id,
+        primary_name: member_primary_name,
+        is_member: member_is_member,
+        guild_id: guild,
     };
 
-    let output = diesel::insert_into(nicknames::table)
-        .values(&new_nickname)
-        .get_result(conn)?;
+    conn.transaction(|conn| {
+        purge_removed_member(conn, guild, member_id)?;
+        let inserted: Option<SchlonghouseMember> = diesel::insert_into(members::table)
+            .values(&new_member)
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()?;
+        if let Some(m) = &inserted {
+            record_audit(conn, actor, guild, "member", m.id, None, snapshot(m))?;
+        }
+        Ok(inserted.is_some())
+    })
+}
 
-    Ok(output)
+/// Marks a member as in or out of the guild. Returns whether anything changed.
+pub fn set_member_presence(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    member_id: i64,
+    present: bool,
+) -> Result<bool, ToddError> {
+    let _timer = metrics::query_timer("set_member_presence");
+    use crate::schema::members;
+    conn.transaction(|conn| {
+        let updated: Vec<SchlonghouseMember> = diesel::update(
+            members::table
+                .filter(members::guild_id.eq(guild))
+                .filter(members::id.eq(member_id))
+                .filter(members::is_member.ne(present))
+                .filter(members::deleted_at.is_null()),
+        )
+        .set(members::is_member.eq(present))
+        .get_results(conn)?;
+        record_presence_changes(conn, actor, guild, &updated, present)?;
+        Ok(updated.len() == 1)
+    })
 }
 
-pub fn get_member(conn: &mut PgConnection, member_id: &str) -> Result<SchlonghouseMember, Error> {
-    let output = if let Ok(parsed_id) = member_id.parse::<i64>() {
-        get_member_from_id(conn, parsed_id)?
-    } else {
-        get_member_from_name(conn, member_id)?
-    };
+fn record_presence_changes(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    updated: &[SchlonghouseMember],
+    present: bool,
+) -> Result<(), ToddError> {
+    for m in updated {
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "member",
+            m.id,
+            Some(json!({ "is_member": !present })),
+            Some(json!({ "is_member": present })),
+        )?;
+    }
+    Ok(())
+}
+
+/// Marks every Discord member not in `present_ids` as having left. Quotes
+/// and everything else about them are kept.
+pub fn mark_departed_members(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    present_ids: &[i64],
+) -> Result<usize, ToddError> {
+    let _timer = metrics::query_timer("mark_departed_members");
+    use crate::schema::members;
+    conn.transaction(|conn| {
+        let updated: Vec<SchlonghouseMember> = diesel::update(
+            members::table
+                .filter(members::guild_id.eq(guild))
+                .filter(members::is_member.eq(true))
+                .filter(members::id.ne_all(present_ids))
+                .filter(members::deleted_at.is_null()),
+        )
+        .set(members::is_member.eq(false))
+        .get_results(conn)?;
+        record_presence_changes(conn, actor, guild, &updated, false)?;
+        Ok(updated.len())
+    })
+}
+
+pub fn get_member_nicknames(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<Vec<Nickname>, ToddError> {
+    let _timer = metrics::query_timer("get_member_nicknames");
+    use crate::schema::nicknames::dsl::*;
+    let output = nicknames
+        .filter(guild_id.eq(member.guild_id))
+        .filter(primary_name.eq(member.id))
+        .filter(deleted_at.is_null())
+        .order(id)
+        .load::<Nickname>(conn)?;
     Ok(output)
 }
 
+/// Looks a member up by id, or by name or nickname ignoring case. Nothing
+/// close is good enough here: names typed into commands go through
+/// `member_commands::resolve_member`, which says when it had to guess.
+pub fn get_member(
+    conn: &mut PgConnection,
+    guild: i64,
+    member_id: &str,
+) -> Result<SchlonghouseMember, ToddError> {
+    let _timer = metrics::query_timer("get_member");
+    if let Ok(parsed_id) = member_id.parse::<i64>() {
+        return get_member_from_id(conn, guild, parsed_id);
+    }
+    get_member_from_name(conn, guild, member_id).map_err(|e| match e {
+        ToddError::NotFound(_) => {
+            ToddError::NotFound(format!("Error: no member called *{}*", member_id))
+        }
+        e => e,
+    })
+}
+
 fn get_member_from_id(
     conn: &mut PgConnection,
+    guild: i64,
     member_id: i64,
-) -> Result<SchlonghouseMember, Error> {
+) -> Result<SchlonghouseMember, ToddError> {
     use crate::schema::members::dsl::*;
-    let output = members.find(member_id).first(conn)?;
+    let output = members
+        .find((guild, member_id))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
     Ok(output)
 }
 
 fn get_member_from_primary_name(
     conn: &mut PgConnection,
+    guild: i64,
     name: &str,
-) -> Result<SchlonghouseMember, Error> {
+) -> Result<SchlonghouseMember, ToddError> {
     use crate::schema::members::dsl::*;
-    let output = members.filter(primary_name.eq(name)).first(conn)?;
+    let output = members
+        .filter(guild_id.eq(guild))
+        .filter(lower(primary_name).eq(name.to_lowercase()))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
     Ok(output)
 }
 
 fn get_member_from_nickname(
     conn: &mut PgConnection,
+    guild: i64,
     nickname_to_check: &str,
-) -> Result<SchlonghouseMember, Error> {
-    use crate::schema::members::dsl::*;
-    use crate::schema::nicknames::dsl::*;
-    let result = nicknames
-        .inner_join(members)
-        .filter(nickname.eq(nickname_to_check))
-        .select((
-            crate::schema::members::id,
-            crate::schema::members::primary_name,
-            crate::schema::members::is_member,
-        ))
+) -> Result<SchlonghouseMember, ToddError> {
+    use crate::schema::{members, nicknames};
+    let result = nicknames::table
+        .inner_join(
+            members::table.on(members::guild_id
+                .eq(nicknames::guild_id)
+                .and(members::id.eq(nicknames::primary_name))),
+        )
+        .filter(nicknames::guild_id.eq(guild))
+        .filter(lower(nicknames::nickname).eq(nickname_to_check.to_lowercase()))
+        .filter(nicknames::deleted_at.is_null())
+        .filter(members::deleted_at.is_null())
+        .select(SchlonghouseMember::as_select())
         .first(conn)?;
     Ok(result)
 }
 
 pub fn get_member_from_name(
     conn: &mut PgConnection,
+    guild: i64,
     name_input: &str,
-) -> Result<SchlonghouseMember, Error> {
-    let output = if let Ok(member) = get_member_from_primary_name(conn, name_input) {
+) -> Result<SchlonghouseMember, ToddError> {
+    let _timer = metrics::query_timer("get_member_from_name");
+    let output = if let Ok(member) = get_member_from_primary_name(conn, guild, name_input) {
         member
     } else {
-        get_member_from_nickname(conn, name_input)?
+        get_member_from_nickname(conn, guild, name_input)?
+    };
+    Ok(output)
+}
+
+/// How `find_member` resolved a name.
+#[derive(Debug)]
+pub enum MemberMatch {
+    Exact(SchlonghouseMember),
+    /// The only member with a name close to the input, and the name it matched
+    Close(SchlonghouseMember, String),
+    /// Several members are equally close, closest first
+    Ambiguous(Vec<(SchlonghouseMember, String)>),
+    NotFound,
+}
+
+const MAX_CANDIDATES: usize = 5;
+
+/// Case-insensitive lookup by primary name or nickname, falling back to
+/// names within a small edit distance of `name_input`.
+pub fn find_member(
+    conn: &mut PgConnection,
+    guild: i64,
+    name_input: &str,
+) -> Result<MemberMatch, ToddError> {
+    let _timer = metrics::query_timer("find_member");
+    use crate::schema::{members, nicknames};
+
+    if let Ok(member) = get_member_from_name(conn, guild, name_input) {
+        return Ok(MemberMatch::Exact(member));
+    }
+    let input = name_input.to_lowercase();
+    let max_distance = match input.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+
+    let all_members: Vec<SchlonghouseMember> = members::table
+        .filter(members::guild_id.eq(guild))
+        .filter(members::deleted_at.is_null())
+        .load(conn)?;
+    let all_nicknames: Vec<(i64, String)> = nicknames::table
+        .filter(nicknames::guild_id.eq(guild))
+        .filter(nicknames::deleted_at.is_null())
+        .select((nicknames::primary_name, nicknames::nickname))
+        .load(conn)?;
+    // Closest name for each member, if any are close enough
+    let mut candidates: Vec<(usize, SchlonghouseMember, String)> = vec![];
+    for member in all_members {
+        let closest = std::iter::once(member.primary_name.clone())
+            .chain(
+                all_nicknames
+                    .iter()
+                    .filter(|(owner, _)| *owner == member.id)
+                    .map(|(_, n)| n.clone()),
+            )
+            .map(|n| (edit_distance(&input, &n.to_lowercase()), n))
+            .filter(|(d, _)| *d <= max_distance)
+            .min_by_key(|(d, _)| *d);
+        if let Some((distance, name)) = closest {
+            candidates.push((distance, member, name));
+        }
+    }
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.2.cmp(&b.2)));
+
+    let output = match candidates.len() {
+        0 => MemberMatch::NotFound,
+        1 => {
+            let (_, member, name) = candidates.remove(0);
+            MemberMatch::Close(member, name)
+        }
+        _ => MemberMatch::Ambiguous(
+            candidates
+                .into_iter()
+                .take(MAX_CANDIDATES)
+                .map(|(_, m, n)| (m, n))
+                .collect(),
+        ),
     };
     Ok(output)
 }
 
-pub fn get_all_members_quotes(conn: &mut PgConnection, owner: &str) -> Result<Vec<Quote>, Error> {
+// Levenshtein distance, by character
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub fn get_all_members_quotes(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<Vec<Quote>, ToddError> {
+    let _timer = metrics::query_timer("get_all_members_quotes");
     use crate::schema::quotes;
 
     let output = quotes::table
-        .filter(quotes::quoted.eq(owner))
+        .filter(quotes::guild_id.eq(member.guild_id))
+        .filter(quotes::quoted.eq(&member.primary_name))
+        .filter(quotes::legacy.eq(false))
+        .filter(quotes::deleted_at.is_null())
+        .filter(quotes::approved.eq(true))
         .load::<Quote>(conn)?;
 
     Ok(output)
 }
 
-pub fn get_random_quote_from_quotes(quote_vector: Vec<Quote>) -> Result<String, Error> {
-    use rand::Rng;
+/// How many times `member`'s quotes have changed. The database keeps count
+/// whatever changes them, so anything built from the quotes is current for
+/// as long as this stays the same.
+pub fn get_quote_generation(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<i64, ToddError> {
+    let _timer = metrics::query_timer("get_quote_generation");
+    use crate::schema::quote_generations::dsl::*;
+    let output = quote_generations
+        .filter(guild_id.eq(member.guild_id))
+        .filter(quoted.eq(&member.primary_name))
+        .select(generation)
+        .first(conn)
+        .optional()?;
+    // Nobody's quotes have changed since before they were counted
+    Ok(output.unwrap_or(0))
+}
+
+/// Adds `lines` to `owner`'s legacy quotes in one transaction, skipping blank
+/// lines and quotes they already have. Returns how many were added and how
+/// many were skipped as duplicates.
+pub fn import_legacy_quotes(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    owner: &str,
+    lines: &[String],
+) -> Result<(usize, usize), ToddError> {
+    let _timer = metrics::query_timer("import_legacy_quotes");
+    use crate::schema::quotes::dsl::*;
+    use std::collections::HashSet;
+
+    conn.transaction(|conn| {
+        let mut existing: HashSet<String> = quotes
+            .filter(guild_id.eq(guild))
+            .filter(quoted.eq(owner))
+            .filter(legacy.eq(true))
+            .filter(deleted_at.is_null())
+            .select(quote)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        let mut new_quotes = vec![];
+        let mut skipped = 0;
+        for line in lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            if existing.insert(line.to_string()) {
+                new_quotes.push(NewQuote {
+                    quoted: owner,
+                    quote: line,
+                    legacy: true,
+                    guild_id: guild,
+                    submitted_by: None,
+                    approved: true,
+                });
+            } else {
+                skipped += 1;
+            }
+        }
+        diesel::insert_into(quotes)
+            .values(&new_quotes)
+            .execute(conn)?;
+        // One entry per import rather than one per line
+        if !new_quotes.is_empty() {
+            record_audit(
+                conn,
+                actor,
+                guild,
+                "legacy_quotes",
+                owner,
+                None,
+                Some(json!({ "added": new_quotes.len(), "skipped": skipped })),
+            )?;
+        }
+        Ok((new_quotes.len(), skipped))
+    })
+}
+
+/// Returns `member`'s legacy quotes, oldest first.
+pub fn get_legacy_quotes(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<Vec<Quote>, ToddError> {
+    let _timer = metrics::query_timer("get_legacy_quotes");
+    use crate::schema::quotes::dsl::*;
+
+    let output = quotes
+        .filter(guild_id.eq(member.guild_id))
+        .filter(quoted.eq(&member.primary_name))
+        .filter(legacy.eq(true))
+        .filter(deleted_at.is_null())
+        .order(id)
+        .load::<Quote>(conn)?;
+
+    Ok(output)
+}
+
+/// How quotes are weighted when picking one at random.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteWeighting {
+    /// Every eligible quote is equally likely.
+    Uniform,
+    /// Newer quotes are more likely; a quote's weight halves after ~30 days.
+    Recency,
+    /// Well rated quotes are more likely; each point of score is worth 1.5x,
+    /// capped at +/-10 points. Unrated quotes behave like `Uniform`.
+    #[default]
+    Votes,
+}
+
+impl QuoteWeighting {
+    // Weighted random sampling (Efraimidis-Spirakis): ordering by
+    // `-ln(u) / weight` and taking the first row picks each row with
+    // probability proportional to its weight. `1 - random()` keeps `u` out of 0.
+    fn order_by_sql(&self) -> &'static str {
+        match self {
+            QuoteWeighting::Uniform => "random()",
+            QuoteWeighting::Recency => {
+                "-ln(1 - random()) \
+                 * (1 + extract(epoch from (LOCALTIMESTAMP - quotes.created_at)) / 2592000)"
+            }
+            QuoteWeighting::Votes => {
+                "-ln(1 - random()) \
+                 * power(1.5, -greatest(-10, least(10, ( \
+                     SELECT COALESCE(SUM(quote_votes.vote), 0) FROM quote_votes \
+                     WHERE quote_votes.quote_id = quotes.id))))"
+            }
+        }
+    }
+}
+
+/// Controls how `get_random_quote` picks a quote.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteSelection {
+    /// Skip the last `avoid_last` quotes served for this member.
+    pub avoid_last: i64,
+    /// Only count quotes served in this channel as recent.
+    /// `None` counts quotes served anywhere.
+    pub channel_id: Option<i64>,
+    pub weighting: QuoteWeighting,
+}
+
+impl Default for QuoteSelection {
+    fn default() -> Self {
+        QuoteSelection {
+            avoid_last: 10,
+            channel_id: None,
+            weighting: QuoteWeighting::default(),
+        }
+    }
+}
+
+fn get_recently_served_quote_ids(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+    selection: &QuoteSelection,
+) -> Result<Vec<i32>, ToddError> {
+    use crate::schema::{quotes, served_quotes};
+
+    let mut query = served_quotes::table
+        .inner_join(quotes::table)
+        .filter(quotes::guild_id.eq(member.guild_id))
+        .filter(quotes::quoted.eq(&member.primary_name))
+        .select(served_quotes::quote_id)
+        .order(served_quotes::served_at.desc())
+        .limit(selection.avoid_last)
+        .into_boxed();
+    if let Some(channel) = selection.channel_id {
+        query = query.filter(served_quotes::channel_id.eq(channel));
+    }
+    let output = query.load::<i32>(conn)?;
+
+    Ok(output)
+}
+
+/// Picks a random quote for `member` in the database, skipping the ones
+/// served recently. If every quote was served recently, any quote may be
+/// picked rather than failing.
+pub fn get_random_quote(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+    selection: &QuoteSelection,
+) -> Result<Quote, ToddError> {
+    let _timer = metrics::query_timer("get_random_quote");
+    use crate::schema::quotes;
+    use diesel::dsl::sql;
+    use diesel::sql_types::Double;
+
+    let recent = get_recently_served_quote_ids(conn, member, selection)?;
+
+    let fresh = quotes::table
+        .filter(quotes::guild_id.eq(member.guild_id))
+        .filter(quotes::quoted.eq(&member.primary_name))
+        .filter(quotes::legacy.eq(false))
+        .filter(quotes::deleted_at.is_null())
+        .filter(quotes::approved.eq(true))
+        .filter(quotes::id.ne_all(&recent))
+        .order(sql::<Double>(selection.weighting.order_by_sql()))
+        .first::<Quote>(conn)
+        .optional()?;
+    if let Some(q) = fresh {
+        return Ok(q);
+    }
+
+    quotes::table
+        .filter(quotes::guild_id.eq(member.guild_id))
+        .filter(quotes::quoted.eq(&member.primary_name))
+        .filter(quotes::legacy.eq(false))
+        .filter(quotes::deleted_at.is_null())
+        .filter(quotes::approved.eq(true))
+        .order(sql::<Double>(selection.weighting.order_by_sql()))
+        .first::<Quote>(conn)
+        .optional()?
+        .ok_or_else(|| ToddError::NotFound("Error: no quotes to pick from".to_string()))
+}
+
+pub fn record_served_quote(
+    conn: &mut PgConnection,
+    quote: &Quote,
+    channel_id: i64,
+    message_id: Option<i64>,
+) -> Result<ServedQuote, ToddError> {
+    let _timer = metrics::query_timer("record_served_quote");
+    use crate::schema::served_quotes;
+
+    let new_served_quote = NewServedQuote {
+        quote_id: quote.id,
+        channel_id,
+        message_id,
+    };
+
+    let output = diesel::insert_into(served_quotes::table)
+        .values(&new_served_quote)
+        .get_result(conn)?;
+
+    Ok(output)
+}
+
+pub fn get_served_quote_from_message(
+    conn: &mut PgConnection,
+    message: i64,
+) -> Result<Option<ServedQuote>, ToddError> {
+    let _timer = metrics::query_timer("get_served_quote_from_message");
+    use crate::schema::served_quotes::dsl::*;
+    let output = served_quotes
+        .filter(message_id.eq(message))
+        .first(conn)
+        .optional()?;
+    Ok(output)
+}
+
+pub fn get_quote_by_id(
+    conn: &mut PgConnection,
+    guild: i64,
+    quote_id: i32,
+) -> Result<Quote, ToddError> {
+    let _timer = metrics::query_timer("get_quote_by_id");
+    use crate::schema::quotes::dsl::*;
+    let output = quotes
+        .filter(guild_id.eq(guild))
+        .filter(id.eq(quote_id))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
+    Ok(output)
+}
+
+/// Soft deletes the quote, see `purge_deleted`. Votes and serving history go
+/// once it's purged.
+pub fn delete_quote_by_id(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    quote_id: i32,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("delete_quote_by_id");
+    use crate::schema::quotes;
+    conn.transaction(|conn| {
+        let deleted: Option<Quote> = diesel::update(
+            quotes::table
+                .filter(quotes::guild_id.eq(guild))
+                .filter(quotes::id.eq(quote_id))
+                .filter(quotes::deleted_at.is_null()),
+        )
+        .set(quotes::deleted_at.eq(Local::now().naive_local()))
+        .get_result(conn)
+        .optional()?;
+        if let Some(d) = &deleted {
+            record_audit(
+                conn,
+                actor,
+                d.guild_id,
+                "quote",
+                d.id,
+                deleted_snapshot(d),
+                None,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Records `user`'s vote on a quote, replacing any earlier vote they made.
+pub fn set_quote_vote(
+    conn: &mut PgConnection,
+    guild: i64,
+    quote: i32,
+    user: i64,
+    new_vote: i16,
+) -> Result<QuoteVote, ToddError> {
+    let _timer = metrics::query_timer("set_quote_vote");
+    use crate::schema::quote_votes::dsl::*;
+    use crate::schema::quotes;
+
+    quotes::table
+        .filter(quotes::guild_id.eq(guild))
+        .filter(quotes::id.eq(quote))
+        .select(quotes::id)
+        .first::<i32>(conn)?;
+
+    let new_quote_vote = NewQuoteVote {
+        quote_id: quote,
+        user_id: user,
+        vote: new_vote,
+    };
+
+    let output = diesel::insert_into(quote_votes)
+        .values(&new_quote_vote)
+        .on_conflict((quote_id, user_id))
+        .do_update()
+        .set((vote.eq(new_vote), voted_at.eq(diesel::dsl::now)))
+        .get_result(conn)?;
+
+    Ok(output)
+}
+
+/// Removes `user`'s vote on a quote if it matches `old_vote`, so taking back
+/// a 👎 doesn't undo a later 👍.
+pub fn remove_quote_vote(
+    conn: &mut PgConnection,
+    guild: i64,
+    quote: i32,
+    user: i64,
+    old_vote: i16,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("remove_quote_vote");
+    use crate::schema::quote_votes::dsl::*;
+    use crate::schema::quotes;
+    // Votes don't have a guild of their own, their quote does
+    let guild_quotes = quotes::table
+        .filter(quotes::guild_id.eq(guild))
+        .select(quotes::id);
+    diesel::delete(
+        quote_votes
+            .filter(quote_id.eq(quote))
+            .filter(quote_id.eq_any(guild_quotes))
+            .filter(user_id.eq(user))
+            .filter(vote.eq(old_vote)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
 
-    if quote_vector.is_empty() {
-        return Err(Error::from("quotes list is empty"));
+pub fn get_quote_score(
+    conn: &mut PgConnection,
+    guild: i64,
+    quote: i32,
+) -> Result<QuoteScore, ToddError> {
+    let _timer = metrics::query_timer("get_quote_score");
+    use crate::schema::quote_votes::dsl::*;
+    use crate::schema::quotes;
+    // Votes don't have a guild of their own, their quote does
+    let guild_quotes = quotes::table
+        .filter(quotes::guild_id.eq(guild))
+        .select(quotes::id);
+    let votes = quote_votes
+        .filter(quote_id.eq(quote))
+        .filter(quote_id.eq_any(guild_quotes))
+        .select(vote)
+        .load::<i16>(conn)?;
+    let output = QuoteScore {
+        upvotes: votes.iter().filter(|v| **v > 0).count() as i64,
+        downvotes: votes.iter().filter(|v| **v < 0).count() as i64,
+    };
+    Ok(output)
+}
+
+/// Returns up to `limit` rated quotes with their total score, best first,
+/// or worst first when `best` is false. Quotes nobody voted on are left out.
+pub fn get_quote_leaderboard(
+    conn: &mut PgConnection,
+    guild: i64,
+    owner: Option<&str>,
+    best: bool,
+    limit: i64,
+) -> Result<Vec<(Quote, i64)>, ToddError> {
+    let _timer = metrics::query_timer("get_quote_leaderboard");
+    use crate::schema::{quote_votes, quotes};
+
+    let mut query = quotes::table
+        .inner_join(quote_votes::table)
+        .filter(quotes::guild_id.eq(guild))
+        .filter(quotes::deleted_at.is_null())
+        .group_by(quotes::id)
+        .select((Quote::as_select(), diesel::dsl::sum(quote_votes::vote)))
+        .limit(limit)
+        .into_boxed();
+    if let Some(o) = owner {
+        query = query.filter(quotes::quoted.eq(o));
     }
-    let random_index = rand::thread_rng().gen_range(0..quote_vector.len());
-    let output = quote_vector
-        .get(random_index)
-        .ok_or(Error::from("Error while indexing a random quote"))?;
+    query = if best {
+        query.order((diesel::dsl::sum(quote_votes::vote).desc(), quotes::id))
+    } else {
+        query.order((diesel::dsl::sum(quote_votes::vote).asc(), quotes::id))
+    };
+    let output = query
+        .load::<(Quote, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(q, score)| (q, score.unwrap_or_default()))
+        .collect();
+
+    Ok(output)
+}
+
+/// Returns `guild`'s settings, creating the defaults the first time.
+pub fn get_quote_of_the_day_settings(
+    conn: &mut PgConnection,
+    guild: i64,
+) -> Result<QuoteOfTheDaySettings, ToddError> {
+    let _timer = metrics::query_timer("get_quote_of_the_day_settings");
+    use crate::schema::quote_of_the_day_settings::dsl::*;
+    diesel::insert_into(quote_of_the_day_settings)
+        .values(&NewQuoteOfTheDaySettings { guild_id: guild })
+        .on_conflict(guild_id)
+        .do_nothing()
+        .execute(conn)?;
+    let output = quote_of_the_day_settings
+        .filter(guild_id.eq(guild))
+        .first(conn)?;
+    Ok(output)
+}
+
+pub fn get_enabled_quote_of_the_day_settings(
+    conn: &mut PgConnection,
+) -> Result<Vec<QuoteOfTheDaySettings>, ToddError> {
+    let _timer = metrics::query_timer("get_enabled_quote_of_the_day_settings");
+    use crate::schema::quote_of_the_day_settings::dsl::*;
+    let output = quote_of_the_day_settings
+        .filter(enabled.eq(true))
+        .load(conn)?;
+    Ok(output)
+}
+
+pub fn update_quote_of_the_day_settings(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    changes: &UpdateQuoteOfTheDaySettings,
+) -> Result<QuoteOfTheDaySettings, ToddError> {
+    let _timer = metrics::query_timer("update_quote_of_the_day_settings");
+    use crate::schema::quote_of_the_day_settings::dsl::*;
+    conn.transaction(|conn| {
+        let before = get_quote_of_the_day_settings(conn, guild)?;
+        let output = diesel::update(quote_of_the_day_settings.filter(guild_id.eq(guild)))
+            .set(changes)
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "qotd_settings",
+            guild,
+            snapshot(&before),
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
+}
+
+/// Returns `guild`'s settings, creating the defaults the first time.
+pub fn get_guild_settings(conn: &mut PgConnection, guild: i64) -> Result<GuildSettings, ToddError> {
+    let _timer = metrics::query_timer("get_guild_settings");
+    use crate::schema::guild_settings::dsl::*;
+    // Guilds only get a row once they change something
+    let output = guild_settings
+        .find(guild)
+        .first(conn)
+        .optional()?
+        .unwrap_or_else(|| GuildSettings::defaults(guild));
+    Ok(output)
+}
 
-    Ok(output.quote.clone())
+pub fn update_guild_settings(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    changes: &UpdateGuildSettings,
+) -> Result<GuildSettings, ToddError> {
+    let _timer = metrics::query_timer("update_guild_settings");
+    use crate::schema::guild_settings::dsl::*;
+    conn.transaction(|conn| {
+        let before = get_guild_settings(conn, guild)?;
+        diesel::insert_into(guild_settings)
+            .values(&NewGuildSettings { guild_id: guild })
+            .on_conflict(guild_id)
+            .do_nothing()
+            .execute(conn)?;
+        let output = diesel::update(guild_settings.find(guild))
+            .set((changes, updated_at.eq(diesel::dsl::now)))
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "guild_settings",
+            guild,
+            snapshot(&before),
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
 }
 
-pub fn remove_member(conn: &mut PgConnection, member_id: i64) -> Result<(), Error> {
+pub fn get_daily_quote_on(
+    conn: &mut PgConnection,
+    guild: i64,
+    date: NaiveDate,
+) -> Result<Option<DailyQuote>, ToddError> {
+    let _timer = metrics::query_timer("get_daily_quote_on");
+    use crate::schema::daily_quotes::dsl::*;
+    let output = daily_quotes
+        .filter(guild_id.eq(guild))
+        .filter(posted_on.eq(date))
+        .first(conn)
+        .optional()?;
+    Ok(output)
+}
+
+/// Picks a quote from any of `guild`'s members that hasn't been the quote of
+/// the day in the `window_days` before `date`. Returns `None` if every quote
+/// has.
+pub fn pick_quote_of_the_day(
+    conn: &mut PgConnection,
+    guild: i64,
+    date: NaiveDate,
+    window_days: i32,
+) -> Result<Option<Quote>, ToddError> {
+    let _timer = metrics::query_timer("pick_quote_of_the_day");
+    use crate::schema::{daily_quotes, quotes};
+    use diesel::dsl::{not, sql};
+    use diesel::sql_types::Double;
+
+    let recent = daily_quotes::table
+        .filter(daily_quotes::guild_id.eq(guild))
+        .filter(daily_quotes::posted_on.gt(date - chrono::Duration::days(window_days.into())))
+        .select(daily_quotes::quote_id);
+    let output = quotes::table
+        .filter(quotes::guild_id.eq(guild))
+        .filter(quotes::legacy.eq(false))
+        .filter(quotes::deleted_at.is_null())
+        .filter(quotes::approved.eq(true))
+        .filter(not(quotes::id.eq_any(recent)))
+        .order(sql::<Double>(QuoteWeighting::default().order_by_sql()))
+        .first::<Quote>(conn)
+        .optional()?;
+    Ok(output)
+}
+
+/// Claims `date`'s quote of the day for `quote`, before it's posted in
+/// `channel_id`. Returns `None` if the day has already been claimed.
+pub fn create_daily_quote(
+    conn: &mut PgConnection,
+    quote: &Quote,
+    date: NaiveDate,
+    channel_id: i64,
+) -> Result<Option<DailyQuote>, ToddError> {
+    let _timer = metrics::query_timer("create_daily_quote");
+    use crate::schema::daily_quotes;
+
+    let new_daily_quote = NewDailyQuote {
+        quote_id: quote.id,
+        posted_on: date,
+        channel_id,
+        guild_id: quote.guild_id,
+    };
+
+    let output = diesel::insert_into(daily_quotes::table)
+        .values(&new_daily_quote)
+        .on_conflict((daily_quotes::guild_id, daily_quotes::posted_on))
+        .do_nothing()
+        .get_result(conn)
+        .optional()?;
+
+    Ok(output)
+}
+
+/// Records the message a claimed quote of the day was posted in.
+pub fn set_daily_quote_message(
+    conn: &mut PgConnection,
+    daily_quote: &DailyQuote,
+    message_id: i64,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("set_daily_quote_message");
+    use crate::schema::daily_quotes;
+    diesel::update(daily_quotes::table.find(daily_quote.id))
+        .set(daily_quotes::message_id.eq(message_id))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Gives up a claimed quote of the day that couldn't be posted, so the next
+/// tick can try again.
+pub fn delete_daily_quote(
+    conn: &mut PgConnection,
+    daily_quote: &DailyQuote,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("delete_daily_quote");
+    use crate::schema::daily_quotes;
+    diesel::delete(
+        daily_quotes::table
+            .find(daily_quote.id)
+            .filter(daily_quotes::message_id.is_null()),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Soft deletes a member and their nicknames at `when`, so `!undo` can bring
+/// them back. Returns how many nicknames went with them.
+fn soft_delete_member(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    member_id: i64,
+    when: NaiveDateTime,
+) -> Result<usize, ToddError> {
+    use crate::schema::{members, nicknames};
+    let deleted_nicknames: Vec<Nickname> = diesel::update(
+        nicknames::table
+            .filter(nicknames::guild_id.eq(guild))
+            .filter(nicknames::primary_name.eq(member_id))
+            .filter(nicknames::deleted_at.is_null()),
+    )
+    .set(nicknames::deleted_at.eq(when))
+    .get_results(conn)?;
+    for n in &deleted_nicknames {
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "nickname",
+            n.id,
+            deleted_snapshot(n),
+            None,
+        )?;
+    }
+    // Recorded last, so `!undo` finds the member before anything of theirs
+    let deleted: Option<SchlonghouseMember> = diesel::update(
+        members::table
+            .find((guild, member_id))
+            .filter(members::deleted_at.is_null()),
+    )
+    .set(members::deleted_at.eq(when))
+    .get_result(conn)
+    .optional()?;
+    if let Some(m) = &deleted {
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "member",
+            m.id,
+            deleted_snapshot(m),
+            None,
+        )?;
+    }
+    Ok(deleted_nicknames.len())
+}
+
+/// Permanently removes a member along with their nicknames, events and the
+/// events' reminders. Returns how many rows went.
+fn purge_member(conn: &mut PgConnection, guild: i64, member_id: i64) -> Result<usize, ToddError> {
+    use crate::schema::{events, members, nicknames, reminders};
+    let owned_events = events::table
+        .filter(events::guild_id.eq(guild))
+        .filter(events::owned_by.eq(member_id));
+    let mut output = diesel::delete(
+        reminders::table.filter(reminders::event_id.eq_any(owned_events.select(events::id))),
+    )
+    .execute(conn)?;
+    output += diesel::delete(owned_events).execute(conn)?;
+    output += diesel::delete(
+        nicknames::table
+            .filter(nicknames::guild_id.eq(guild))
+            .filter(nicknames::primary_name.eq(member_id)),
+    )
+    .execute(conn)?;
+    output += diesel::delete(members::table.find((guild, member_id))).execute(conn)?;
+    Ok(output)
+}
+
+/// A member added with the id of one that was removed takes their place,
+/// and the removed one can't be restored any more.
+fn purge_removed_member(
+    conn: &mut PgConnection,
+    guild: i64,
+    member_id: i64,
+) -> Result<(), ToddError> {
     use crate::schema::members;
-    diesel::delete(members::table.filter(members::id.eq(member_id))).execute(conn)?;
+    let removed = members::table
+        .find((guild, member_id))
+        .filter(members::deleted_at.is_not_null())
+        .select(members::id)
+        .first::<i64>(conn)
+        .optional()?;
+    if removed.is_some() {
+        purge_member(conn, guild, member_id)?;
+    }
     Ok(())
 }
+
+/// What `remove_member_and_data` deleted or moved.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MemberRemoval {
+    pub nicknames: usize,
+    pub quotes: usize,
+    pub events_moved: usize,
+    pub events_deleted: usize,
+    pub reminders: usize,
+}
+
+/// Removes a member along with everything that points at them, in one
+/// transaction. It's all soft deleted at the same time, so `!undo` brings it
+/// back together. With `reassign_to`, their nicknames, quotes and events
+/// move to that member instead (and their old primary name becomes a
+/// nickname), except birthdays. Nothing's left to restore then, so the
+/// member and their birthdays are deleted for good.
+pub fn remove_member_and_data(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    member: &SchlonghouseMember,
+    reassign_to: Option<&SchlonghouseMember>,
+) -> Result<MemberRemoval, ToddError> {
+    let _timer = metrics::query_timer("remove_member_and_data");
+    use crate::schema::{events, nicknames, quotes, reminders};
+
+    let when = Local::now().naive_local();
+    conn.transaction(|conn| {
+        let guild = member.guild_id;
+        let mut output = MemberRemoval::default();
+        let owned_events = events::table
+            .filter(events::guild_id.eq(guild))
+            .filter(events::owned_by.eq(member.id))
+            .filter(events::deleted_at.is_null());
+        let owned_nicknames = nicknames::table
+            .filter(nicknames::guild_id.eq(guild))
+            .filter(nicknames::primary_name.eq(member.id))
+            .filter(nicknames::deleted_at.is_null());
+        let owned_quotes = quotes::table
+            .filter(quotes::guild_id.eq(guild))
+            .filter(quotes::quoted.eq(&member.primary_name))
+            .filter(quotes::deleted_at.is_null());
+
+        let Some(target) = reassign_to else {
+            let deleted_reminders: Vec<Reminder> = diesel::update(
+                reminders::table
+                    .filter(reminders::event_id.eq_any(owned_events.select(events::id)))
+                    .filter(reminders::deleted_at.is_null()),
+            )
+            .set(reminders::deleted_at.eq(when))
+            .get_results(conn)?;
+            for r in &deleted_reminders {
+                record_audit(
+                    conn,
+                    actor,
+                    guild,
+                    "reminder",
+                    r.id,
+                    deleted_snapshot(r),
+                    None,
+                )?;
+            }
+            output.reminders = deleted_reminders.len();
+            let deleted_events: Vec<ToddEvent> = diesel::update(owned_events)
+                .set(events::deleted_at.eq(when))
+                .get_results(conn)?;
+            for e in &deleted_events {
+                record_audit(conn, actor, guild, "event", e.id, deleted_snapshot(e), None)?;
+            }
+            output.events_deleted = deleted_events.len();
+            let deleted_quotes: Vec<Quote> = diesel::update(owned_quotes)
+                .set(quotes::deleted_at.eq(when))
+                .get_results(conn)?;
+            for q in &deleted_quotes {
+                record_audit(conn, actor, guild, "quote", q.id, deleted_snapshot(q), None)?;
+            }
+            output.quotes = deleted_quotes.len();
+            output.nicknames = soft_delete_member(conn, actor, guild, member.id, when)?;
+            return Ok(output);
+        };
+
+        let birthdays: Vec<i32> = owned_events
+            .filter(events::title.eq("Birthday"))
+            .select(events::id)
+            .load(conn)?;
+        let deleted_reminders: Vec<Reminder> =
+            diesel::delete(reminders::table.filter(reminders::event_id.eq_any(&birthdays)))
+                .get_results(conn)?;
+        // Reminders that were already deleted go too, but aren't counted again
+        for r in deleted_reminders.iter().filter(|r| r.deleted_at.is_none()) {
+            record_audit(conn, actor, guild, "reminder", r.id, snapshot(r), None)?;
+            output.reminders += 1;
+        }
+        let removed_events: Vec<ToddEvent> =
+            diesel::delete(events::table.filter(events::id.eq_any(&birthdays)))
+                .get_results(conn)?;
+        for e in &removed_events {
+            record_audit(conn, actor, guild, "event", e.id, snapshot(e), None)?;
+        }
+        output.events_deleted = removed_events.len();
+        let moved_events: Vec<ToddEvent> = diesel::update(owned_events)
+            .set(events::owned_by.eq(target.id))
+            .get_results(conn)?;
+        for e in &moved_events {
+            record_audit(
+                conn,
+                actor,
+                guild,
+                "event",
+                e.id,
+                Some(json!({ "owned_by": member.id })),
+                Some(json!({ "owned_by": target.id })),
+            )?;
+        }
+        output.events_moved = moved_events.len();
+        let moved_nicknames: Vec<Nickname> = diesel::update(owned_nicknames)
+            .set(nicknames::primary_name.eq(target.id))
+            .get_results(conn)?;
+        for n in &moved_nicknames {
+            record_audit(
+                conn,
+                actor,
+                guild,
+                "nickname",
+                n.id,
+                Some(json!({ "primary_name": member.id })),
+                Some(json!({ "primary_name": target.id })),
+            )?;
+        }
+        output.nicknames = moved_nicknames.len();
+        let moved_quotes: Vec<Quote> = diesel::update(owned_quotes)
+            .set(quotes::quoted.eq(&target.primary_name))
+            .get_results(conn)?;
+        for q in &moved_quotes {
+            record_audit(
+                conn,
+                actor,
+                guild,
+                "quote",
+                q.id,
+                Some(json!({ "quoted": member.primary_name })),
+                Some(json!({ "quoted": target.primary_name })),
+            )?;
+        }
+        output.quotes = moved_quotes.len();
+
+        // Their deleted quotes and events can't be restored without them
+        diesel::delete(
+            quotes::table
+                .filter(quotes::guild_id.eq(guild))
+                .filter(quotes::quoted.eq(&member.primary_name))
+                .filter(quotes::deleted_at.is_not_null()),
+        )
+        .execute(conn)?;
+        purge_member(conn, guild, member.id)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "member",
+            member.id,
+            snapshot(member),
+            None,
+        )?;
+        // Only once the member is gone is their name free to become a nickname
+        create_nickname(conn, actor, target, &member.primary_name)?;
+        Ok(output)
+    })
+}
+
+/// Changes a member's primary name, updating their quotes to match. The
+/// quotes follow the name, so only the member's change is audited.
+pub fn rename_member(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    member: &SchlonghouseMember,
+    new_primary_name: &str,
+) -> Result<SchlonghouseMember, ToddError> {
+    let _timer = metrics::query_timer("rename_member");
+    use crate::schema::{members, quotes};
+
+    conn.transaction(|conn| {
+        check_name_available(conn, member.guild_id, new_primary_name, Some(member.id))?;
+        diesel::update(
+            quotes::table
+                .filter(quotes::guild_id.eq(member.guild_id))
+                .filter(quotes::quoted.eq(&member.primary_name)),
+        )
+        .set(quotes::quoted.eq(new_primary_name))
+        .execute(conn)?;
+        let output: SchlonghouseMember =
+            diesel::update(members::table.find((member.guild_id, member.id)))
+                .set(members::primary_name.eq(new_primary_name))
+                .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            member.guild_id,
+            "member",
+            member.id,
+            snapshot(member),
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
+    .map_err(|e| name_conflict(conn, member.guild_id, new_primary_name, Some(member.id), e))
+}
+
+pub fn get_nickname(
+    conn: &mut PgConnection,
+    guild: i64,
+    name: &str,
+) -> Result<Nickname, ToddError> {
+    let _timer = metrics::query_timer("get_nickname");
+    use crate::schema::nicknames::dsl::*;
+    let output = nicknames
+        .filter(guild_id.eq(guild))
+        .filter(lower(nickname).eq(name.to_lowercase()))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
+    Ok(output)
+}
+
+pub fn delete_nickname_by_id(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    nickname_id: i32,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("delete_nickname_by_id");
+    use crate::schema::nicknames;
+    conn.transaction(|conn| {
+        let deleted: Option<Nickname> = diesel::delete(
+            nicknames::table
+                .find(nickname_id)
+                .filter(nicknames::guild_id.eq(guild)),
+        )
+        .get_result(conn)
+        .optional()?;
+        if let Some(d) = &deleted {
+            record_audit(conn, actor, guild, "nickname", d.id, snapshot(d), None)?;
+        }
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_event(
     conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
     new_title: &str,
     new_description: &str,
     when: NaiveDateTime,
     recuring: bool,
     owned_by_member_id: i64,
     recurring_by_num: Option<i16>,
-) -> Result<ToddEvent, Error> {
+) -> Result<ToddEvent, ToddError> {
+    let _timer = metrics::query_timer("create_event");
     use crate::schema::events;
     let new_event = NewEvent {
         title: new_title,
@@ -181,97 +1710,1367 @@ pub fn create_event(
         is_recuring: recuring,
         owned_by: owned_by_member_id,
         recurring_by: recurring_by_num,
+        guild_id: guild,
     };
-    let output = diesel::insert_into(events::table)
-        .values(&new_event)
-        .get_result(conn)?;
-    Ok(output)
+    conn.transaction(|conn| {
+        let output: ToddEvent = diesel::insert_into(events::table)
+            .values(&new_event)
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "event",
+            output.id,
+            None,
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
 }
-pub fn delete_event_by_id(conn: &mut PgConnection, event_id_to_delete: i32) -> Result<(), Error> {
-    use crate::schema::events;
-    diesel::delete(events::table.filter(events::id.eq(event_id_to_delete))).execute(conn)?;
-    Ok(())
+/// Soft deletes the event along with its reminders, which are restored
+/// together with it.
+pub fn delete_event_by_id(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    event_id_to_delete: i32,
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("delete_event_by_id");
+    use crate::schema::{events, reminders};
+    let when = Local::now().naive_local();
+    conn.transaction(|conn| {
+        let deleted_reminders: Vec<Reminder> = diesel::update(
+            reminders::table
+                .filter(reminders::guild_id.eq(guild))
+                .filter(reminders::event_id.eq(event_id_to_delete))
+                .filter(reminders::deleted_at.is_null()),
+        )
+        .set(reminders::deleted_at.eq(when))
+        .get_results(conn)?;
+        for r in &deleted_reminders {
+            record_audit(
+                conn,
+                actor,
+                r.guild_id,
+                "reminder",
+                r.id,
+                deleted_snapshot(r),
+                None,
+            )?;
+        }
+        // Recorded after its reminders, so `!undo` finds the event first
+        let deleted: Option<ToddEvent> = diesel::update(
+            events::table
+                .filter(events::guild_id.eq(guild))
+                .filter(events::id.eq(event_id_to_delete))
+                .filter(events::deleted_at.is_null()),
+        )
+        .set(events::deleted_at.eq(when))
+        .get_result(conn)
+        .optional()?;
+        if let Some(d) = &deleted {
+            record_audit(
+                conn,
+                actor,
+                d.guild_id,
+                "event",
+                d.id,
+                deleted_snapshot(d),
+                None,
+            )?;
+        }
+        Ok(())
+    })
 }
-pub fn get_event(conn: &mut PgConnection, event: &str) -> Result<Vec<ToddEvent>, Error> {
+pub fn get_event(
+    conn: &mut PgConnection,
+    guild: i64,
+    event: &str,
+) -> Result<Vec<ToddEvent>, ToddError> {
+    let _timer = metrics::query_timer("get_event");
+    use crate::schema::events::dsl::*;
     if let Ok(p) = event.parse::<i32>() {
-        let output = vec![get_event_by_id(conn, p)?];
+        let output = vec![events
+            .filter(guild_id.eq(guild))
+            .filter(id.eq(p))
+            .filter(deleted_at.is_null())
+            .first(conn)?];
         Ok(output)
     } else {
-        get_event_by_title(conn, event)
+        get_event_by_title(conn, guild, event)
     }
 }
 pub fn get_event_by_title(
     conn: &mut PgConnection,
+    guild: i64,
     event_title: &str,
-) -> Result<Vec<ToddEvent>, Error> {
+) -> Result<Vec<ToddEvent>, ToddError> {
+    let _timer = metrics::query_timer("get_event_by_title");
     use crate::schema::events::dsl::*;
     let output = events
+        .filter(guild_id.eq(guild))
         .filter(title.eq(event_title))
+        .filter(deleted_at.is_null())
         .load::<ToddEvent>(conn)?;
     Ok(output)
 }
-pub fn get_event_by_id(conn: &mut PgConnection, event_id: i32) -> Result<ToddEvent, Error> {
+pub fn get_event_by_id(
+    conn: &mut PgConnection,
+    guild: i64,
+    event_id: i32,
+) -> Result<ToddEvent, ToddError> {
+    let _timer = metrics::query_timer("get_event_by_id");
     use crate::schema::events::dsl::*;
-    let output = events.filter(id.eq(event_id)).first(conn)?;
+    let output = events
+        .filter(guild_id.eq(guild))
+        .filter(id.eq(event_id))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
     Ok(output)
 }
 pub fn get_birthday(
     conn: &mut PgConnection,
-    member: SchlonghouseMember,
-) -> Result<ToddEvent, Error> {
+    member: &SchlonghouseMember,
+) -> Result<ToddEvent, ToddError> {
+    let _timer = metrics::query_timer("get_birthday");
     use crate::schema::events::dsl::*;
     let output = events
+        .filter(guild_id.eq(member.guild_id))
         .filter(title.eq("Birthday".to_string()))
         .filter(owned_by.eq(member.id))
+        .filter(deleted_at.is_null())
         .first(conn)?;
     Ok(output)
 }
+pub fn get_events_owned_by(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<Vec<ToddEvent>, ToddError> {
+    let _timer = metrics::query_timer("get_events_owned_by");
+    use crate::schema::events::dsl::*;
+    let output = events
+        .filter(guild_id.eq(member.guild_id))
+        .filter(owned_by.eq(member.id))
+        .filter(deleted_at.is_null())
+        .order(timedate)
+        .load::<ToddEvent>(conn)?;
+    Ok(output)
+}
+pub fn count_members_quotes(
+    conn: &mut PgConnection,
+    member: &SchlonghouseMember,
+) -> Result<i64, ToddError> {
+    let _timer = metrics::query_timer("count_members_quotes");
+    use crate::schema::quotes::dsl::*;
+    let output = quotes
+        .filter(guild_id.eq(member.guild_id))
+        .filter(quoted.eq(&member.primary_name))
+        .filter(legacy.eq(false))
+        .filter(deleted_at.is_null())
+        .filter(approved.eq(true))
+        .count()
+        .get_result(conn)?;
+    Ok(output)
+}
 pub fn create_reminder(
     conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
     new_time_before: NaiveDateTime,
     owned_by_event_id: i32,
-) -> Result<Reminder, Error> {
+) -> Result<Reminder, ToddError> {
+    let _timer = metrics::query_timer("create_reminder");
     use crate::schema::reminders;
     let new_reminder = NewReminder {
         time_before: &new_time_before,
         event_id: owned_by_event_id,
+        guild_id: guild,
     };
-    let output = diesel::insert_into(reminders::table)
-        .values(&new_reminder)
-        .get_result(conn)?;
-    Ok(output)
+    conn.transaction(|conn| {
+        let output: Reminder = diesel::insert_into(reminders::table)
+            .values(&new_reminder)
+            .get_result(conn)?;
+        record_audit(
+            conn,
+            actor,
+            guild,
+            "reminder",
+            output.id,
+            None,
+            snapshot(&output),
+        )?;
+        Ok(output)
+    })
 }
 pub fn delete_reminder_by_id(
     conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
     reminder_id_to_delete: i32,
-) -> Result<(), Error> {
+) -> Result<(), ToddError> {
+    let _timer = metrics::query_timer("delete_reminder_by_id");
     use crate::schema::reminders;
-    diesel::delete(reminders::table.filter(reminders::id.eq(reminder_id_to_delete)))
-        .execute(conn)?;
-    Ok(())
+    conn.transaction(|conn| {
+        let deleted: Option<Reminder> = diesel::update(
+            reminders::table
+                .filter(reminders::guild_id.eq(guild))
+                .filter(reminders::id.eq(reminder_id_to_delete))
+                .filter(reminders::deleted_at.is_null()),
+        )
+        .set(reminders::deleted_at.eq(Local::now().naive_local()))
+        .get_result(conn)
+        .optional()?;
+        if let Some(d) = &deleted {
+            record_audit(
+                conn,
+                actor,
+                d.guild_id,
+                "reminder",
+                d.id,
+                deleted_snapshot(d),
+                None,
+            )?;
+        }
+        Ok(())
+    })
+}
+/// Counts another try at announcing a reminder, returning how many there
+/// have been. Bookkeeping, so it isn't audited.
+pub fn record_reminder_attempt(
+    conn: &mut PgConnection,
+    guild: i64,
+    reminder_id: i32,
+) -> Result<i32, ToddError> {
+    let _timer = metrics::query_timer("record_reminder_attempt");
+    use crate::schema::reminders::dsl::*;
+    let output = diesel::update(reminders.filter(guild_id.eq(guild)).find(reminder_id))
+        .set(attempts.eq(attempts + 1))
+        .returning(attempts)
+        .get_result(conn)?;
+    Ok(output)
 }
+
 pub fn get_reminders_from_event(
     conn: &mut PgConnection,
+    guild: i64,
     todd_event: &ToddEvent,
-) -> Result<Vec<Reminder>, Error> {
+) -> Result<Vec<Reminder>, ToddError> {
+    let _timer = metrics::query_timer("get_reminders_from_event");
     use crate::schema::reminders;
     let event_reminders = reminders::table
+        .filter(reminders::guild_id.eq(guild))
         .filter(reminders::event_id.eq(todd_event.id))
+        .filter(reminders::deleted_at.is_null())
         .load::<Reminder>(conn)?;
     Ok(event_reminders)
 }
-pub fn get_reminder_from_id(conn: &mut PgConnection, input_id: i32) -> Result<Reminder, Error> {
+pub fn get_reminder_from_id(
+    conn: &mut PgConnection,
+    guild: i64,
+    input_id: i32,
+) -> Result<Reminder, ToddError> {
+    let _timer = metrics::query_timer("get_reminder_from_id");
     use crate::schema::reminders::dsl::*;
-    let output = reminders.filter(id.eq(input_id)).first(conn)?;
+    let output = reminders
+        .filter(guild_id.eq(guild))
+        .filter(id.eq(input_id))
+        .filter(deleted_at.is_null())
+        .first(conn)?;
     Ok(output)
 }
-pub fn get_all_events(conn: &mut PgConnection) -> Result<Vec<ToddEvent>, Error> {
+pub fn get_all_events(conn: &mut PgConnection, guild: i64) -> Result<Vec<ToddEvent>, ToddError> {
+    let _timer = metrics::query_timer("get_all_events");
     use crate::schema::events::dsl::*;
-    let output = events.load::<ToddEvent>(conn)?;
+    let output = events
+        .filter(guild_id.eq(guild))
+        .filter(deleted_at.is_null())
+        .load::<ToddEvent>(conn)?;
+    Ok(output)
+}
+pub fn get_all_reminders(conn: &mut PgConnection, guild: i64) -> Result<Vec<Reminder>, ToddError> {
+    let _timer = metrics::query_timer("get_all_reminders");
+    use crate::schema::reminders::dsl::*;
+    let output = reminders
+        .filter(guild_id.eq(guild))
+        .filter(deleted_at.is_null())
+        .load::<Reminder>(conn)?;
+    Ok(output)
+}
+
+/// What `undo_last_deletion` brought back.
+#[derive(Debug)]
+pub enum Restored {
+    Quote(Quote),
+    /// The event and how many of its reminders came back with it.
+    Event(ToddEvent, usize),
+    Reminder(Reminder),
+    /// The member and how many of their quotes and events came back with them.
+    Member(SchlonghouseMember, usize, usize),
+}
+
+/// Restores the newest of the actor's deletions in `guild` made since
+/// `since` that can still be restored, skipping ones already undone. Only
+/// members, quotes, events and reminders are soft deleted, so only they can
+/// come back.
+pub fn undo_last_deletion(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    since: NaiveDateTime,
+) -> Result<Option<Restored>, ToddError> {
+    let _timer = metrics::query_timer("undo_last_deletion");
+    use crate::schema::audit_log;
+    let Some(user) = actor.user_id else {
+        return Ok(None);
+    };
+    conn.transaction(|conn| {
+        let deletions: Vec<AuditEntry> = audit_log::table
+            .filter(audit_log::guild_id.eq(guild))
+            .filter(audit_log::actor_id.eq(user))
+            .filter(audit_log::action.eq(Action::Delete.name()))
+            .filter(audit_log::entity_type.eq_any(["member", "quote", "event", "reminder"]))
+            .filter(audit_log::created_at.ge(since))
+            .order(audit_log::id.desc())
+            .load(conn)?;
+        for entry in deletions {
+            if entry.entity_type == "member" {
+                let Ok(member) = entry.entity_id.parse::<i64>() else {
+                    continue;
+                };
+                let restored = restore_member(conn, actor, guild, member)?
+                    .map(|(m, q, e)| Restored::Member(m, q, e));
+                if restored.is_some() {
+                    return Ok(restored);
+                }
+                continue;
+            }
+            let Ok(entity) = entry.entity_id.parse::<i32>() else {
+                continue;
+            };
+            let restored = match entry.entity_type.as_str() {
+                "quote" => restore_quote(conn, actor, guild, entity)?.map(Restored::Quote),
+                "event" => {
+                    restore_event(conn, actor, guild, entity)?.map(|(e, r)| Restored::Event(e, r))
+                }
+                _ => restore_reminder(conn, actor, guild, entity)?.map(Restored::Reminder),
+            };
+            if restored.is_some() {
+                return Ok(restored);
+            }
+        }
+        Ok(None)
+    })
+}
+
+/// Brings the member back along with everything removed with them. Fails
+/// if someone else has taken their name since, and nicknames someone else
+/// has taken stay deleted.
+fn restore_member(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    member_id: i64,
+) -> Result<Option<(SchlonghouseMember, usize, usize)>, ToddError> {
+    use crate::schema::{events, members, nicknames, quotes};
+    let Some(removed) = members::table
+        .find((guild, member_id))
+        .filter(members::deleted_at.is_not_null())
+        .first::<SchlonghouseMember>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    check_name_available(conn, guild, &removed.primary_name, None)?;
+    let restored: SchlonghouseMember = diesel::update(members::table.find((guild, member_id)))
+        .set(members::deleted_at.eq(None::<NaiveDateTime>))
+        .get_result(conn)?;
+    let after = snapshot(&restored);
+    record_audit_action(
+        conn,
+        actor,
+        guild,
+        Action::Restore,
+        "member",
+        restored.id,
+        None,
+        after,
+    )?;
+
+    let removed_nicknames: Vec<Nickname> = nicknames::table
+        .filter(nicknames::guild_id.eq(guild))
+        .filter(nicknames::primary_name.eq(member_id))
+        .filter(nicknames::deleted_at.eq(removed.deleted_at))
+        .load(conn)?;
+    for n in removed_nicknames {
+        if check_name_available(conn, guild, &n.nickname, None).is_err() {
+            continue;
+        }
+        let restored_nickname: Nickname = diesel::update(nicknames::table.find(n.id))
+            .set(nicknames::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result(conn)?;
+        let after = snapshot(&restored_nickname);
+        record_audit_action(
+            conn,
+            actor,
+            guild,
+            Action::Restore,
+            "nickname",
+            n.id,
+            None,
+            after,
+        )?;
+    }
+    let removed_quotes: Vec<i32> = quotes::table
+        .filter(quotes::guild_id.eq(guild))
+        .filter(quotes::quoted.eq(&removed.primary_name))
+        .filter(quotes::deleted_at.eq(removed.deleted_at))
+        .select(quotes::id)
+        .load(conn)?;
+    let mut restored_quotes = 0;
+    for q in removed_quotes {
+        restored_quotes += restore_quote(conn, actor, guild, q)?.map_or(0, |_| 1);
+    }
+    let removed_events: Vec<i32> = events::table
+        .filter(events::guild_id.eq(guild))
+        .filter(events::owned_by.eq(member_id))
+        .filter(events::deleted_at.eq(removed.deleted_at))
+        .select(events::id)
+        .load(conn)?;
+    let mut restored_events = 0;
+    for e in removed_events {
+        restored_events += restore_event(conn, actor, guild, e)?.map_or(0, |_| 1);
+    }
+    Ok(Some((restored, restored_quotes, restored_events)))
+}
+
+fn restore_quote(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    quote_id: i32,
+) -> Result<Option<Quote>, ToddError> {
+    use crate::schema::quotes;
+    let restored: Option<Quote> = diesel::update(
+        quotes::table
+            .filter(quotes::guild_id.eq(guild))
+            .filter(quotes::id.eq(quote_id))
+            .filter(quotes::deleted_at.is_not_null()),
+    )
+    .set(quotes::deleted_at.eq(None::<NaiveDateTime>))
+    .get_result(conn)
+    .optional()?;
+    if let Some(q) = &restored {
+        let after = snapshot(q);
+        record_audit_action(
+            conn,
+            actor,
+            guild,
+            Action::Restore,
+            "quote",
+            q.id,
+            None,
+            after,
+        )?;
+    }
+    Ok(restored)
+}
+
+/// Brings the event back along with the reminders deleted with it.
+fn restore_event(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    event_id: i32,
+) -> Result<Option<(ToddEvent, usize)>, ToddError> {
+    use crate::schema::{events, reminders};
+    let Some(deleted) = events::table
+        .filter(events::guild_id.eq(guild))
+        .filter(events::id.eq(event_id))
+        .filter(events::deleted_at.is_not_null())
+        .first::<ToddEvent>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let restored: ToddEvent = diesel::update(events::table.find(event_id))
+        .set(events::deleted_at.eq(None::<NaiveDateTime>))
+        .get_result(conn)?;
+    let after = snapshot(&restored);
+    record_audit_action(
+        conn,
+        actor,
+        guild,
+        Action::Restore,
+        "event",
+        restored.id,
+        None,
+        after,
+    )?;
+    let restored_reminders: Vec<Reminder> = diesel::update(
+        reminders::table
+            .filter(reminders::guild_id.eq(guild))
+            .filter(reminders::event_id.eq(event_id))
+            .filter(reminders::deleted_at.eq(deleted.deleted_at)),
+    )
+    .set(reminders::deleted_at.eq(None::<NaiveDateTime>))
+    .get_results(conn)?;
+    for r in &restored_reminders {
+        let after = snapshot(r);
+        record_audit_action(
+            conn,
+            actor,
+            guild,
+            Action::Restore,
+            "reminder",
+            r.id,
+            None,
+            after,
+        )?;
+    }
+    Ok(Some((restored, restored_reminders.len())))
+}
+
+/// Reminders only come back while their event is still around.
+fn restore_reminder(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    reminder_id: i32,
+) -> Result<Option<Reminder>, ToddError> {
+    use crate::schema::{events, reminders};
+    let live_events = events::table
+        .filter(events::guild_id.eq(guild))
+        .filter(events::deleted_at.is_null())
+        .select(events::id);
+    let restored: Option<Reminder> = diesel::update(
+        reminders::table
+            .filter(reminders::guild_id.eq(guild))
+            .filter(reminders::id.eq(reminder_id))
+            .filter(reminders::deleted_at.is_not_null())
+            .filter(reminders::event_id.eq_any(live_events)),
+    )
+    .set(reminders::deleted_at.eq(None::<NaiveDateTime>))
+    .get_result(conn)
+    .optional()?;
+    if let Some(r) = &restored {
+        let after = snapshot(r);
+        record_audit_action(
+            conn,
+            actor,
+            guild,
+            Action::Restore,
+            "reminder",
+            r.id,
+            None,
+            after,
+        )?;
+    }
+    Ok(restored)
+}
+
+/// Reminders that haven't been announced yet and are still to come.
+pub fn count_pending_reminders(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> Result<i64, ToddError> {
+    let _timer = metrics::query_timer("count_pending_reminders");
+    use crate::schema::reminders::dsl::*;
+    let output = reminders
+        .filter(deleted_at.is_null())
+        .filter(time_before.ge(now))
+        .count()
+        .get_result(conn)?;
     Ok(output)
 }
-pub fn get_all_reminders(conn: &mut PgConnection) -> Result<Vec<Reminder>, Error> {
+
+/// Reminders that are still waiting to be announced even though their time
+/// came and went.
+#[cfg(feature = "metrics")]
+pub fn count_overdue_reminders(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> Result<i64, ToddError> {
+    let _timer = metrics::query_timer("count_overdue_reminders");
     use crate::schema::reminders::dsl::*;
-    let output = reminders.load::<Reminder>(conn)?;
+    // A tick announces anything due within the next minute, so only count
+    // reminders the last tick should have caught
+    let output = reminders
+        .filter(deleted_at.is_null())
+        .filter(time_before.lt(now - chrono::Duration::minutes(1)))
+        .count()
+        .get_result(conn)?;
+    Ok(output)
+}
+
+#[cfg(feature = "metrics")]
+pub fn count_quotes_by_guild(conn: &mut PgConnection) -> Result<Vec<(i64, i64)>, ToddError> {
+    let _timer = metrics::query_timer("count_quotes_by_guild");
+    use crate::schema::quotes::dsl::*;
+    let output = quotes
+        .filter(deleted_at.is_null())
+        .filter(approved.eq(true))
+        .group_by(guild_id)
+        .select((guild_id, diesel::dsl::count_star()))
+        .load(conn)?;
+    Ok(output)
+}
+
+#[cfg(feature = "metrics")]
+pub fn count_events_by_guild(conn: &mut PgConnection) -> Result<Vec<(i64, i64)>, ToddError> {
+    let _timer = metrics::query_timer("count_events_by_guild");
+    use crate::schema::events::dsl::*;
+    let output = events
+        .filter(deleted_at.is_null())
+        .group_by(guild_id)
+        .select((guild_id, diesel::dsl::count_star()))
+        .load(conn)?;
+    Ok(output)
+}
+
+/// The guilds with anything deleted before `before`, see `purge_deleted`.
+pub fn get_guilds_with_deleted(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> Result<BTreeSet<i64>, ToddError> {
+    let _timer = metrics::query_timer("get_guilds_with_deleted");
+    use crate::schema::{events, members, nicknames, quotes, reminders};
+    let mut output = BTreeSet::new();
+    output.extend(
+        members::table
+            .filter(members::deleted_at.lt(before))
+            .select(members::guild_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    output.extend(
+        nicknames::table
+            .filter(nicknames::deleted_at.lt(before))
+            .select(nicknames::guild_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    output.extend(
+        events::table
+            .filter(events::deleted_at.lt(before))
+            .select(events::guild_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    output.extend(
+        reminders::table
+            .filter(reminders::deleted_at.lt(before))
+            .select(reminders::guild_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    output.extend(
+        quotes::table
+            .filter(quotes::deleted_at.lt(before))
+            .select(quotes::guild_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
     Ok(output)
 }
+
+/// Permanently removes `guild`'s members, nicknames, quotes, events and
+/// reminders deleted before `before`. Returns how many rows went.
+pub fn purge_deleted(
+    conn: &mut PgConnection,
+    guild: i64,
+    before: NaiveDateTime,
+) -> Result<usize, ToddError> {
+    let _timer = metrics::query_timer("purge_deleted");
+    use crate::schema::{events, members, nicknames, quotes, reminders};
+    conn.transaction(|conn| {
+        let old_members: Vec<i64> = members::table
+            .filter(members::guild_id.eq(guild))
+            .filter(members::deleted_at.lt(before))
+            .select(members::id)
+            .load(conn)?;
+        let mut output = 0;
+        // Events restored on their own since still belong to the member, so
+        // they go with them
+        for member in old_members {
+            output += purge_member(conn, guild, member)?;
+        }
+        output += diesel::delete(
+            nicknames::table
+                .filter(nicknames::guild_id.eq(guild))
+                .filter(nicknames::deleted_at.lt(before)),
+        )
+        .execute(conn)?;
+        let old_events = events::table
+            .filter(events::guild_id.eq(guild))
+            .filter(events::deleted_at.lt(before));
+        output += diesel::delete(
+            reminders::table
+                .filter(reminders::guild_id.eq(guild))
+                .filter(
+                    reminders::deleted_at
+                        .lt(before)
+                        .or(reminders::event_id.eq_any(old_events.select(events::id))),
+                ),
+        )
+        .execute(conn)?;
+        output += diesel::delete(old_events).execute(conn)?;
+        output += diesel::delete(
+            quotes::table
+                .filter(quotes::guild_id.eq(guild))
+                .filter(quotes::deleted_at.lt(before)),
+        )
+        .execute(conn)?;
+        Ok(output)
+    })
+}
+
+#[cfg(test)]
+mod databaser_tests {
+    use super::*;
+    use crate::Error;
+
+    // Test data lives in its own guild so it can't collide with real members
+    const GUILD: i64 = -1;
+
+    fn actor() -> Actor {
+        Actor::bot("test")
+    }
+
+    fn sample_member(primary_name: &str) -> SchlonghouseMember {
+        SchlonghouseMember {
+            id: 0,
+            primary_name: primary_name.to_string(),
+            is_member: false,
+            created_at: None,
+            guild_id: GUILD,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_get_random_quote_avoids_recent() -> Result<(), Error> {
+        use crate::schema::quotes;
+
+        let mut conn = establish_connection()?;
+        let owner = "sample_random_quote";
+        for q in ["foo", "bar", "baz"] {
+            create_quote(&mut conn, &actor(), GUILD, owner, q)?;
+        }
+        let selection = QuoteSelection {
+            avoid_last: 2,
+            channel_id: Some(1),
+            weighting: QuoteWeighting::Recency,
+        };
+
+        let mut served = vec![];
+        let mut result = Ok(());
+        for _ in 0..6 {
+            let q = get_random_quote(&mut conn, &sample_member(owner), &selection)?;
+            if served.iter().rev().take(2).any(|id| *id == q.id) {
+                result = Err(Error::from(format!("Quote repeated: {:?}", q)));
+                break;
+            }
+            record_served_quote(&mut conn, &q, 1, None)?;
+            served.push(q.id);
+        }
+
+        diesel::delete(quotes::table.filter(quotes::quoted.eq(owner))).execute(&mut conn)?;
+        result
+    }
+
+    #[test]
+    fn test_quote_votes_and_leaderboard() -> Result<(), Error> {
+        use crate::schema::quotes;
+
+        let mut conn = establish_connection()?;
+        let owner = "sample_quote_votes";
+        let good = create_quote(&mut conn, &actor(), GUILD, owner, "good")?;
+        let bad = create_quote(&mut conn, &actor(), GUILD, owner, "bad")?;
+        set_quote_vote(&mut conn, GUILD, good.id, 1, 1)?;
+        set_quote_vote(&mut conn, GUILD, good.id, 2, 1)?;
+        set_quote_vote(&mut conn, GUILD, bad.id, 1, 1)?;
+        // Changing a vote replaces it rather than adding a second one
+        set_quote_vote(&mut conn, GUILD, bad.id, 1, -1)?;
+        // Removing the wrong vote leaves the existing one alone
+        remove_quote_vote(&mut conn, GUILD, good.id, 2, -1)?;
+
+        let score = get_quote_score(&mut conn, GUILD, good.id)?;
+        // Another server can't vote on it or see its votes
+        let other_vote = set_quote_vote(&mut conn, GUILD - 1, good.id, 3, 1);
+        let other_score = get_quote_score(&mut conn, GUILD - 1, good.id)?;
+        let top = get_quote_leaderboard(&mut conn, GUILD, Some(owner), true, 10)?;
+        let worst = get_quote_leaderboard(&mut conn, GUILD, Some(owner), false, 10)?;
+        let picked = get_random_quote(&mut conn, &sample_member(owner), &QuoteSelection::default());
+
+        diesel::delete(quotes::table.filter(quotes::quoted.eq(owner))).execute(&mut conn)?;
+        assert_eq!(score.total(), 2);
+        assert!(matches!(other_vote, Err(ToddError::NotFound(_))));
+        assert_eq!(other_score.total(), 0);
+        assert_eq!(top[0], (good, 2));
+        assert_eq!(worst[0], (bad, -1));
+        assert!(picked.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_guild_settings_are_stored_on_first_write() -> Result<(), Error> {
+        use crate::schema::{audit_log, guild_settings};
+        let guild = -43;
+        let mut conn = establish_connection()?;
+        let read = get_guild_settings(&mut conn, guild)?;
+        let stored_after_read = guild_settings::table
+            .find(guild)
+            .count()
+            .get_result::<i64>(&mut conn)?;
+        let written =
+            update_guild_settings(&mut conn, &actor(), guild, &UpdateGuildSettings::default())?;
+
+        diesel::delete(audit_log::table.filter(audit_log::guild_id.eq(guild)))
+            .execute(&mut conn)?;
+        diesel::delete(guild_settings::table.find(guild)).execute(&mut conn)?;
+        assert_eq!(stored_after_read, 0);
+        assert_eq!(
+            GuildSettings {
+                updated_at: written.updated_at,
+                ..read
+            },
+            written
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quotes_wait_for_approval() -> Result<(), Error> {
+        use crate::schema::{audit_log, guild_settings, quotes};
+        // A guild of its own, so quotes other tests add aren't held
+        let guild = -40;
+        let mut conn = establish_connection()?;
+        let changes = UpdateGuildSettings {
+            quote_approval: Some(true),
+            ..Default::default()
+        };
+        update_guild_settings(&mut conn, &actor(), guild, &changes)?;
+        let quote = create_quote(&mut conn, &actor(), guild, "sample_pending", "held")?;
+        let member = SchlonghouseMember {
+            guild_id: guild,
+            ..sample_member("sample_pending")
+        };
+        let held = get_random_quote(&mut conn, &member, &QuoteSelection::default()).is_err();
+        let pending = get_pending_quotes(&mut conn, guild)?;
+        let approved = approve_quote(&mut conn, &actor(), guild, quote.id);
+        let approved_again = approve_quote(&mut conn, &actor(), guild, quote.id);
+        let served = get_random_quote(&mut conn, &member, &QuoteSelection::default());
+
+        diesel::delete(quotes::table.filter(quotes::guild_id.eq(guild))).execute(&mut conn)?;
+        diesel::delete(audit_log::table.filter(audit_log::guild_id.eq(guild)))
+            .execute(&mut conn)?;
+        diesel::delete(guild_settings::table.find(guild)).execute(&mut conn)?;
+        assert!(!quote.approved);
+        assert!(held);
+        assert_eq!(pending, [quote]);
+        assert!(approved?.approved);
+        assert!(matches!(approved_again, Err(ToddError::NotFound(_))));
+        assert!(served?.approved);
+        Ok(())
+    }
+
+    #[test]
+    fn test_daily_quote_is_claimed_once() -> Result<(), Error> {
+        use crate::schema::{daily_quotes, quotes};
+
+        let mut conn = establish_connection()?;
+        let quote = create_quote(&mut conn, &actor(), GUILD, "sample_daily", "claimed")?;
+        let day = NaiveDate::from_ymd_opt(2001, 2, 3).unwrap();
+        let claimed = create_daily_quote(&mut conn, &quote, day, 1)?;
+        let claimed_again = create_daily_quote(&mut conn, &quote, day, 1)?;
+        // A claim that couldn't be posted is given up
+        if let Some(d) = &claimed {
+            delete_daily_quote(&mut conn, d)?;
+        }
+        let reclaimed = create_daily_quote(&mut conn, &quote, day, 1)?;
+        // but not once it's been posted
+        if let Some(d) = &reclaimed {
+            set_daily_quote_message(&mut conn, d, 2)?;
+            delete_daily_quote(&mut conn, d)?;
+        }
+        let posted = get_daily_quote_on(&mut conn, GUILD, day)?;
+
+        diesel::delete(daily_quotes::table.filter(daily_quotes::quote_id.eq(quote.id)))
+            .execute(&mut conn)?;
+        diesel::delete(quotes::table.find(quote.id)).execute(&mut conn)?;
+        assert_eq!(claimed.map(|d| d.message_id), Some(None));
+        assert!(claimed_again.is_none());
+        assert!(reclaimed.is_some());
+        assert_eq!(posted.and_then(|d| d.message_id), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_changes_bump_the_generation() -> Result<(), Error> {
+        use crate::schema::{quote_generations, quotes};
+
+        let mut conn = establish_connection()?;
+        let member = sample_member("sample_generation");
+        let before = get_quote_generation(&mut conn, &member)?;
+        let quote = create_quote(&mut conn, &actor(), GUILD, "sample_generation", "one")?;
+        let added = get_quote_generation(&mut conn, &member)?;
+        get_all_members_quotes(&mut conn, &member)?;
+        let read = get_quote_generation(&mut conn, &member)?;
+        delete_quote_by_id(&mut conn, &actor(), GUILD, quote.id)?;
+        let deleted = get_quote_generation(&mut conn, &member)?;
+        // Changes that don't go through here count too
+        diesel::update(quotes::table.find(quote.id))
+            .set(quotes::deleted_at.eq(None::<NaiveDateTime>))
+            .execute(&mut conn)?;
+        let restored = get_quote_generation(&mut conn, &member)?;
+
+        diesel::delete(quotes::table.filter(quotes::quoted.eq("sample_generation")))
+            .execute(&mut conn)?;
+        diesel::delete(
+            quote_generations::table.filter(quote_generations::quoted.eq("sample_generation")),
+        )
+        .execute(&mut conn)?;
+        assert!(added > before);
+        assert_eq!(read, added);
+        assert!(deleted > added);
+        assert!(restored > deleted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_legacy_quotes() -> Result<(), Error> {
+        use crate::schema::quotes;
+
+        let mut conn = establish_connection()?;
+        let owner = "sample_legacy";
+        let lines: Vec<String> = ["one", "", "two", "one", "three"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let first = import_legacy_quotes(&mut conn, &actor(), GUILD, owner, &lines)?;
+        let second = import_legacy_quotes(&mut conn, &actor(), GUILD, owner, &lines)?;
+        let legacy = get_legacy_quotes(&mut conn, &sample_member(owner))?;
+        let current = get_all_members_quotes(&mut conn, &sample_member(owner))?;
+
+        diesel::delete(quotes::table.filter(quotes::quoted.eq(owner))).execute(&mut conn)?;
+        assert_eq!(first, (3, 1));
+        assert_eq!(second, (0, 4));
+        let legacy: Vec<&str> = legacy.iter().map(|q| q.quote.as_str()).collect();
+        assert_eq!(legacy, ["one", "two", "three"]);
+        assert!(
+            current.is_empty(),
+            "Legacy quotes leaked into current quotes"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_member_reassigns_data() -> Result<(), Error> {
+        let mut conn = establish_connection()?;
+        let old = create_member(&mut conn, &actor(), GUILD, -35001, "sample_removed", false)?;
+        let target = create_member(
+            &mut conn,
+            &actor(),
+            GUILD,
+            -35002,
+            "sample_reassigned",
+            false,
+        )?;
+        create_nickname(&mut conn, &actor(), &old, "sample_removed_nick")?;
+        create_quote(&mut conn, &actor(), GUILD, &old.primary_name, "moved")?;
+        let when = chrono::Local::now().naive_local();
+        create_event(
+            &mut conn,
+            &actor(),
+            GUILD,
+            "Birthday",
+            "",
+            when,
+            true,
+            old.id,
+            Some(1),
+        )?;
+        create_event(
+            &mut conn,
+            &actor(),
+            GUILD,
+            "Party",
+            "",
+            when,
+            false,
+            old.id,
+            None,
+        )?;
+
+        let removed = remove_member_and_data(&mut conn, &actor(), &old, Some(&target));
+        let moved_quotes = count_members_quotes(&mut conn, &target);
+        let moved_events = get_events_owned_by(&mut conn, &target);
+        let resolved = get_member_from_name(&mut conn, GUILD, &old.primary_name);
+        let gone = get_member(&mut conn, GUILD, &old.id.to_string()).is_err();
+        remove_member_and_data(&mut conn, &actor(), &target, None)?;
+
+        let removed = removed?;
+        assert_eq!(removed.quotes, 1);
+        assert_eq!(removed.nicknames, 1);
+        // The birthday is deleted and the party moved
+        assert_eq!(removed.events_deleted, 1);
+        assert_eq!(removed.events_moved, 1);
+        assert_eq!(moved_quotes?, 1);
+        assert_eq!(moved_events?.len(), 1);
+        assert_eq!(resolved?.id, target.id);
+        assert!(gone);
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_are_audited() -> Result<(), Error> {
+        let mut conn = establish_connection()?;
+        let member = create_member(&mut conn, &actor(), GUILD, -38001, "sample_audited", false)?;
+        let when = chrono::Local::now().naive_local();
+        let event = create_event(
+            &mut conn,
+            &actor(),
+            GUILD,
+            "Audited",
+            "",
+            when,
+            false,
+            member.id,
+            None,
+        )?;
+        let renamed = rename_member(&mut conn, &actor(), &member, "sample_reaudited")?;
+        delete_event_by_id(&mut conn, &actor(), GUILD, event.id)?;
+        remove_member_and_data(&mut conn, &actor(), &renamed, None)?;
+
+        let event_history =
+            get_audit_entries_for(&mut conn, GUILD, "event", &event.id.to_string())?;
+        let member_history =
+            get_audit_entries_for(&mut conn, GUILD, "member", &member.id.to_string())?;
+        {
+            use crate::schema::audit_log;
+            diesel::delete(
+                audit_log::table
+                    .filter(audit_log::guild_id.eq(GUILD))
+                    .filter(
+                        audit_log::entity_id.eq_any([event.id.to_string(), member.id.to_string()]),
+                    ),
+            )
+            .execute(&mut conn)?;
+        }
+
+        let actions = |h: &[AuditEntry]| h.iter().map(|e| e.action.clone()).collect::<Vec<_>>();
+        assert_eq!(actions(&event_history), ["create", "delete"]);
+        assert_eq!(actions(&member_history), ["create", "update", "delete"]);
+        assert_eq!(event_history[0].after, snapshot(&event));
+        assert_eq!(event_history[1].before, snapshot(&event));
+        assert_eq!(member_history[1].after, snapshot(&renamed));
+        assert!(member_history.iter().all(|e| e.command == "test"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deleted_quotes_can_be_undone() -> Result<(), Error> {
+        use crate::schema::{audit_log, quotes};
+        let mut conn = establish_connection()?;
+        let someone = Actor {
+            user_id: Some(-39001),
+            command: "test".to_string(),
+        };
+        let since = Local::now().naive_local() - chrono::Duration::minutes(5);
+        let first = create_quote(&mut conn, &someone, GUILD, "sample_undone", "first")?;
+        let second = create_quote(&mut conn, &someone, GUILD, "sample_undone", "second")?;
+        delete_quote_by_id(&mut conn, &someone, GUILD, first.id)?;
+        delete_quote_by_id(&mut conn, &someone, GUILD, second.id)?;
+
+        let hidden = get_quote_by_id(&mut conn, GUILD, second.id).is_err();
+        let not_theirs = undo_last_deletion(&mut conn, &actor(), GUILD, since)?;
+        let undone = undo_last_deletion(&mut conn, &someone, GUILD, since)?;
+        let shown = get_quote_by_id(&mut conn, GUILD, second.id).is_ok();
+        let undone_again = undo_last_deletion(&mut conn, &someone, GUILD, since)?;
+        let nothing_left = undo_last_deletion(&mut conn, &someone, GUILD, since)?;
+
+        // Pretend they were deleted long ago so purging can't touch real data
+        let ids = [first.id, second.id];
+        let long_ago = NaiveDate::from_ymd_opt(2000, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        diesel::update(quotes::table.filter(quotes::id.eq_any(ids)))
+            .set(quotes::deleted_at.eq(long_ago))
+            .execute(&mut conn)?;
+        let to_purge = get_guilds_with_deleted(&mut conn, long_ago + chrono::Duration::days(1))?;
+        let purged = purge_deleted(&mut conn, GUILD, long_ago + chrono::Duration::days(1))?;
+        let left: i64 = quotes::table
+            .filter(quotes::id.eq_any(ids))
+            .count()
+            .get_result(&mut conn)?;
+        diesel::delete(
+            audit_log::table
+                .filter(audit_log::guild_id.eq(GUILD))
+                .filter(audit_log::entity_type.eq("quote"))
+                .filter(audit_log::entity_id.eq_any(ids.map(|i| i.to_string()))),
+        )
+        .execute(&mut conn)?;
+
+        assert!(hidden);
+        assert!(not_theirs.is_none());
+        assert!(matches!(undone, Some(Restored::Quote(q)) if q.id == second.id));
+        assert!(shown);
+        assert!(matches!(undone_again, Some(Restored::Quote(q)) if q.id == first.id));
+        assert!(nothing_left.is_none());
+        assert!(to_purge.contains(&GUILD));
+        assert_eq!(purged, 2);
+        assert_eq!(left, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deleted_event_comes_back_with_reminders() -> Result<(), Error> {
+        let mut conn = establish_connection()?;
+        let someone = Actor {
+            user_id: Some(-39002),
+            command: "test".to_string(),
+        };
+        let since = Local::now().naive_local() - chrono::Duration::minutes(5);
+        let member = create_member(&mut conn, &actor(), GUILD, -39002, "sample_restored", false)?;
+        let when = Local::now().naive_local() + chrono::Duration::days(1);
+        let event = create_event(
+            &mut conn, &someone, GUILD, "Restored", "", when, false, member.id, None,
+        )?;
+        create_reminder(&mut conn, &someone, GUILD, when, event.id)?;
+        create_reminder(&mut conn, &someone, GUILD, when, event.id)?;
+        delete_event_by_id(&mut conn, &someone, GUILD, event.id)?;
+
+        let hidden = get_reminders_from_event(&mut conn, GUILD, &event)?.len();
+        let undone = undo_last_deletion(&mut conn, &someone, GUILD, since);
+        let shown = get_reminders_from_event(&mut conn, GUILD, &event).map(|r| r.len());
+        let found = get_event_by_id(&mut conn, GUILD, event.id).map(|e| e.id);
+        let other_guild = get_event_by_id(&mut conn, GUILD - 1, event.id);
+        delete_event_by_id(&mut conn, &someone, GUILD, event.id)?;
+        // Purging the member takes the deleted event with them
+        purge_member(&mut conn, GUILD, member.id)?;
+
+        assert_eq!(hidden, 0);
+        assert!(matches!(undone?, Some(Restored::Event(e, 2)) if e.id == event.id));
+        assert_eq!(shown?, 2);
+        assert_eq!(found?, event.id);
+        assert!(matches!(other_guild, Err(ToddError::NotFound(_))));
+        assert!(get_event_by_id(&mut conn, GUILD, event.id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_removed_member_can_be_undone() -> Result<(), Error> {
+        use crate::schema::{audit_log, members, quotes};
+        let mut conn = establish_connection()?;
+        let someone = Actor {
+            user_id: Some(-39003),
+            command: "test".to_string(),
+        };
+        let since = Local::now().naive_local() - chrono::Duration::minutes(5);
+        let member = create_member(
+            &mut conn,
+            &actor(),
+            GUILD,
+            -39003,
+            "sample_unremoved",
+            false,
+        )?;
+        create_nickname(&mut conn, &actor(), &member, "sample_unremoved_nick")?;
+        let quote = create_quote(&mut conn, &actor(), GUILD, &member.primary_name, "back")?;
+        let when = Local::now().naive_local() + chrono::Duration::days(1);
+        let event = create_event(
+            &mut conn,
+            &actor(),
+            GUILD,
+            "Unremoved",
+            "",
+            when,
+            false,
+            member.id,
+            None,
+        )?;
+        create_reminder(&mut conn, &actor(), GUILD, when, event.id)?;
+
+        let removed = remove_member_and_data(&mut conn, &someone, &member, None)?;
+        let gone = get_member(&mut conn, GUILD, "sample_unremoved_nick").is_err();
+        let name_free = check_name_available(&mut conn, GUILD, &member.primary_name, None);
+        let undone = undo_last_deletion(&mut conn, &someone, GUILD, since);
+        let by_nickname = get_member(&mut conn, GUILD, "sample_unremoved_nick");
+        let reminders = get_reminders_from_event(&mut conn, GUILD, &event).map(|r| r.len());
+        let quote_back = get_quote_by_id(&mut conn, GUILD, quote.id).is_ok();
+
+        // Pretend they were removed long ago so purging can't touch real data
+        remove_member_and_data(&mut conn, &someone, &member, None)?;
+        let long_ago = NaiveDate::from_ymd_opt(2000, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        diesel::update(members::table.find((GUILD, member.id)))
+            .set(members::deleted_at.eq(long_ago))
+            .execute(&mut conn)?;
+        diesel::update(quotes::table.find(quote.id))
+            .set(quotes::deleted_at.eq(long_ago))
+            .execute(&mut conn)?;
+        let purged = purge_deleted(&mut conn, GUILD, long_ago + chrono::Duration::days(1))?;
+        diesel::delete(audit_log::table.filter(audit_log::actor_id.eq(someone.user_id)))
+            .execute(&mut conn)?;
+
+        assert_eq!(removed.quotes, 1);
+        assert_eq!(removed.nicknames, 1);
+        assert_eq!(removed.events_deleted, 1);
+        assert_eq!(removed.reminders, 1);
+        assert!(gone);
+        assert!(name_free.is_ok());
+        assert!(matches!(undone?, Some(Restored::Member(m, 1, 1)) if m.id == member.id));
+        assert_eq!(by_nickname?.id, member.id);
+        assert_eq!(reminders?, 1);
+        assert!(quote_back);
+        // The member, their nickname, event, reminder and quote
+        assert_eq!(purged, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("jake", "jake"), 0);
+        assert_eq!(edit_distance("jaek", "jake"), 2);
+        assert_eq!(edit_distance("jak", "jake"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_find_member_fuzzy() -> Result<(), Error> {
+        let mut conn = establish_connection()?;
+        let first = create_member(&mut conn, &actor(), GUILD, -36001, "Sample_Fuzzy", false)?;
+        let second = create_member(&mut conn, &actor(), GUILD, -36002, "sample_fuzzed", false)?;
+
+        let exact = find_member(&mut conn, GUILD, "sample_fuzzy");
+        let close = find_member(&mut conn, GUILD, "sample_fuzy");
+        let ambiguous = find_member(&mut conn, GUILD, "sample_fuzze");
+        let missing = find_member(&mut conn, GUILD, "nobody_at_all");
+        // Only `find_member` guesses
+        let exact_only = get_member(&mut conn, GUILD, "sample_fuzy");
+        let any_case = get_member(&mut conn, GUILD, "SAMPLE_FUZZY");
+        purge_member(&mut conn, GUILD, first.id)?;
+        purge_member(&mut conn, GUILD, second.id)?;
+
+        assert!(matches!(exact?, MemberMatch::Exact(m) if m.id == first.id));
+        assert!(matches!(close?, MemberMatch::Close(m, _) if m.id == first.id));
+        match ambiguous? {
+            MemberMatch::Ambiguous(c) => assert_eq!(c.len(), 2),
+            other => panic!("Expected ambiguous match, got {:?}", other),
+        }
+        assert!(matches!(missing?, MemberMatch::NotFound));
+        assert!(matches!(exact_only, Err(ToddError::NotFound(_))));
+        assert_eq!(any_case?.id, first.id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_names_are_rejected() -> Result<(), Error> {
+        let mut conn = establish_connection()?;
+        let owner = create_member(&mut conn, &actor(), GUILD, -37001, "sample_owner", false)?;
+        create_nickname(&mut conn, &actor(), &owner, "sample_owned")?;
+
+        let same_id = create_member(&mut conn, &actor(), GUILD, -37001, "sample_other", false);
+        let same_name = create_member(&mut conn, &actor(), GUILD, -37002, "SAMPLE_OWNER", false);
+        let same_nickname = create_nickname(&mut conn, &actor(), &owner, "Sample_Owned");
+        remove_member_and_data(&mut conn, &actor(), &owner, None)?;
+
+        let downcast = |r: Result<_, ToddError>| match r {
+            Err(ToddError::Name(e)) => Some(e),
+            _ => None,
+        };
+        assert_eq!(
+            downcast(same_id.map(|_| ())).as_ref(),
+            Some(&NameError::MemberExists {
+                id: -37001,
+                primary_name: "sample_owner".to_string()
+            })
+        );
+        assert_eq!(
+            downcast(same_name.map(|_| ())).as_ref(),
+            Some(&NameError::NameTaken {
+                name: "SAMPLE_OWNER".to_string(),
+                owner: "sample_owner".to_string()
+            })
+        );
+        assert_eq!(
+            downcast(same_nickname.map(|_| ())).as_ref(),
+            Some(&NameError::NicknameTaken {
+                nickname: "Sample_Owned".to_string(),
+                owner: "sample_owner".to_string()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_races_are_name_errors() -> Result<(), Error> {
+        use crate::schema::members;
+        let mut conn = establish_connection()?;
+        let owner = create_member(&mut conn, &actor(), GUILD, -37101, "sample_racer", false)?;
+        // What a second insert sees when it passed `check_name_available`
+        // just before the first one committed
+        let raced = diesel::insert_into(members::table)
+            .values(&NewMember {
+                id: -37102,
+                primary_name: "Sample_Racer",
+                is_member: false,
+                guild_id: GUILD,
+            })
+            .execute(&mut conn)
+            .map_err(ToddError::from);
+        let mapped = name_conflict(&mut conn, GUILD, "Sample_Racer", None, raced.unwrap_err());
+        let other = name_conflict(
+            &mut conn,
+            GUILD,
+            "Sample_Racer",
+            None,
+            ToddError::from(DieselError::BrokenTransactionManager),
+        );
+        remove_member_and_data(&mut conn, &actor(), &owner, None)?;
+
+        assert!(matches!(
+            mapped,
+            ToddError::Name(NameError::NameTaken { owner, .. }) if owner == "sample_racer"
+        ));
+        assert!(other.is_internal());
+        Ok(())
+    }
+
+    #[test]
+    fn test_guilds_are_isolated() -> Result<(), Error> {
+        const OTHER_GUILD: i64 = -2;
+
+        let mut conn = establish_connection()?;
+        let here = create_member(&mut conn, &actor(), GUILD, -39001, "sample_guilded", false)?;
+        // The same person and name can exist in another guild
+        let there = create_member(
+            &mut conn,
+            &actor(),
+            OTHER_GUILD,
+            -39001,
+            "sample_guilded",
+            false,
+        );
+        let quote = create_quote(&mut conn, &actor(), GUILD, &here.primary_name, "only here")?;
+
+        let other_quote = get_quote_by_id(&mut conn, OTHER_GUILD, quote.id);
+        let other_quotes = get_all_members_quotes(&mut conn, there.as_ref().unwrap_or(&here));
+        let stray = find_member(&mut conn, OTHER_GUILD, "sample_guilde");
+        remove_member_and_data(&mut conn, &actor(), &here, None)?;
+        if let Ok(there) = &there {
+            remove_member_and_data(&mut conn, &actor(), there, None)?;
+        }
+
+        assert!(there.is_ok());
+        assert!(other_quote.is_err());
+        assert!(other_quotes?.is_empty());
+        assert!(matches!(stray?, MemberMatch::Close(m, _) if m.guild_id == OTHER_GUILD));
+        Ok(())
+    }
+}
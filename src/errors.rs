@@ -1,46 +1,278 @@
 // errors.rs
 
+use crate::logging;
+use crate::metrics;
 use crate::{Context, Data, Error};
+use poise::serenity_prelude as serenity;
+use std::fmt;
 
 pub async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
     // This is our custom error handler
     // They are many errors that can occur, so we only handle the ones we want to customize
     // and forward the rest to the default handler
+    metrics::framework_error(kind(&error));
     match error {
         poise::FrameworkError::Setup { error, .. } => panic!("Failed to start bot: {:?}", error),
         poise::FrameworkError::Command { error, ctx } => {
-            println!("Error in command `{}`: {:?}", ctx.command().name, error,);
-            error_reply(ctx, error).await;
+            match error.downcast_ref::<ToddError>() {
+                Some(ToddError::PermissionDenied(message)) => {
+                    logging::command_failed(ctx, &error, None).await;
+                    private_reply(ctx, message.clone()).await
+                }
+                Some(e) if !e.is_internal() => {
+                    logging::command_failed(ctx, &error, None).await;
+                    let _ = ctx.reply(e.user_message()).await;
+                }
+                e => {
+                    // Only the logs get the details, the reference ties the
+                    // reply to them
+                    let reference = format!("{:08x}", rand::random::<u32>());
+                    logging::command_failed(ctx, &error, Some(&reference)).await;
+                    let message = e.map_or(INTERNAL_MESSAGE.to_string(), |e| e.user_message());
+                    let _ = ctx
+                        .reply(format!(
+                            "{}\nIf it keeps happening, pass on reference `{}`",
+                            message, reference
+                        ))
+                        .await;
+                }
+            }
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+        } => {
+            tracing::info!(
+                command = %ctx.command().qualified_name,
+                author = %ctx.author().name,
+                %error,
+                "command check failed"
+            );
+            private_reply(ctx, error.to_string()).await;
         }
         error => {
             if let Err(e) = poise::builtins::on_error(error).await {
-                println!("Error while handling error: {}", e)
+                tracing::error!("Error while handling error: {}", e)
+            }
+        }
+    }
+}
+
+/// The name of the error's variant, to count errors by.
+fn kind(error: &poise::FrameworkError<'_, Data, Error>) -> &'static str {
+    use poise::FrameworkError::*;
+    match error {
+        Setup { .. } => "Setup",
+        EventHandler { .. } => "EventHandler",
+        Command { .. } => "Command",
+        SubcommandRequired { .. } => "SubcommandRequired",
+        CommandPanic { .. } => "CommandPanic",
+        ArgumentParse { .. } => "ArgumentParse",
+        CommandStructureMismatch { .. } => "CommandStructureMismatch",
+        CooldownHit { .. } => "CooldownHit",
+        MissingBotPermissions { .. } => "MissingBotPermissions",
+        MissingUserPermissions { .. } => "MissingUserPermissions",
+        NotAnOwner { .. } => "NotAnOwner",
+        GuildOnly { .. } => "GuildOnly",
+        DmOnly { .. } => "DmOnly",
+        NsfwOnly { .. } => "NsfwOnly",
+        CommandCheckFailed { .. } => "CommandCheckFailed",
+        DynamicPrefix { .. } => "DynamicPrefix",
+        UnknownCommand { .. } => "UnknownCommand",
+        UnknownInteraction { .. } => "UnknownInteraction",
+        _ => "Other",
+    }
+}
+
+/// A reply only the author sees: ephemeral for slash commands, a DM for prefix
+/// commands. Falls back to replying in the channel when the author doesn't
+/// take DMs.
+async fn private_reply(ctx: Context<'_>, content: String) {
+    if let poise::Context::Prefix(_) = ctx {
+        let dm = ctx
+            .author()
+            .direct_message(ctx, |m| m.content(&content))
+            .await;
+        if dm.is_ok() {
+            return;
+        }
+    }
+    let _ = ctx
+        .send(|m| m.content(content).ephemeral(true).reply(true))
+        .await;
+}
+
+/// A member or nickname couldn't be added because the name or id is in use.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NameError {
+    MemberExists { id: i64, primary_name: String },
+    NameTaken { name: String, owner: String },
+    NicknameTaken { nickname: String, owner: String },
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::MemberExists { id, primary_name } => {
+                write!(f, "Error: {} is already a member as *{}*", id, primary_name)
+            }
+            NameError::NameTaken { name, owner } => {
+                write!(f, "Error: *{}* is already {}'s name", name, owner)
+            }
+            NameError::NicknameTaken { nickname, owner } => {
+                write!(f, "Error: *{}* is already {}'s nickname", nickname, owner)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+const INTERNAL_MESSAGE: &str = "Error: something went wrong on my end";
+
+/// Why a command failed. The first few are the user's to fix and their
+/// message is shown as is. The rest only tell the user something went wrong,
+/// their details go to the logs.
+#[derive(Debug)]
+pub enum ToddError {
+    /// Nothing matched what was asked for.
+    NotFound(String),
+    /// More than one thing matched what was asked for.
+    Ambiguous(String),
+    /// The input couldn't be understood.
+    InvalidInput(String),
+    /// The author isn't allowed to run a command, or to run it on that target.
+    PermissionDenied(String),
+    Name(NameError),
+    /// The bot is on its way down and isn't taking commands.
+    ShuttingDown,
+    DatabaseUnavailable(diesel::ConnectionError),
+    Database(diesel::result::Error),
+    Discord(Box<serenity::Error>),
+    /// Something that shouldn't happen, described for the logs.
+    Internal(String),
+}
+
+impl ToddError {
+    /// Whether it's our fault rather than the user's.
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ToddError::DatabaseUnavailable(_)
+                | ToddError::Database(_)
+                | ToddError::Discord(_)
+                | ToddError::Internal(_)
+        )
+    }
+
+    /// What to tell the user, without anything they shouldn't see.
+    pub fn user_message(&self) -> String {
+        match self {
+            ToddError::NotFound(message)
+            | ToddError::Ambiguous(message)
+            | ToddError::InvalidInput(message)
+            | ToddError::PermissionDenied(message) => message.clone(),
+            ToddError::Name(e) => e.to_string(),
+            ToddError::ShuttingDown => "Error: I'm restarting, try again in a minute".to_string(),
+            ToddError::DatabaseUnavailable(_) => {
+                "Error: can't reach the database right now, try again in a bit".to_string()
+            }
+            ToddError::Discord(_) => "Error: Discord didn't accept that, try again".to_string(),
+            ToddError::Database(_) | ToddError::Internal(_) => INTERNAL_MESSAGE.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ToddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToddError::DatabaseUnavailable(e) => {
+                write!(f, "Could not connect to the database: {}", e)
             }
+            ToddError::Database(e) => write!(f, "Database error: {}", e),
+            ToddError::Discord(e) => write!(f, "Discord error: {}", e),
+            ToddError::Internal(message) => write!(f, "{}", message),
+            _ => write!(f, "{}", self.user_message()),
         }
     }
 }
 
-async fn error_reply(ctx: Context<'_>, error: Error) {
-    let reply = format!(
-        "Error in command `{}`: {:?}\n**{}**",
-        ctx.command().name,
-        error,
-        error
-    );
+impl std::error::Error for ToddError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ToddError::Name(e) => Some(e),
+            ToddError::DatabaseUnavailable(e) => Some(e),
+            ToddError::Database(e) => Some(e),
+            ToddError::Discord(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
-    let _ = ctx.reply(reply).await;
+impl From<NameError> for ToddError {
+    fn from(e: NameError) -> Self {
+        ToddError::Name(e)
+    }
+}
+
+impl From<diesel::ConnectionError> for ToddError {
+    fn from(e: diesel::ConnectionError) -> Self {
+        ToddError::DatabaseUnavailable(e)
+    }
 }
 
-pub async fn message_too_large_handler(ctx: Context<'_>, input: &str) -> Result<(), Error> {
-    let (first_half, second_half) = split_in_half(input);
+impl From<diesel::result::Error> for ToddError {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::NotFound => {
+                ToddError::NotFound("Error: couldn't find that".to_string())
+            }
+            e => ToddError::Database(e),
+        }
+    }
+}
 
-    ctx.reply(first_half).await?;
-    ctx.say(second_half).await?;
+impl From<serenity::Error> for ToddError {
+    fn from(e: serenity::Error) -> Self {
+        ToddError::Discord(Box::new(e))
+    }
+}
 
-    Ok(())
+impl From<serde_json::Error> for ToddError {
+    fn from(e: serde_json::Error) -> Self {
+        ToddError::Internal(format!("JSON error: {}", e))
+    }
 }
-fn split_in_half(input: &str) -> (&str, &str) {
-    let mid = input.len() / 2;
-    let (left, right) = input.split_at(mid);
-    (left, right)
+
+#[cfg(test)]
+mod errors_tests {
+    use super::*;
+
+    #[test]
+    fn test_user_messages_hide_internals() {
+        let bad_input = ToddError::InvalidInput("Error: *x* is not an id".to_string());
+        assert!(!bad_input.is_internal());
+        assert_eq!(bad_input.user_message(), "Error: *x* is not an id");
+        assert!(!ToddError::ShuttingDown.is_internal());
+
+        let internal = ToddError::Internal("Event 3 has no timeframe".to_string());
+        assert!(internal.is_internal());
+        assert_eq!(internal.user_message(), INTERNAL_MESSAGE);
+        assert_eq!(internal.to_string(), "Event 3 has no timeframe");
+
+        let unavailable = ToddError::from(diesel::ConnectionError::BadConnection(
+            "postgres://todd:hunter2@db refused".to_string(),
+        ));
+        assert!(unavailable.is_internal());
+        assert!(!unavailable.user_message().contains("hunter2"));
+        assert!(unavailable.to_string().contains("refused"));
+    }
+
+    #[test]
+    fn test_missing_rows_are_not_found() {
+        let missing = ToddError::from(diesel::result::Error::NotFound);
+        assert!(matches!(missing, ToddError::NotFound(_)));
+        let broken = ToddError::from(diesel::result::Error::BrokenTransactionManager);
+        assert!(broken.is_internal());
+    }
 }
@@ -0,0 +1,55 @@
+// guilds.rs
+
+use crate::config;
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+use serenity::{ChannelId, GuildId};
+
+/// The guild that owned everything before the bot knew about guilds. The
+/// command line tools work on its data, and it's the guild the default
+/// channel belongs to.
+pub fn home_guild_id() -> Result<i64, Error> {
+    config::get().home_guild_id.ok_or_else(|| {
+        Error::from(ToddError::Internal(
+            "home_guild_id (HOME_GUILD_ID) is not set".to_string(),
+        ))
+    })
+}
+
+/// The guild a command was run in. Everything the bot stores belongs to a
+/// guild, so commands don't work in DMs.
+pub fn guild_id(ctx: Context<'_>) -> Result<i64, Error> {
+    ctx.guild_id().map(i64::from).ok_or_else(|| {
+        Error::from(ToddError::InvalidInput(
+            "Error: this command only works in a server".to_string(),
+        ))
+    })
+}
+
+/// Where event announcements for `guild` go: the channel set with `!config`,
+/// or failing that the default channel for the home guild and the system
+/// channel for the rest.
+pub async fn announcement_channel(ctx: &serenity::Context, guild: i64) -> Result<ChannelId, Error> {
+    let mut conn = databaser::establish_connection()?;
+    if let Some(c) = databaser::get_guild_settings(&mut conn, guild)?.announcement_channel_id {
+        return Ok(ChannelId(c as u64));
+    }
+    if let (Some(c), true) = (
+        config::get().channels.default,
+        home_guild_id().ok() == Some(guild),
+    ) {
+        return Ok(ChannelId(c));
+    }
+    GuildId(guild as u64)
+        .to_partial_guild(&ctx.http)
+        .await?
+        .system_channel_id
+        .ok_or_else(|| {
+            Error::from(ToddError::Internal(format!(
+                "Guild {} has no channel for announcements",
+                guild
+            )))
+        })
+}
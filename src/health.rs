@@ -0,0 +1,264 @@
+// health.rs
+
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::permissions;
+use crate::{Context, Error};
+use chrono::prelude::*;
+use poise::serenity_prelude as serenity;
+use serde::Serialize;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// The scheduler ticks every minute, so this long without one means it's stuck.
+const TICK_DEADLINE: Duration = Duration::from_secs(300);
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static SHARD_MANAGER: OnceLock<Arc<tokio::sync::Mutex<serenity::ShardManager>>> = OnceLock::new();
+static LAST_TICK: Mutex<Option<(Instant, DateTime<Local>)>> = Mutex::new(None);
+
+/// Starts the uptime clock. Called once at startup.
+pub fn start_clock() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Lets the health checks see gateway latencies once the bot has connected.
+pub fn watch_shards(shard_manager: Arc<tokio::sync::Mutex<serenity::ShardManager>>) {
+    let _ = SHARD_MANAGER.set(shard_manager);
+}
+
+/// Called by `calendar::check_events_loop` at the start of every tick.
+pub fn scheduler_ticked() {
+    *LAST_TICK.lock().unwrap() = Some((Instant::now(), Local::now()));
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub version: &'static str,
+    pub uptime_seconds: u64,
+    pub database: DatabaseStatus,
+    pub scheduler_last_tick: Option<DateTime<Local>>,
+    pub scheduler_stalled: bool,
+    pub pending_reminders: Option<i64>,
+    pub shards: Vec<ShardStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseStatus {
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShardStatus {
+    pub id: u64,
+    pub latency_ms: Option<u64>,
+}
+
+impl Status {
+    /// Alive as long as the scheduler hasn't stalled. Restarting won't fix
+    /// the database, so that's left to `is_ready`.
+    pub fn is_alive(&self) -> bool {
+        !self.scheduler_stalled
+    }
+
+    /// Ready once the scheduler is running and the database answers.
+    pub fn is_ready(&self) -> bool {
+        self.is_alive() && self.scheduler_last_tick.is_some() && self.database.reachable
+    }
+
+    pub fn uptime(&self) -> Duration {
+        Duration::from_secs(self.uptime_seconds)
+    }
+}
+
+/// Checks everything `!status` and the health endpoints report on.
+pub async fn check() -> Status {
+    let uptime = STARTED.get().map(|s| s.elapsed()).unwrap_or_default();
+    let last_tick = *LAST_TICK.lock().unwrap();
+    // Connecting blocks, and hangs for a while when Postgres is down
+    let probe = tokio::task::spawn_blocking(probe_database)
+        .await
+        .unwrap_or_else(|e| Err(ToddError::Internal(e.to_string())));
+    let (database, pending_reminders) = match probe {
+        Ok((latency, pending)) => (
+            DatabaseStatus {
+                reachable: true,
+                latency_ms: Some(latency.as_millis() as u64),
+                error: None,
+            },
+            Some(pending),
+        ),
+        Err(err) => (
+            DatabaseStatus {
+                reachable: false,
+                latency_ms: None,
+                error: Some(err.user_message()),
+            },
+            None,
+        ),
+    };
+    Status {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_seconds: uptime.as_secs(),
+        database,
+        scheduler_last_tick: last_tick.map(|(_, at)| at),
+        scheduler_stalled: last_tick.is_some_and(|(at, _)| at.elapsed() > TICK_DEADLINE),
+        pending_reminders,
+        shards: shard_latencies().await,
+    }
+}
+
+fn probe_database() -> Result<(Duration, i64), ToddError> {
+    let started = Instant::now();
+    let mut conn = databaser::establish_connection()?;
+    let pending = databaser::count_pending_reminders(&mut conn, Local::now().naive_local())?;
+    Ok((started.elapsed(), pending))
+}
+
+pub async fn shard_latencies() -> Vec<ShardStatus> {
+    let Some(shard_manager) = SHARD_MANAGER.get() else {
+        return vec![];
+    };
+    let runners = shard_manager.lock().await.runners.clone();
+    let mut shards: Vec<ShardStatus> = runners
+        .lock()
+        .await
+        .iter()
+        .map(|(id, runner)| ShardStatus {
+            id: id.0,
+            latency_ms: runner.latency.map(|l| l.as_millis() as u64),
+        })
+        .collect();
+    shards.sort_by_key(|s| s.id);
+    shards
+}
+
+/// Shows how the bot is doing: uptime, the database, the scheduler and
+/// gateway latency.
+#[poise::command(prefix_command, category = "Admin", check = "permissions::admin")]
+pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    let status = check().await;
+    ctx.reply(describe(&status)).await?;
+    Ok(())
+}
+
+fn describe(status: &Status) -> String {
+    let mut lines = vec![
+        format!("**Todd v{}**", status.version),
+        format!("Uptime: {}", format_duration(status.uptime())),
+    ];
+    lines.push(
+        match (&status.database.latency_ms, &status.database.error) {
+            (Some(ms), _) => format!("Database: reachable ({} ms)", ms),
+            (None, Some(err)) => format!("Database: **unreachable** ({})", err),
+            (None, None) => "Database: **unreachable**".to_string(),
+        },
+    );
+    lines.push(match status.scheduler_last_tick {
+        Some(at) => {
+            let ago = (Local::now() - at).to_std().unwrap_or_default();
+            let stalled = if status.scheduler_stalled {
+                " **stalled**"
+            } else {
+                ""
+            };
+            format!(
+                "Scheduler: last tick at {} ({} ago){}",
+                at.format("%I:%M:%S %P"),
+                format_duration(ago),
+                stalled
+            )
+        }
+        None => "Scheduler: hasn't ticked yet".to_string(),
+    });
+    lines.push(match status.pending_reminders {
+        Some(n) => format!("Pending reminders: {}", n),
+        None => "Pending reminders: unknown".to_string(),
+    });
+    for shard in &status.shards {
+        lines.push(match shard.latency_ms {
+            Some(ms) => format!("Shard {} latency: {} ms", shard.id, ms),
+            None => format!("Shard {} latency: not measured yet", shard.id),
+        });
+    }
+    lines.join("\n")
+}
+
+/// Like `3d 4h 12m`, or `42s` for anything under a minute.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        return format!("{}s", secs);
+    }
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    let mut parts = vec![];
+    if days > 0 {
+        parts.push(format!("{}d", days));
+    }
+    if days > 0 || hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    parts.push(format!("{}m", minutes));
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+
+    fn status(last_tick: Option<DateTime<Local>>, stalled: bool, reachable: bool) -> Status {
+        Status {
+            version: "1.0.0",
+            uptime_seconds: 90061,
+            database: DatabaseStatus {
+                reachable,
+                latency_ms: reachable.then_some(3),
+                error: (!reachable).then(|| "Error: could not connect to the database".to_string()),
+            },
+            scheduler_last_tick: last_tick,
+            scheduler_stalled: stalled,
+            pending_reminders: reachable.then_some(2),
+            shards: vec![ShardStatus {
+                id: 0,
+                latency_ms: Some(40),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 2m");
+        assert_eq!(format_duration(Duration::from_secs(90061)), "1d 1h 1m");
+    }
+
+    #[test]
+    fn test_readiness() {
+        let now = Some(Local::now());
+        assert!(status(now, false, true).is_ready());
+        // Still logging in
+        let starting = status(None, false, true);
+        assert!(starting.is_alive() && !starting.is_ready());
+        // Postgres went away
+        let no_db = status(now, false, false);
+        assert!(no_db.is_alive() && !no_db.is_ready());
+        let stalled = status(now, true, true);
+        assert!(!stalled.is_alive() && !stalled.is_ready());
+    }
+
+    #[test]
+    fn test_describe() {
+        let text = describe(&status(None, false, false));
+        assert!(text.contains("**Todd v1.0.0**"));
+        assert!(text.contains("Uptime: 1d 1h 1m"));
+        assert!(
+            text.contains("Database: **unreachable** (Error: could not connect to the database)")
+        );
+        assert!(text.contains("Scheduler: hasn't ticked yet"));
+        assert!(text.contains("Pending reminders: unknown"));
+        assert!(text.contains("Shard 0 latency: 40 ms"));
+    }
+}
@@ -0,0 +1,125 @@
+// http.rs
+
+use crate::health;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::Error;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+/// Serves the health checks, and `/metrics` with the `metrics` feature, on
+/// `addr` in the background. Returns the address it bound, which is where to
+/// find it when `addr` asks for any port.
+///
+/// `/health/live` fails once the scheduler has stalled, so the bot should be
+/// restarted. `/health/ready` also fails until the bot has connected and
+/// while the database can't be reached. Both answer with the same JSON as
+/// `!status`.
+pub fn serve(addr: SocketAddr) -> Result<SocketAddr, Error> {
+    let server = Server::try_bind(&addr)?.serve(make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(handle))
+    }));
+    let bound = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!("HTTP server stopped: {}", err);
+        }
+    });
+    Ok(bound)
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/health/live") => health_response(|s| s.is_alive()).await,
+        (&Method::GET, "/health/ready") => health_response(|s| s.is_ready()).await,
+        #[cfg(feature = "metrics")]
+        (&Method::GET, "/metrics") => match metrics::encode() {
+            Ok(body) => {
+                let mut response = Response::new(Body::from(body));
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static(prometheus::TEXT_FORMAT),
+                );
+                response
+            }
+            Err(err) => with_status(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+        },
+        _ => with_status(StatusCode::NOT_FOUND, "Not found".to_string()),
+    };
+    Ok(response)
+}
+
+async fn health_response(healthy: impl Fn(&health::Status) -> bool) -> Response<Body> {
+    let status = health::check().await;
+    let code = if healthy(&status) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let mut response = with_status(code, serde_json::to_string(&status).unwrap_or_default());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn with_status(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod http_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_checks() -> Result<(), Error> {
+        let addr = serve("127.0.0.1:0".parse()?)?;
+
+        let live = reqwest::get(format!("http://{}/health/live", addr)).await?;
+        assert_eq!(live.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&live.text().await?)?;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["database"]["reachable"], true);
+        assert!(!body.to_string().contains("postgres://"));
+
+        // The scheduler only runs once the bot is connected, which it never
+        // is in tests
+        let ready = reqwest::get(format!("http://{}/health/ready", addr)).await?;
+        assert_eq!(ready.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_metrics_can_be_scraped() -> Result<(), Error> {
+        use std::time::Duration;
+        metrics::command_finished("scrape_test", Duration::from_millis(250));
+        metrics::framework_error("ScrapeTest");
+        {
+            let _outer = metrics::query_timer("scrape_outer");
+            let _inner = metrics::query_timer("scrape_inner");
+        }
+        let addr = serve("127.0.0.1:0".parse()?)?;
+
+        let response = reqwest::get(format!("http://{}/metrics", addr)).await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let content_type = response.headers()[reqwest::header::CONTENT_TYPE].clone();
+        assert_eq!(content_type, prometheus::TEXT_FORMAT);
+        let body = response.text().await?;
+        assert!(body.contains("todd_commands_total{command=\"scrape_test\"} 1"));
+        assert!(body.contains("todd_command_duration_seconds_count{command=\"scrape_test\"} 1"));
+        assert!(body.contains("todd_framework_errors_total{kind=\"ScrapeTest\"} 1"));
+        // A call made inside another is timed as part of it
+        assert!(body.contains("todd_db_query_duration_seconds_count{query=\"scrape_outer\"} 1"));
+        assert!(!body.contains("scrape_inner"));
+
+        let missing = reqwest::get(format!("http://{}/nope", addr)).await?;
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+        Ok(())
+    }
+}
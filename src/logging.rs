@@ -0,0 +1,169 @@
+// logging.rs
+
+use crate::config::{Config, LogFormat, LogRotation};
+use crate::metrics;
+use crate::{Context, Error};
+use std::time::Instant;
+use tracing::{error, field, info, info_span, warn, Span};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, Layer, Registry};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Sets up logging to stdout, and to rotating files when `logging.dir` is
+/// set. The returned guard flushes the file writer when dropped, so keep it
+/// alive until the bot exits.
+pub fn init(config: &Config) -> Option<WorkerGuard> {
+    let format = config.logging.format;
+    let mut layers = vec![format_layer(format, std::io::stdout, true)];
+
+    let guard = config.logging.dir.as_ref().map(|dir| {
+        let rotation = match config.logging.rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        };
+        let appender = RollingFileAppender::new(rotation, dir, "todd.log");
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        layers.push(format_layer(format, writer, false));
+        guard
+    });
+
+    tracing_subscriber::registry()
+        .with(layers.with_filter(config.log_filter()))
+        .init();
+    guard
+}
+
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => layer.boxed(),
+        LogFormat::Json => layer.json().with_current_span(true).boxed(),
+    }
+}
+
+/// The span of a command invocation, kept in the invocation data between
+/// `pre_command` and `post_command` or `on_error`, which log inside it.
+struct Invocation {
+    span: Span,
+    started: Instant,
+}
+
+fn command_span(command: &str, author: &str, author_id: u64, guild: Option<u64>) -> Span {
+    info_span!(
+        "command",
+        command,
+        author,
+        author_id,
+        guild,
+        latency_ms = field::Empty,
+    )
+}
+
+/// Opens the span for a command invocation.
+pub async fn command_started(ctx: Context<'_>) {
+    let span = command_span(
+        &ctx.command().qualified_name,
+        &ctx.author().name,
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+    );
+    log_started(&span);
+    ctx.set_invocation_data(Invocation {
+        span,
+        started: Instant::now(),
+    })
+    .await;
+}
+
+pub async fn command_finished(ctx: Context<'_>) {
+    if let Some(span) = close_span(ctx).await {
+        span.in_scope(|| info!("command finished"));
+    }
+}
+
+/// Logs why the command failed. Errors given a `reference` weren't the
+/// user's fault, so they're logged in full for it to be looked up by.
+pub async fn command_failed(ctx: Context<'_>, error: &Error, reference: Option<&str>) {
+    let span = close_span(ctx).await.unwrap_or_else(Span::none);
+    log_failed(&span, error, reference);
+}
+
+fn log_started(span: &Span) {
+    span.in_scope(|| info!("command started"));
+}
+
+fn log_failed(span: &Span, error: &Error, reference: Option<&str>) {
+    span.in_scope(|| match reference {
+        Some(reference) => error!(reference, error = ?error, "command failed: {}", error),
+        None => warn!(%error, "command failed"),
+    });
+}
+
+/// Records how long the command took. Checks run before `pre_command`, so
+/// commands stopped by a check have no span to close.
+async fn close_span(ctx: Context<'_>) -> Option<Span> {
+    let invocation = ctx.invocation_data::<Invocation>().await?;
+    let latency = invocation.started.elapsed();
+    metrics::command_finished(&ctx.command().qualified_name, latency);
+    invocation
+        .span
+        .record("latency_ms", latency.as_millis() as u64);
+    Some(invocation.span.clone())
+}
+
+#[cfg(test)]
+mod logging_tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_command_logs_carry_its_span() {
+        let logs = Arc::new(Mutex::new(vec![]));
+        let writer = {
+            let logs = logs.clone();
+            move || Captured(logs.clone())
+        };
+        let subscriber =
+            tracing_subscriber::registry().with(format_layer(LogFormat::Json, writer, false));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let span = command_span("quote show", "someone", 42, Some(7));
+        log_started(&span);
+        let error = Error::from(crate::errors::ToddError::Internal("broken".to_string()));
+        log_failed(&span, &error, Some("0000beef"));
+        info!("outside the command");
+
+        let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+        let line = |message: &str| logs.lines().find(|l| l.contains(message)).unwrap();
+        for message in ["command started", "command failed"] {
+            let logged = line(message);
+            assert!(logged.contains(r#""command":"quote show""#));
+            assert!(logged.contains(r#""author":"someone""#));
+            assert!(logged.contains(r#""author_id":42"#));
+            assert!(logged.contains(r#""guild":7"#));
+        }
+        assert!(line("command failed").contains(r#""reference":"0000beef""#));
+        assert!(!line("outside the command").contains("quote show"));
+    }
+}
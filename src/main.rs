@@ -1,22 +1,43 @@
 // main.rs
 
-use dotenv::dotenv;
 use poise::serenity_prelude as serenity;
 use serenity::prelude::TypeMapKey;
-use std::{env::var, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 use tokio::sync::Mutex;
+mod audit;
 mod calendar;
+mod cli;
+mod config;
 mod databaser;
 mod errors;
-mod helper;
+mod guilds;
+mod health;
+mod http;
+mod logging;
+mod markov;
+mod member_commands;
+mod metrics;
 mod models;
+mod pages;
+mod permissions;
+mod quote_card;
+mod quote_commands;
+mod quote_of_the_day;
+mod roster;
 mod schema;
+mod seeder;
+mod settings;
 mod shitposts;
+mod shutdown;
 mod todd_commands;
+mod undo;
 use crate::models::*;
 
 pub struct Data {
+    pub config: &'static config::Config,
     pub reminders: Arc<Mutex<Vec<Reminder>>>,
+    pub markov_models: Mutex<markov::MarkovCache>,
+    pub guild_settings: Mutex<settings::SettingsCache>,
 } // User data, which is stored and accessible
   // in all command invocations
   // Types used by all command functions
@@ -29,9 +50,25 @@ impl TypeMapKey for RemindersKey {
 
 #[tokio::main]
 async fn main() {
-    // env_logger::init();
-
-    dotenv().ok();
+    let args = std::env::args().collect::<Vec<_>>();
+    // Anything on the command line is a maintenance command, which doesn't
+    // need to connect to Discord
+    let config = match config::init(args.len() < 2) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(result) = cli::run(&args) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let log_guard = logging::init(config);
+    health::start_clock();
     // FrameworkOptions contains all of poise's configuration option in one struct
     // Every option can be omitted to use its default value
     let options = poise::FrameworkOptions {
@@ -39,16 +76,21 @@ async fn main() {
             todd_commands::add(),
             todd_commands::todd(),
             todd_commands::old_quotes(),
+            quote_commands::quote(),
+            member_commands::member(),
+            member_commands::nickname(),
+            quote_of_the_day::qotd(),
             shitposts::nerd(),
             calendar::calendar(),
+            settings::config(),
+            audit::audit(),
+            undo::undo(),
+            health::status(),
         ],
         prefix_options: poise::PrefixFrameworkOptions {
-            prefix: Some("!".into()),
+            // Each guild picks its own prefixes, see `!config set prefixes`
+            stripped_dynamic_prefix: Some(settings::strip_prefix),
             edit_tracker: Some(poise::EditTracker::for_timespan(Duration::from_secs(3600))),
-            additional_prefixes: vec![
-                poise::Prefix::Literal("Todd!"),
-                poise::Prefix::Literal("todd!"),
-            ],
             ..Default::default()
         },
         // The global error handler for all error cases that may occur
@@ -56,67 +98,87 @@ async fn main() {
         // This code is run before every command
         pre_command: |ctx| {
             Box::pin(async move {
-                println!(
-                    "{} is executing command {}...",
-                    ctx.author().name,
-                    ctx.command().qualified_name
-                );
+                logging::command_started(ctx).await;
             })
         },
         // This code is run after a command if it was successful (returned Ok)
         post_command: |ctx| {
             Box::pin(async move {
-                println!("Executed command {}!", ctx.command().qualified_name);
+                logging::command_finished(ctx).await;
             })
         },
         // Every command invocation must pass this check to continue execution
         command_check: Some(|ctx| {
             Box::pin(async move {
-                let bot_id = var("BOT_ID")
-                    .expect("Missing BOT_ID")
-                    .parse::<u64>()
-                    .expect("Invalid BOT_ID");
-                if ctx.author().id == bot_id {
+                if Some(ctx.author().id.0) == ctx.data().config.bot_id {
                     return Ok(false);
                 }
-                Ok(true)
+                if shutdown::is_shutting_down() {
+                    return Err(errors::ToddError::ShuttingDown.into());
+                }
+                settings::check_feature(ctx).await
             })
         }),
 
         // Enforce command checks even for owners (enforced by default)
         // Set to true to bypass checks, which is useful for testing
         skip_checks_for_owners: false,
-        // event_handler: |_ctx, event, _framework, _data| {
-        //     Box::pin(async move {
-        //         println!("Got an event in event handler: {:?}", event.name());
-        //         Ok(())
-        //     })
-        // },
+        event_handler: |ctx, event, _framework, _data| {
+            Box::pin(async move {
+                quote_commands::handle_vote_event(ctx, event).await?;
+                roster::handle_roster_event(ctx, event).await?;
+                Ok(())
+            })
+        },
         ..Default::default()
     };
 
-    let token = "DISCORD_TOKEN";
-    poise::Framework::builder()
-        .token(var(token).expect("missing DISCORD_TOKEN"))
+    let mut intents =
+        serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT;
+    if roster::sync_enabled() {
+        intents |= serenity::GatewayIntents::GUILD_MEMBERS;
+    }
+
+    let framework = poise::Framework::builder()
+        .token(config.discord_token.clone().unwrap_or_default())
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
-                println!("Logged in as {}", _ready.user.name);
+                tracing::info!("Logged in as {}", _ready.user.name);
                 let reminders = Arc::new(Mutex::new(Vec::new()));
                 let data = Data {
+                    config,
                     reminders: reminders.clone(),
+                    markov_models: Mutex::new(markov::MarkovCache::new()),
+                    guild_settings: Mutex::new(settings::SettingsCache::new()),
                 };
                 ctx.data.write().await.insert::<RemindersKey>(reminders);
+                // Registered once for every guild rather than per guild: each
+                // command works out its guild when it runs and only touches
+                // that guild's data, and guilds the bot joins later get them
+                // without registering again
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 tokio::spawn(calendar::check_events_loop(ctx.clone()));
                 tokio::spawn(calendar::fetch_events_loop(ctx.clone()));
+                tokio::spawn(calendar::purge_deleted_loop());
+                health::watch_shards(framework.shard_manager().clone());
+                if let Some(addr) = config.http.listen {
+                    match http::serve(addr) {
+                        Ok(bound) => tracing::info!("Serving health checks on http://{}", bound),
+                        Err(err) => tracing::error!("Could not serve on {}: {}", addr, err),
+                    }
+                    metrics::start();
+                }
                 Ok(data)
             })
         })
         .options(options)
-        .intents(
-            serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT,
-        )
-        .run()
+        .intents(intents)
+        .build()
         .await
         .unwrap();
+    shutdown::listen(framework.shard_manager().clone());
+    framework.start().await.unwrap();
+    tracing::info!("Shut down");
+    // Flushes whatever is still waiting to be written to the log files
+    drop(log_guard);
 }
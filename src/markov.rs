@@ -0,0 +1,182 @@
+// markov.rs
+
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::member_commands;
+use crate::{Context, Error};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const DEFAULT_ORDER: usize = 2;
+const MAX_ORDER: usize = 4;
+const MAX_WORDS: usize = 60;
+const MAX_ATTEMPTS: usize = 50;
+
+/// Word-level Markov chain. Each state is the last `order` words, and maps to
+/// every word that followed it in the corpus, with `None` marking the end of a
+/// quote. Repeats are kept so more common continuations are picked more often.
+#[derive(Debug)]
+pub struct MarkovChain {
+    order: usize,
+    starts: Vec<Vec<String>>,
+    transitions: HashMap<Vec<String>, Vec<Option<String>>>,
+    corpus: Vec<String>,
+}
+
+/// Chains already built, keyed by guild, member primary name and order,
+/// along with the generation of the member's quotes they were built from.
+pub type MarkovCache = HashMap<(i64, String, usize), (i64, Arc<MarkovChain>)>;
+
+impl MarkovChain {
+    pub fn new(order: usize, quotes: &[String]) -> Self {
+        let mut starts = vec![];
+        let mut transitions: HashMap<Vec<String>, Vec<Option<String>>> = HashMap::new();
+        for q in quotes {
+            let words: Vec<String> = q.split_whitespace().map(String::from).collect();
+            if words.len() < order {
+                continue;
+            }
+            starts.push(words[..order].to_vec());
+            for i in 0..=words.len() - order {
+                transitions
+                    .entry(words[i..i + order].to_vec())
+                    .or_default()
+                    .push(words.get(i + order).cloned());
+            }
+        }
+        MarkovChain {
+            order,
+            starts,
+            transitions,
+            corpus: quotes.iter().map(|q| normalize(q)).collect(),
+        }
+    }
+
+    /// Generates a new quote, or `None` if every attempt reproduced an
+    /// existing quote word for word.
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        for _ in 0..MAX_ATTEMPTS {
+            let mut words = self.starts.choose(rng)?.clone();
+            while words.len() < MAX_WORDS {
+                let state = &words[words.len() - self.order..];
+                match self.transitions.get(state).and_then(|n| n.choose(rng)) {
+                    Some(Some(next)) => words.push(next.clone()),
+                    _ => break,
+                }
+            }
+            let output = words.join(" ");
+            if !self.corpus.contains(&normalize(&output)) {
+                return Some(output);
+            }
+        }
+        None
+    }
+}
+
+fn normalize(quote: &str) -> String {
+    quote
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[poise::command(prefix_command, global_cooldown = 30, broadcast_typing)]
+pub async fn imitate(ctx: Context<'_>, input: String, order: Option<usize>) -> Result<(), Error> {
+    let order = order.unwrap_or(DEFAULT_ORDER);
+    if order == 0 || order > MAX_ORDER {
+        return Err(Error::from(ToddError::InvalidInput(format!(
+            "Error: order must be between 1 and {}",
+            MAX_ORDER
+        ))));
+    }
+    let mut conn = databaser::establish_connection()?;
+    let schlonghouse_member = member_commands::resolve_member(ctx, &mut conn, &input).await?;
+
+    let key = (
+        schlonghouse_member.guild_id,
+        schlonghouse_member.primary_name.clone(),
+        order,
+    );
+    // Read before the quotes, so a change made in between only means the
+    // chain gets built again next time
+    let generation = databaser::get_quote_generation(&mut conn, &schlonghouse_member)?;
+    let cached = ctx.data().markov_models.lock().await.get(&key).cloned();
+    let chain = match cached {
+        Some((built_from, c)) if built_from == generation => c,
+        _ => {
+            let quotes: Vec<String> =
+                databaser::get_all_members_quotes(&mut conn, &schlonghouse_member)?
+                    .into_iter()
+                    .map(|q| q.quote)
+                    .collect();
+            let chain = Arc::new(MarkovChain::new(order, &quotes));
+            ctx.data()
+                .markov_models
+                .lock()
+                .await
+                .insert(key, (generation, chain.clone()));
+            chain
+        }
+    };
+
+    let generated = chain.generate(&mut rand::thread_rng()).ok_or_else(|| {
+        Error::from(ToddError::NotFound(format!(
+            "Error: {} doesn't have enough quotes to imitate",
+            schlonghouse_member.primary_name
+        )))
+    })?;
+    ctx.reply(format!(
+        "\"{}\"\n- {} (probably)",
+        generated, schlonghouse_member.primary_name
+    ))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod markov_tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn sample_quotes() -> Vec<String> {
+        vec![
+            "I like my coffee black and my mornings late".to_string(),
+            "I like my dogs big and my cats small".to_string(),
+            "my mornings are for coffee and nothing else".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_generate_never_verbatim() {
+        let quotes = sample_quotes();
+        let chain = MarkovChain::new(1, &quotes);
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            if let Some(generated) = chain.generate(&mut rng) {
+                assert!(
+                    !quotes.iter().any(|q| normalize(q) == normalize(&generated)),
+                    "Reproduced a quote: {}",
+                    generated
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_single_quote_corpus() {
+        // With one quote every walk reproduces it, so nothing can be generated
+        let quotes = vec!["nothing to see here".to_string()];
+        let chain = MarkovChain::new(2, &quotes);
+        assert_eq!(chain.generate(&mut StdRng::seed_from_u64(7)), None);
+    }
+
+    #[test]
+    fn test_generate_empty_corpus() {
+        let chain = MarkovChain::new(3, &["too short".to_string()]);
+        assert_eq!(chain.generate(&mut StdRng::seed_from_u64(7)), None);
+    }
+}
@@ -0,0 +1,323 @@
+// member_commands.rs
+
+use crate::audit::Actor;
+use crate::calendar;
+use crate::databaser;
+use crate::databaser::MemberMatch;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::models::SchlonghouseMember;
+use crate::pages;
+use crate::permissions;
+use crate::todd_commands::parse_member_or_return_lowercase;
+use crate::{Context, Error};
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use serenity::{ButtonStyle, InteractionResponseType, UserId};
+use std::time::Duration;
+
+const PICK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Resolves a mention, id, name or nickname to a member for a command. A
+/// unique near miss is used with a note saying so, and several near misses
+/// ask the author to pick one with buttons.
+pub async fn resolve_member(
+    ctx: Context<'_>,
+    conn: &mut PgConnection,
+    input: &String,
+) -> Result<SchlonghouseMember, Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let member_id = parse_member_or_return_lowercase(input);
+    if member_id.parse::<i64>().is_ok() {
+        return Ok(databaser::get_member(conn, guild, &member_id)?);
+    }
+    match databaser::find_member(conn, guild, &member_id)? {
+        MemberMatch::Exact(member) => Ok(member),
+        MemberMatch::Close(member, name) => {
+            ctx.say(format!(
+                "No one is called *{}*, going with **{}** ({})",
+                input, member.primary_name, name
+            ))
+            .await?;
+            Ok(member)
+        }
+        MemberMatch::Ambiguous(candidates) => pick_member(ctx, input, candidates).await,
+        MemberMatch::NotFound => Err(Error::from(ToddError::NotFound(format!(
+            "Error: no member called *{}*",
+            input
+        )))),
+    }
+}
+
+async fn pick_member(
+    ctx: Context<'_>,
+    input: &str,
+    mut candidates: Vec<(SchlonghouseMember, String)>,
+) -> Result<SchlonghouseMember, Error> {
+    let reply = ctx
+        .send(|m| {
+            m.content(format!("No one is called *{}*, did you mean:", input))
+                .components(|c| {
+                    c.create_action_row(|r| {
+                        for (member, name) in &candidates {
+                            r.create_button(|b| {
+                                b.custom_id(member.id)
+                                    .label(match name == &member.primary_name {
+                                        true => name.clone(),
+                                        false => format!("{} ({})", member.primary_name, name),
+                                    })
+                                    .style(ButtonStyle::Secondary)
+                            });
+                        }
+                        r
+                    })
+                })
+        })
+        .await?;
+
+    let interaction = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(PICK_TIMEOUT)
+        .await;
+    let picked = interaction.as_ref().and_then(|i| {
+        candidates
+            .iter()
+            .position(|(m, _)| m.id.to_string() == i.data.custom_id)
+    });
+    if let Some(i) = &interaction {
+        i.create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+    }
+    reply
+        .edit(ctx, |m| {
+            m.components(|c| c).content(match picked {
+                Some(p) => format!("Going with **{}**", candidates[p].0.primary_name),
+                None => format!("No one picked who *{}* was meant to be", input),
+            })
+        })
+        .await?;
+
+    match picked {
+        Some(p) => Ok(candidates.swap_remove(p).0),
+        None => Err(Error::from(ToddError::Ambiguous(format!(
+            "Error: *{}* could be several members",
+            input
+        )))),
+    }
+}
+
+#[poise::command(
+    prefix_command,
+    global_cooldown = 10,
+    category = "Based Todd",
+    broadcast_typing,
+    subcommands("info", "remove", "rename"),
+    subcommand_required
+)]
+pub async fn member(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, member_cooldown = 30, broadcast_typing)]
+async fn info(ctx: Context<'_>, input: String) -> Result<(), Error> {
+    let mut conn = databaser::establish_connection()?;
+    let schlonghouse_member = resolve_member(ctx, &mut conn, &input).await?;
+
+    let nicknames = databaser::get_member_nicknames(&mut conn, &schlonghouse_member)?
+        .into_iter()
+        .map(|n| n.nickname)
+        .collect::<Vec<_>>();
+    let quote_count = databaser::count_members_quotes(&mut conn, &schlonghouse_member)?;
+    let birthday = databaser::get_birthday(&mut conn, &schlonghouse_member).ok();
+    let events = databaser::get_events_owned_by(&mut conn, &schlonghouse_member)?
+        .into_iter()
+        .filter(|e| Some(e.id) != birthday.as_ref().map(|b| b.id))
+        .map(|e| format!("{} ({})", e.title, e.timedate.format("%D")))
+        .collect::<Vec<_>>();
+    let avatar = if schlonghouse_member.is_member {
+        UserId(schlonghouse_member.id as u64)
+            .to_user(ctx)
+            .await
+            .ok()
+            .map(|u| u.face())
+    } else {
+        None
+    };
+
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.title(&schlonghouse_member.primary_name)
+                .field(
+                    "Discord id",
+                    match schlonghouse_member.is_member {
+                        true => format!("<@{}>", schlonghouse_member.id),
+                        false => schlonghouse_member.id.to_string(),
+                    },
+                    true,
+                )
+                .field("Quotes", quote_count, true)
+                .field(
+                    "Birthday",
+                    match &birthday {
+                        Some(b) => b.timedate.format("%B %-d").to_string(),
+                        None => "unknown".to_string(),
+                    },
+                    true,
+                )
+                .field("Nicknames", list_or_none(&nicknames), false)
+                .field("Events", list_or_none(&events), false)
+                .field(
+                    "Added",
+                    match schlonghouse_member.created_at {
+                        Some(d) => d.format("%D").to_string(),
+                        None => "before anyone was keeping track".to_string(),
+                    },
+                    false,
+                );
+            if let Some(url) = &avatar {
+                e.thumbnail(url);
+            }
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Removes a member. Their quotes, nicknames and events are deleted too,
+/// unless `reassign_to` names a member to hand them to. Without one,
+/// `!undo` brings it all back.
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn remove(ctx: Context<'_>, input: String, reassign_to: Option<String>) -> Result<(), Error> {
+    let mut conn = databaser::establish_connection()?;
+    let schlonghouse_member = resolve_member(ctx, &mut conn, &input).await?;
+    let target = match reassign_to {
+        Some(t) => {
+            let target = resolve_member(ctx, &mut conn, &t).await?;
+            if target.id == schlonghouse_member.id {
+                return Err(Error::from(ToddError::InvalidInput(
+                    "Error: can't reassign a member to themselves".to_string(),
+                )));
+            }
+            Some(target)
+        }
+        None => None,
+    };
+
+    let removed = databaser::remove_member_and_data(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        &schlonghouse_member,
+        target.as_ref(),
+    )?;
+    // Don't wait for the next fetch to stop watching deleted reminders
+    if removed.reminders > 0 {
+        *ctx.data().reminders.lock().await = calendar::fetch_reminders().await?;
+    }
+
+    let response = match &target {
+        Some(t) => format!(
+            "Removed **{}**. Moved {} quotes, {} nicknames and {} events to **{}**, deleted {} birthdays and {} reminders",
+            schlonghouse_member.primary_name,
+            removed.quotes,
+            removed.nicknames,
+            removed.events_moved,
+            t.primary_name,
+            removed.events_deleted,
+            removed.reminders
+        ),
+        None => format!(
+            "Removed **{}** along with {} quotes, {} nicknames, {} events and {} reminders",
+            schlonghouse_member.primary_name,
+            removed.quotes,
+            removed.nicknames,
+            removed.events_deleted,
+            removed.reminders
+        ),
+    };
+    ctx.reply(response).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn rename(ctx: Context<'_>, input: String, new_primary_name: String) -> Result<(), Error> {
+    let mut conn = databaser::establish_connection()?;
+    let schlonghouse_member = resolve_member(ctx, &mut conn, &input).await?;
+
+    let renamed = databaser::rename_member(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        &schlonghouse_member,
+        &new_primary_name.to_lowercase(),
+    )?;
+    ctx.reply(format!(
+        "Renamed **{}** to **{}**",
+        schlonghouse_member.primary_name, renamed.primary_name
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    global_cooldown = 10,
+    category = "Based Todd",
+    broadcast_typing,
+    subcommands("list", "remove_nickname"),
+    subcommand_required
+)]
+pub async fn nickname(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, member_cooldown = 10)]
+async fn list(ctx: Context<'_>, input: String) -> Result<(), Error> {
+    let mut conn = databaser::establish_connection()?;
+    let schlonghouse_member = resolve_member(ctx, &mut conn, &input).await?;
+    let nicknames = databaser::get_member_nicknames(&mut conn, &schlonghouse_member)?
+        .into_iter()
+        .map(|n| format!("- {}", n.nickname))
+        .collect::<Vec<_>>();
+    let title = format!("{}'s nicknames", schlonghouse_member.primary_name);
+    pages::reply_pages(ctx, &title, &nicknames, "None").await?;
+    Ok(())
+}
+
+/// Anyone can remove their own nicknames, everyone else's need a moderator.
+#[poise::command(prefix_command, rename = "remove")]
+async fn remove_nickname(ctx: Context<'_>, nickname: String) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let found =
+        databaser::get_nickname(&mut conn, guild, &nickname.to_lowercase()).map_err(|_| {
+            ToddError::NotFound(format!("Error: *{}* isn't anyone's nickname", nickname))
+        })?;
+    permissions::require_owner(
+        ctx,
+        &[found.primary_name],
+        &format!("the nickname *{}*", found.nickname),
+    )
+    .await?;
+    databaser::delete_nickname_by_id(&mut conn, &Actor::from_ctx(ctx), guild, found.id)?;
+    ctx.reply(format!("Removed the nickname **{}**", found.nickname))
+        .await?;
+    Ok(())
+}
+
+// Embed field values can't be empty or longer than 1024 characters
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        return "none".to_string();
+    }
+    let joined = items.join(", ");
+    if joined.chars().count() <= 1024 {
+        joined
+    } else {
+        joined.chars().take(1021).collect::<String>() + "..."
+    }
+}
@@ -0,0 +1,251 @@
+// metrics.rs
+
+// Counters and timings for Prometheus, served from `/metrics` on
+// `http.listen`. They're only kept when built with the `metrics` feature;
+// without it everything here does nothing, so callers don't need to care.
+
+pub use imp::*;
+
+#[cfg(feature = "metrics")]
+mod imp {
+    use crate::databaser;
+    use crate::health;
+    use crate::Error;
+    use chrono::prelude::*;
+    use prometheus::core::Collector;
+    use prometheus::{
+        Encoder, GaugeVec, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec,
+        IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+    };
+    use std::cell::Cell;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+    use tokio::time::interval;
+    use tracing::warn;
+
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+    struct Metrics {
+        registry: Registry,
+        commands: IntCounterVec,
+        command_seconds: HistogramVec,
+        framework_errors: IntCounterVec,
+        reminders_fired: IntCounter,
+        reminders_missed: IntCounter,
+        reminders_overdue: IntGauge,
+        query_seconds: HistogramVec,
+        gateway_latency: GaugeVec,
+        quotes: IntGaugeVec,
+        events: IntGaugeVec,
+    }
+
+    impl Metrics {
+        fn new() -> Metrics {
+            let registry =
+                Registry::new_custom(Some("todd".to_string()), None).expect("the prefix is valid");
+            let counter = |name: &str, help: &str, labels: &[&str]| {
+                register(&registry, IntCounterVec::new(Opts::new(name, help), labels))
+            };
+            let gauge = |name: &str, help: &str, labels: &[&str]| {
+                register(&registry, IntGaugeVec::new(Opts::new(name, help), labels))
+            };
+            let histogram = |name: &str, help: &str, label: &str| {
+                register(
+                    &registry,
+                    HistogramVec::new(HistogramOpts::new(name, help), &[label]),
+                )
+            };
+            Metrics {
+                commands: counter("commands_total", "Commands run, by name", &["command"]),
+                command_seconds: histogram(
+                    "command_duration_seconds",
+                    "How long commands took, by name",
+                    "command",
+                ),
+                framework_errors: counter(
+                    "framework_errors_total",
+                    "Errors handled by on_error, by kind",
+                    &["kind"],
+                ),
+                reminders_fired: register(
+                    &registry,
+                    IntCounter::new("reminders_fired_total", "Reminders announced"),
+                ),
+                reminders_missed: register(
+                    &registry,
+                    IntCounter::new(
+                        "reminders_missed_total",
+                        "Reminder announcements that failed, each is tried again next tick up to a limit",
+                    ),
+                ),
+                reminders_overdue: register(
+                    &registry,
+                    IntGauge::new(
+                        "reminders_overdue",
+                        "Reminders whose time has passed without being announced",
+                    ),
+                ),
+                query_seconds: histogram(
+                    "db_query_duration_seconds",
+                    "How long database queries took, by query",
+                    "query",
+                ),
+                gateway_latency: register(
+                    &registry,
+                    GaugeVec::new(
+                        Opts::new("gateway_latency_seconds", "Heartbeat latency, by shard"),
+                        &["shard"],
+                    ),
+                ),
+                quotes: gauge(
+                    "quotes",
+                    "Quotes that haven't been deleted, by guild",
+                    &["guild"],
+                ),
+                events: gauge(
+                    "events",
+                    "Events that haven't been deleted, by guild",
+                    &["guild"],
+                ),
+                registry,
+            }
+        }
+    }
+
+    fn register<M: Collector + Clone + 'static>(
+        registry: &Registry,
+        metric: prometheus::Result<M>,
+    ) -> M {
+        let metric = metric.expect("metric options are valid");
+        registry
+            .register(Box::new(metric.clone()))
+            .expect("metric names are unique");
+        metric
+    }
+
+    fn metrics() -> &'static Metrics {
+        METRICS.get_or_init(Metrics::new)
+    }
+
+    /// Keeps the gauges up to date while `/metrics` is being served.
+    pub fn start() {
+        tokio::spawn(collect_loop());
+    }
+
+    /// Everything collected so far, in Prometheus' text format.
+    pub fn encode() -> Result<String, Error> {
+        let mut output = vec![];
+        TextEncoder::new().encode(&metrics().registry.gather(), &mut output)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    pub fn command_finished(command: &str, latency: Duration) {
+        let m = metrics();
+        m.commands.with_label_values(&[command]).inc();
+        m.command_seconds
+            .with_label_values(&[command])
+            .observe(latency.as_secs_f64());
+    }
+
+    pub fn framework_error(kind: &str) {
+        metrics().framework_errors.with_label_values(&[kind]).inc();
+    }
+
+    pub fn reminder_fired() {
+        metrics().reminders_fired.inc();
+    }
+
+    pub fn reminder_missed() {
+        metrics().reminders_missed.inc();
+    }
+
+    thread_local! {
+        /// Whether a database call on this thread is already being timed.
+        static TIMING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Times a database call under `query` until it's dropped. Calls made
+    /// while another one is being timed are part of that one's time, so
+    /// they aren't counted again.
+    pub fn query_timer(query: &str) -> QueryTimer {
+        let outermost = !TIMING.with(|t| t.replace(true));
+        QueryTimer(outermost.then(|| {
+            metrics()
+                .query_seconds
+                .with_label_values(&[query])
+                .start_timer()
+        }))
+    }
+
+    pub struct QueryTimer(Option<HistogramTimer>);
+
+    impl Drop for QueryTimer {
+        fn drop(&mut self) {
+            if let Some(timer) = self.0.take() {
+                timer.observe_duration();
+                TIMING.with(|t| t.set(false));
+            }
+        }
+    }
+
+    async fn collect_loop() {
+        let mut interval = interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            collect_gateway_latency().await;
+            if let Err(err) = collect_totals() {
+                warn!("Could not collect totals for metrics: {}", err);
+            }
+        }
+    }
+
+    async fn collect_gateway_latency() {
+        let gauge = &metrics().gateway_latency;
+        for shard in health::shard_latencies().await {
+            if let Some(ms) = shard.latency_ms {
+                gauge
+                    .with_label_values(&[&shard.id.to_string()])
+                    .set(ms as f64 / 1000.0);
+            }
+        }
+    }
+
+    fn collect_totals() -> Result<(), Error> {
+        let m = metrics();
+        let mut conn = databaser::establish_connection()?;
+        let now = Local::now().naive_local();
+        m.reminders_overdue
+            .set(databaser::count_overdue_reminders(&mut conn, now)?);
+        // Reset first so guilds with nothing left drop out
+        m.quotes.reset();
+        for (guild, count) in databaser::count_quotes_by_guild(&mut conn)? {
+            m.quotes.with_label_values(&[&guild.to_string()]).set(count);
+        }
+        m.events.reset();
+        for (guild, count) in databaser::count_events_by_guild(&mut conn)? {
+            m.events.with_label_values(&[&guild.to_string()]).set(count);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod imp {
+    use std::time::Duration;
+
+    pub fn start() {}
+
+    pub fn command_finished(_command: &str, _latency: Duration) {}
+
+    pub fn framework_error(_kind: &str) {}
+
+    pub fn reminder_fired() {}
+
+    pub fn reminder_missed() {}
+
+    pub struct QueryTimer;
+
+    pub fn query_timer(_query: &str) -> QueryTimer {
+        QueryTimer
+    }
+}
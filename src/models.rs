@@ -1,32 +1,42 @@
 // models.rs
-use crate::schema::{events, members, nicknames, quotes, reminders};
+use crate::schema::{
+    audit_log, daily_quotes, events, guild_settings, members, nicknames, quote_of_the_day_settings,
+    quote_votes, quotes, reminders, served_quotes,
+};
 use chrono::prelude::*;
 use diesel::prelude::*;
-use std::fmt;
+use serde::Serialize;
 
-#[derive(Debug, Queryable, Identifiable, Selectable)]
+#[derive(Debug, Queryable, Identifiable, Selectable, Serialize)]
 #[diesel(table_name = members)]
+#[diesel(primary_key(guild_id, id))]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct SchlonghouseMember {
     pub id: i64,
     pub primary_name: String,
     pub is_member: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub guild_id: i64,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
-#[diesel(belongs_to(SchlonghouseMember, foreign_key = primary_name))]
+// Nicknames and events point at a member by (guild_id, id), which diesel's
+// associations can't express, so they're looked up with explicit filters.
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq, Serialize)]
 #[diesel(table_name = nicknames)]
 pub struct Nickname {
     pub id: i32,
     pub nickname: String,
     pub primary_name: i64,
+    pub guild_id: i64,
+    pub deleted_at: Option<NaiveDateTime>,
 }
-#[derive(Debug, Insertable, Associations)]
-#[diesel(belongs_to(SchlonghouseMember, foreign_key = primary_name))]
+#[derive(Debug, Insertable)]
 #[diesel(table_name = nicknames)]
 pub struct NewNickname<'a> {
     pub nickname: &'a str,
     pub primary_name: i64,
+    pub guild_id: i64,
 }
 
 #[derive(Debug, Insertable)]
@@ -35,14 +45,22 @@ pub struct NewMember<'a> {
     pub id: i64,
     pub primary_name: &'a str,
     pub is_member: bool,
+    pub guild_id: i64,
 }
-#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq, Serialize)]
 #[diesel(belongs_to(SchlonghouseMember))]
 #[diesel(table_name = quotes)]
 pub struct Quote {
     pub id: i32,
     pub quoted: String,
     pub quote: String,
+    pub created_at: NaiveDateTime,
+    pub legacy: bool,
+    pub guild_id: i64,
+    pub submitted_by: Option<i64>,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// False while it's waiting for a moderator, see `quote_approval`.
+    pub approved: bool,
 }
 
 #[derive(Debug, Insertable)]
@@ -51,9 +69,179 @@ pub struct Quote {
 pub struct NewQuote<'a> {
     pub quoted: &'a str,
     pub quote: &'a str,
+    pub legacy: bool,
+    pub guild_id: i64,
+    pub submitted_by: Option<i64>,
+    pub approved: bool,
+}
+#[derive(Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
+#[diesel(belongs_to(Quote))]
+#[diesel(table_name = served_quotes)]
+pub struct ServedQuote {
+    pub id: i32,
+    pub quote_id: i32,
+    pub channel_id: i64,
+    pub served_at: NaiveDateTime,
+    pub message_id: Option<i64>,
+}
+#[derive(Debug, Insertable, Associations)]
+#[diesel(belongs_to(Quote))]
+#[diesel(table_name = served_quotes)]
+pub struct NewServedQuote {
+    pub quote_id: i32,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+}
+#[derive(Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
+#[diesel(belongs_to(Quote))]
+#[diesel(primary_key(quote_id, user_id))]
+#[diesel(table_name = quote_votes)]
+pub struct QuoteVote {
+    pub quote_id: i32,
+    pub user_id: i64,
+    pub vote: i16,
+    pub voted_at: NaiveDateTime,
+}
+#[derive(Debug, Insertable, Associations)]
+#[diesel(belongs_to(Quote))]
+#[diesel(table_name = quote_votes)]
+pub struct NewQuoteVote {
+    pub quote_id: i32,
+    pub user_id: i64,
+    pub vote: i16,
+}
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteScore {
+    pub upvotes: i64,
+    pub downvotes: i64,
+}
+impl QuoteScore {
+    pub fn total(&self) -> i64 {
+        self.upvotes - self.downvotes
+    }
+}
+#[derive(Debug, Clone, Queryable, Identifiable, Selectable, PartialEq, Serialize)]
+#[diesel(table_name = quote_of_the_day_settings)]
+pub struct QuoteOfTheDaySettings {
+    pub id: i32,
+    pub enabled: bool,
+    pub post_time: NaiveTime,
+    pub channel_id: Option<i64>,
+    pub repeat_window_days: i32,
+    pub guild_id: i64,
+}
+#[derive(Debug, Insertable)]
+#[diesel(table_name = quote_of_the_day_settings)]
+pub struct NewQuoteOfTheDaySettings {
+    pub guild_id: i64,
+}
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = quote_of_the_day_settings)]
+pub struct UpdateQuoteOfTheDaySettings {
+    pub enabled: Option<bool>,
+    pub post_time: Option<NaiveTime>,
+    pub channel_id: Option<Option<i64>>,
+    pub repeat_window_days: Option<i32>,
+}
+#[derive(Debug, Clone, Queryable, Identifiable, Selectable, PartialEq, Serialize)]
+#[diesel(table_name = guild_settings)]
+#[diesel(primary_key(guild_id))]
+pub struct GuildSettings {
+    pub guild_id: i64,
+    pub announcement_channel_id: Option<i64>,
+    pub prefixes: Vec<String>,
+    pub admin_role_id: Option<i64>,
+    pub timezone: Option<String>,
+    pub quote_approval: bool,
+    pub disabled_features: Vec<String>,
+    pub updated_at: NaiveDateTime,
+    pub moderator_role_id: Option<i64>,
+    pub audit_channel_id: Option<i64>,
+}
+impl GuildSettings {
+    /// What a guild has before its settings are first changed, matching the
+    /// column defaults.
+    pub fn defaults(guild: i64) -> Self {
+        GuildSettings {
+            guild_id: guild,
+            announcement_channel_id: None,
+            prefixes: crate::settings::DEFAULT_PREFIXES.map(String::from).to_vec(),
+            admin_role_id: None,
+            timezone: None,
+            quote_approval: false,
+            disabled_features: vec![],
+            updated_at: Local::now().naive_local(),
+            moderator_role_id: None,
+            audit_channel_id: None,
+        }
+    }
+}
+#[derive(Debug, Insertable)]
+#[diesel(table_name = guild_settings)]
+pub struct NewGuildSettings {
+    pub guild_id: i64,
+}
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = guild_settings)]
+pub struct UpdateGuildSettings {
+    pub announcement_channel_id: Option<Option<i64>>,
+    pub prefixes: Option<Vec<String>>,
+    pub admin_role_id: Option<Option<i64>>,
+    pub moderator_role_id: Option<Option<i64>>,
+    pub audit_channel_id: Option<Option<i64>>,
+    pub timezone: Option<Option<String>>,
+    pub quote_approval: Option<bool>,
+    pub disabled_features: Option<Vec<String>>,
 }
-#[derive(Clone, Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
-#[diesel(belongs_to(SchlonghouseMember, foreign_key = owned_by))]
+#[derive(Debug, Clone, Queryable, Identifiable, Selectable, PartialEq)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub guild_id: i64,
+    pub actor_id: Option<i64>,
+    pub command: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+    pub mirrored: bool,
+}
+#[derive(Debug, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry<'a> {
+    pub guild_id: i64,
+    pub actor_id: Option<i64>,
+    pub command: &'a str,
+    pub action: &'a str,
+    pub entity_type: &'a str,
+    pub entity_id: &'a str,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+#[derive(Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
+#[diesel(belongs_to(Quote))]
+#[diesel(table_name = daily_quotes)]
+pub struct DailyQuote {
+    pub id: i32,
+    pub quote_id: i32,
+    pub posted_on: NaiveDate,
+    pub channel_id: i64,
+    /// `None` until the quote has been posted.
+    pub message_id: Option<i64>,
+    pub guild_id: i64,
+}
+#[derive(Debug, Insertable, Associations)]
+#[diesel(belongs_to(Quote))]
+#[diesel(table_name = daily_quotes)]
+pub struct NewDailyQuote {
+    pub quote_id: i32,
+    pub posted_on: NaiveDate,
+    pub channel_id: i64,
+    pub guild_id: i64,
+}
+#[derive(Clone, Queryable, Identifiable, Selectable, Debug, PartialEq, Serialize)]
 #[diesel(table_name = events)]
 pub struct ToddEvent {
     pub id: i32,
@@ -63,9 +251,10 @@ pub struct ToddEvent {
     pub is_recuring: bool,
     pub owned_by: i64,
     pub recurring_by: Option<i16>,
+    pub guild_id: i64,
+    pub deleted_at: Option<NaiveDateTime>,
 }
-#[derive(Debug, Insertable, Associations)]
-#[diesel(belongs_to(SchlonghouseMember, foreign_key = owned_by))]
+#[derive(Debug, Insertable)]
 #[diesel(table_name = events)]
 pub struct NewEvent<'a> {
     pub title: &'a str,
@@ -74,14 +263,19 @@ pub struct NewEvent<'a> {
     pub is_recuring: bool,
     pub owned_by: i64,
     pub recurring_by: Option<i16>,
+    pub guild_id: i64,
 }
-#[derive(Clone, Queryable, Identifiable, Selectable, Debug, Associations, PartialEq)]
+#[derive(Clone, Queryable, Identifiable, Selectable, Debug, Associations, PartialEq, Serialize)]
 #[diesel(belongs_to(ToddEvent, foreign_key = event_id))]
 #[diesel(table_name = reminders)]
 pub struct Reminder {
     pub id: i32,
     pub time_before: NaiveDateTime,
     pub event_id: i32,
+    pub guild_id: i64,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// How many times announcing it has been tried.
+    pub attempts: i32,
 }
 #[derive(Debug, Insertable, Associations)]
 #[diesel(belongs_to(ToddEvent, foreign_key = event_id))]
@@ -89,59 +283,14 @@ pub struct Reminder {
 pub struct NewReminder<'a> {
     pub time_before: &'a NaiveDateTime,
     pub event_id: i32,
+    pub guild_id: i64,
 }
 
-#[derive(Debug)]
-pub struct BirthdayEvent {
-    pub title: String,
-    pub description: String,
-    pub date: NaiveDateTime,
-    pub is_recuring: bool,
-    pub member_id: i64,
-}
 #[derive(Debug, Clone)]
 pub enum CalendarType {
     Tevent(ToddEvent),
     Teminder(Reminder),
 }
-#[derive(Debug)]
-pub enum Calendar {
-    Event,
-    Birthday,
-    Reminder,
-}
-pub struct CalendarParseError;
-impl fmt::Display for CalendarParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error parsing Calendar")
-    }
-}
-impl fmt::Debug for CalendarParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Err at {{ {} line {} }}, \nnot event reminder or birthday",
-            file!(),
-            line!()
-        )
-    }
-}
-
-impl std::str::FromStr for Calendar {
-    type Err = CalendarParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.contains("event") {
-            Ok(Calendar::Event)
-        } else if s.contains("birthday") {
-            Ok(Calendar::Birthday)
-        } else if s.contains("reminder") {
-            Ok(Calendar::Reminder)
-        } else {
-            Err(CalendarParseError)
-        }
-    }
-}
 pub trait ToCalendar {
     fn to_calendar(self) -> CalendarType;
 }
@@ -166,7 +315,7 @@ impl CalendarType {
                 } else {
                     return "Reminder".to_string();
                 };
-                if let Ok(e) = databaser::get_event_by_id(&mut conn, r.event_id) {
+                if let Ok(e) = databaser::get_event_by_id(&mut conn, r.guild_id, r.event_id) {
                     format!("{} Reminder", e.title)
                 } else {
                     "Reminder".to_string()
@@ -183,7 +332,7 @@ impl CalendarType {
                     Ok(c) => c,
                     Err(_) => return None,
                 };
-                let parent = match databaser::get_event_by_id(&mut conn, r.event_id) {
+                let parent = match databaser::get_event_by_id(&mut conn, r.guild_id, r.event_id) {
                     Ok(p) => p,
                     Err(_) => return None,
                 };
@@ -197,17 +346,11 @@ impl CalendarType {
             CalendarType::Teminder(r) => r.time_before,
         }
     }
-    pub fn is_recuring(&self) -> bool {
-        match self {
-            CalendarType::Tevent(t) => t.is_recuring,
-            CalendarType::Teminder(_) => false,
-        }
-    }
 }
 impl Reminder {
     pub fn parent(&self, conn: &mut PgConnection) -> Option<ToddEvent> {
         use crate::databaser;
-        if let Ok(p) = databaser::get_event_by_id(conn, self.event_id) {
+        if let Ok(p) = databaser::get_event_by_id(conn, self.guild_id, self.event_id) {
             return Some(p);
         }
         None
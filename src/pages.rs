@@ -0,0 +1,166 @@
+// pages.rs
+
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+use serenity::{ButtonStyle, CreateComponents, CreateEmbed, InteractionResponseType};
+use std::time::Duration;
+
+/// The most characters Discord takes in a message. Embed descriptions can
+/// be longer, but pages this size are easier to read.
+pub const PAGE_LEN: usize = 2000;
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+const PREVIOUS: &str = "page_previous";
+const NEXT: &str = "page_next";
+
+/// Joins `items` with newlines into as few pages of at most `limit`
+/// characters as possible. Pages only break between items, unless an item
+/// doesn't fit on a page by itself.
+pub fn paginate(items: &[String], limit: usize) -> Vec<String> {
+    let mut pages: Vec<String> = vec![];
+    let mut last_len = 0;
+    for item in items {
+        let len = item.chars().count();
+        match pages.last_mut() {
+            Some(last) if last_len + 1 + len <= limit => {
+                last.push('\n');
+                last.push_str(item);
+                last_len += 1 + len;
+            }
+            _ if len <= limit => {
+                pages.push(item.clone());
+                last_len = len;
+            }
+            _ => {
+                let chars: Vec<char> = item.chars().collect();
+                for chunk in chars.chunks(limit) {
+                    pages.push(chunk.iter().collect());
+                    last_len = chunk.len();
+                }
+            }
+        }
+    }
+    pages
+}
+
+/// Replies with `items` in an embed titled `title`, one page at a time with
+/// buttons to flip through them when they don't all fit. `empty` is shown
+/// when there's nothing to list.
+pub async fn reply_pages(
+    ctx: Context<'_>,
+    title: &str,
+    items: &[String],
+    empty: &str,
+) -> Result<(), Error> {
+    let mut pages = paginate(items, PAGE_LEN);
+    if pages.is_empty() {
+        pages.push(empty.to_string());
+    }
+    let mut current = 0;
+    let reply = ctx
+        .send(|m| {
+            m.embed(|e| fill_page(e, title, &pages, current))
+                .components(|c| fill_buttons(c, &pages, current))
+        })
+        .await?;
+    if pages.len() == 1 {
+        return Ok(());
+    }
+
+    let message = reply.message().await?;
+    while let Some(interaction) = message
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(PAGE_TIMEOUT)
+        .await
+    {
+        current = match interaction.data.custom_id.as_str() {
+            PREVIOUS => current.saturating_sub(1),
+            NEXT => (current + 1).min(pages.len() - 1),
+            _ => current,
+        };
+        interaction
+            .create_interaction_response(ctx, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.embed(|e| fill_page(e, title, &pages, current))
+                            .components(|c| fill_buttons(c, &pages, current))
+                    })
+            })
+            .await?;
+    }
+    // No one's listening for the buttons any more
+    reply
+        .edit(ctx, |m| {
+            m.embed(|e| fill_page(e, title, &pages, current))
+                .components(|c| c)
+        })
+        .await?;
+    Ok(())
+}
+
+fn fill_page<'a>(
+    e: &'a mut CreateEmbed,
+    title: &str,
+    pages: &[String],
+    current: usize,
+) -> &'a mut CreateEmbed {
+    e.title(title).description(&pages[current]);
+    if pages.len() > 1 {
+        e.footer(|f| f.text(format!("Page {} of {}", current + 1, pages.len())));
+    }
+    e
+}
+
+fn fill_buttons<'a>(
+    c: &'a mut CreateComponents,
+    pages: &[String],
+    current: usize,
+) -> &'a mut CreateComponents {
+    if pages.len() < 2 {
+        return c;
+    }
+    c.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(PREVIOUS)
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(current == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(NEXT)
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(current + 1 == pages.len())
+        })
+    })
+}
+
+#[cfg(test)]
+mod pages_tests {
+    use super::*;
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_paginate_breaks_between_items() {
+        let lines = items(&["aaaa", "bbbb", "cccc"]);
+        assert_eq!(paginate(&lines, 9), ["aaaa\nbbbb", "cccc"]);
+        assert_eq!(paginate(&lines, 100), ["aaaa\nbbbb\ncccc"]);
+        assert!(paginate(&[], 100).is_empty());
+        // Items with newlines of their own stay together
+        let sections = items(&["a\n```\nb\n```", "c"]);
+        assert_eq!(paginate(&sections, 12), ["a\n```\nb\n```", "c"]);
+    }
+
+    #[test]
+    fn test_paginate_counts_characters() {
+        // Each of these is 3 bytes but one character
+        let lines = items(&["ééé", "ééé"]);
+        assert_eq!(paginate(&lines, 7), ["ééé\nééé"]);
+        // Too long for a page of its own, so it's split between characters
+        let long = items(&["ab", "ééééé"]);
+        assert_eq!(paginate(&long, 3), ["ab", "ééé", "éé"]);
+    }
+}
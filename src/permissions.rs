@@ -0,0 +1,136 @@
+// permissions.rs
+
+use crate::errors::ToddError;
+use crate::models::GuildSettings;
+use crate::settings;
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+use serenity::RoleId;
+use std::fmt;
+
+/// How trusted someone is in a guild. Commands mark the level they need with
+/// `check = "permissions::moderator"` or `check = "permissions::admin"`,
+/// and commands on things people own use `require_owner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Everyone,
+    Moderator,
+    Admin,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Everyone => write!(f, "everyone"),
+            Level::Moderator => write!(f, "moderators"),
+            Level::Admin => write!(f, "admins"),
+        }
+    }
+}
+
+/// Manage Server or the guild's admin role make an admin, the moderator role
+/// makes a moderator.
+pub fn level_of(settings: &GuildSettings, roles: &[RoleId], manage_guild: bool) -> Level {
+    let has_role = |role: Option<i64>| role.is_some_and(|r| roles.contains(&RoleId(r as u64)));
+    if manage_guild || has_role(settings.admin_role_id) {
+        Level::Admin
+    } else if has_role(settings.moderator_role_id) {
+        Level::Moderator
+    } else {
+        Level::Everyone
+    }
+}
+
+/// The author's level in the guild the command was run in. Outside a guild
+/// everyone is just everyone.
+pub async fn author_level(ctx: Context<'_>) -> Result<Level, Error> {
+    let (Some(guild), Some(member)) = (ctx.guild_id(), ctx.author_member().await) else {
+        return Ok(Level::Everyone);
+    };
+    let settings = settings::for_guild(ctx.data(), i64::from(guild)).await?;
+    let manage_guild = member.permissions(ctx)?.manage_guild();
+    Ok(level_of(&settings, &member.roles, manage_guild))
+}
+
+/// Refuses the command unless the author is at least `level`.
+pub async fn require(ctx: Context<'_>, level: Level) -> Result<(), Error> {
+    if author_level(ctx).await? >= level {
+        return Ok(());
+    }
+    Err(Error::from(ToddError::PermissionDenied(format!(
+        "Error: only {} can use `{}`",
+        level,
+        ctx.command().qualified_name
+    ))))
+}
+
+/// Refuses the command unless the author is one of `owners` or a moderator.
+/// `what` names the thing being changed for the reply.
+pub async fn require_owner(ctx: Context<'_>, owners: &[i64], what: &str) -> Result<(), Error> {
+    if owners.contains(&i64::from(ctx.author().id)) || author_level(ctx).await? >= Level::Moderator
+    {
+        return Ok(());
+    }
+    Err(Error::from(ToddError::PermissionDenied(format!(
+        "Error: only the owner of {} or a moderator can do that",
+        what
+    ))))
+}
+
+pub async fn moderator(ctx: Context<'_>) -> Result<bool, Error> {
+    require(ctx, Level::Moderator).await?;
+    Ok(true)
+}
+
+pub async fn admin(ctx: Context<'_>) -> Result<bool, Error> {
+    require(ctx, Level::Admin).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod permissions_tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn sample_settings() -> GuildSettings {
+        GuildSettings {
+            guild_id: -1,
+            announcement_channel_id: None,
+            prefixes: settings::DEFAULT_PREFIXES.map(String::from).to_vec(),
+            admin_role_id: Some(1),
+            timezone: None,
+            quote_approval: false,
+            disabled_features: vec![],
+            updated_at: Local::now().naive_local(),
+            moderator_role_id: Some(2),
+            audit_channel_id: None,
+        }
+    }
+
+    #[test]
+    fn test_level_of() {
+        let settings = sample_settings();
+        assert_eq!(level_of(&settings, &[], false), Level::Everyone);
+        assert_eq!(level_of(&settings, &[RoleId(3)], false), Level::Everyone);
+        assert_eq!(level_of(&settings, &[RoleId(2)], false), Level::Moderator);
+        assert_eq!(
+            level_of(&settings, &[RoleId(2), RoleId(1)], false),
+            Level::Admin
+        );
+        assert_eq!(level_of(&settings, &[], true), Level::Admin);
+        assert!(Level::Admin > Level::Moderator && Level::Moderator > Level::Everyone);
+    }
+
+    #[test]
+    fn test_unset_roles_grant_nothing() {
+        let settings = GuildSettings {
+            admin_role_id: None,
+            moderator_role_id: None,
+            ..sample_settings()
+        };
+        assert_eq!(
+            level_of(&settings, &[RoleId(1), RoleId(2)], false),
+            Level::Everyone
+        );
+    }
+}
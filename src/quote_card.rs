@@ -0,0 +1,322 @@
+// quote_card.rs
+
+use crate::errors::ToddError;
+use crate::models::{Quote, SchlonghouseMember};
+use crate::{Context, Error};
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use chrono::NaiveDateTime;
+use image::{imageops, Rgba, RgbaImage};
+use poise::serenity_prelude as serenity;
+use serenity::{AttachmentType, Message, UserId};
+use std::borrow::Cow;
+use std::io::Cursor;
+
+// The font is compiled in so a card renders the same on every machine.
+static FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+
+const WIDTH: u32 = 800;
+const PADDING: u32 = 40;
+const AVATAR_SIZE: u32 = 128;
+const NAME_SIZE: f32 = 40.0;
+const DATE_SIZE: f32 = 24.0;
+const QUOTE_SIZE: f32 = 32.0;
+const BACKGROUND: Rgba<u8> = Rgba([43, 45, 49, 255]);
+const TEXT: Rgba<u8> = Rgba([242, 243, 245, 255]);
+const MUTED: Rgba<u8> = Rgba([148, 155, 164, 255]);
+
+pub struct QuoteCard<'a> {
+    pub quote: &'a str,
+    pub name: &'a str,
+    pub date: NaiveDateTime,
+    pub avatar: Option<&'a RgbaImage>,
+}
+
+impl QuoteCard<'_> {
+    pub fn render(&self) -> Result<RgbaImage, Error> {
+        let font = FontRef::try_from_slice(FONT)?;
+        let quote_font = font.as_scaled(PxScale::from(QUOTE_SIZE));
+        let line_height = quote_font.height() + quote_font.line_gap();
+        let text_width = (WIDTH - 2 * PADDING) as f32;
+
+        let lines = wrap_text(
+            &font,
+            QUOTE_SIZE,
+            &format!("\u{201c}{}\u{201d}", self.quote),
+            text_width,
+        );
+        let quote_top = PADDING + AVATAR_SIZE + PADDING / 2;
+        let height = quote_top + (lines.len() as f32 * line_height).ceil() as u32 + PADDING;
+
+        let mut card = RgbaImage::from_pixel(WIDTH, height, BACKGROUND);
+
+        draw_avatar(&mut card, self.avatar, PADDING, PADDING);
+        let header_x = (PADDING + AVATAR_SIZE + PADDING / 2) as f32;
+        let name_font = font.as_scaled(PxScale::from(NAME_SIZE));
+        let name_baseline = PADDING as f32 + AVATAR_SIZE as f32 / 2.0;
+        draw_text(
+            &mut card,
+            &font,
+            NAME_SIZE,
+            TEXT,
+            header_x,
+            name_baseline,
+            self.name,
+        );
+        draw_text(
+            &mut card,
+            &font,
+            DATE_SIZE,
+            MUTED,
+            header_x,
+            name_baseline - name_font.descent() + DATE_SIZE,
+            &self.date.format("%B %-d, %Y").to_string(),
+        );
+
+        for (i, line) in lines.iter().enumerate() {
+            let baseline = quote_top as f32 + quote_font.ascent() + i as f32 * line_height;
+            draw_text(
+                &mut card,
+                &font,
+                QUOTE_SIZE,
+                TEXT,
+                PADDING as f32,
+                baseline,
+                line,
+            );
+        }
+
+        Ok(card)
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        let mut output = Cursor::new(Vec::new());
+        self.render()?
+            .write_to(&mut output, image::ImageOutputFormat::Png)?;
+        Ok(output.into_inner())
+    }
+}
+
+fn text_width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(p) = previous {
+            width += scaled.kern(p, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+// Greedy word wrap. Words wider than a whole line are split by character.
+fn wrap_text(font: &FontRef, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if text_width(font, size, &candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            line = String::new();
+            for c in word.chars() {
+                line.push(c);
+                if text_width(font, size, &line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(line);
+                    line = c.to_string();
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let coverage = coverage.clamp(0.0, 1.0);
+    for i in 0..3 {
+        pixel[i] = (pixel[i] as f32 * (1.0 - coverage) + color[i] as f32 * coverage).round() as u8;
+    }
+}
+
+fn draw_text(
+    card: &mut RgbaImage,
+    font: &FontRef,
+    size: f32,
+    color: Rgba<u8>,
+    x: f32,
+    baseline: f32,
+    text: &str,
+) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut caret = x;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(p) = previous {
+            caret += scaled.kern(p, id);
+        }
+        let glyph = id.with_scale_and_position(size, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if px >= 0 && py >= 0 && (px as u32) < card.width() && (py as u32) < card.height() {
+                    blend(card.get_pixel_mut(px as u32, py as u32), color, coverage);
+                }
+            });
+        }
+    }
+}
+
+// Draws the avatar cropped to a circle, or a plain circle if there isn't one.
+fn draw_avatar(card: &mut RgbaImage, avatar: Option<&RgbaImage>, x: u32, y: u32) {
+    let resized = avatar
+        .map(|a| imageops::resize(a, AVATAR_SIZE, AVATAR_SIZE, imageops::FilterType::Triangle));
+    let radius = AVATAR_SIZE as f32 / 2.0;
+    for ay in 0..AVATAR_SIZE {
+        for ax in 0..AVATAR_SIZE {
+            let dx = ax as f32 + 0.5 - radius;
+            let dy = ay as f32 + 0.5 - radius;
+            let edge = radius - (dx * dx + dy * dy).sqrt() + 0.5;
+            if edge <= 0.0 {
+                continue;
+            }
+            let (color, alpha) = match &resized {
+                Some(r) => {
+                    let p = *r.get_pixel(ax, ay);
+                    (p, p[3] as f32 / 255.0)
+                }
+                None => (MUTED, 1.0),
+            };
+            blend(
+                card.get_pixel_mut(x + ax, y + ay),
+                color,
+                edge.min(1.0) * alpha,
+            );
+        }
+    }
+}
+
+async fn fetch_avatar(ctx: Context<'_>, member_id: i64) -> Option<RgbaImage> {
+    let user = UserId(member_id as u64).to_user(ctx).await.ok()?;
+    let url = user
+        .static_avatar_url()
+        .unwrap_or_else(|| user.default_avatar_url())
+        .replace("size=1024", &format!("size={}", AVATAR_SIZE));
+    let bytes = reqwest::get(url).await.ok()?.bytes().await.ok()?;
+    image::load_from_memory(&bytes).ok().map(|i| i.to_rgba8())
+}
+
+/// Renders `quote` as a card and attaches it in reply to the invoking message.
+pub async fn reply_with_quote_card(
+    ctx: Context<'_>,
+    member: &SchlonghouseMember,
+    quote: &Quote,
+) -> Result<Message, Error> {
+    let message = if let Context::Prefix(p) = ctx {
+        p.msg
+    } else {
+        return Err(Error::from(ToddError::InvalidInput(
+            "Error: not prefix command".to_string(),
+        )));
+    };
+    let avatar = fetch_avatar(ctx, member.id).await;
+    let png = QuoteCard {
+        quote: &quote.quote,
+        name: &member.primary_name,
+        date: quote.created_at,
+        avatar: avatar.as_ref(),
+    }
+    .to_png()?;
+
+    let output = ctx
+        .channel_id()
+        .send_message(&ctx.http(), |m| {
+            m.add_file(AttachmentType::Bytes {
+                data: Cow::Owned(png),
+                filename: format!("quote-{}.png", quote.id),
+            })
+            .reference_message(message)
+        })
+        .await?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod quote_card_tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const SNAPSHOT: &str = "assets/snapshots/quote_card.png";
+
+    fn sample_avatar() -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 {
+                Rgba([88, 101, 242, 255])
+            } else {
+                Rgba([235, 69, 158, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn test_quote_card_snapshot() -> Result<(), Error> {
+        let avatar = sample_avatar();
+        let card = QuoteCard {
+            quote: "I'm not saying I'm always right, I'm just saying I've never been wrong. \
+                    Supercalifragilisticexpialidociousness-is-not-a-real-word-but-it-should-be",
+            name: "getty",
+            date: NaiveDate::from_ymd_opt(2023, 12, 30)
+                .unwrap()
+                .and_hms_opt(19, 35, 42)
+                .unwrap(),
+            avatar: Some(&avatar),
+        };
+        let rendered = card.render()?;
+        assert_eq!(rendered, card.render()?, "Rendering is not deterministic");
+
+        // Run with `UPDATE_SNAPSHOTS=1` after an intentional change to the layout
+        if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+            rendered.save(SNAPSHOT)?;
+        }
+        let expected = image::open(SNAPSHOT)?.to_rgba8();
+        assert!(
+            rendered == expected,
+            "Quote card does not match {}, rerun with UPDATE_SNAPSHOTS=1 if this was intended",
+            SNAPSHOT
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_text_fits_width() -> Result<(), Error> {
+        let font = FontRef::try_from_slice(FONT)?;
+        let lines = wrap_text(&font, QUOTE_SIZE, "word ".repeat(50).trim(), 300.0);
+        assert!(lines.len() > 1);
+        for line in lines {
+            assert!(
+                text_width(&font, QUOTE_SIZE, &line) <= 300.0,
+                "Too wide: {}",
+                line
+            );
+        }
+        Ok(())
+    }
+}
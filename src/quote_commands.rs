@@ -0,0 +1,391 @@
+// quote_commands.rs
+
+use crate::audit::Actor;
+use crate::databaser;
+use crate::guilds;
+use crate::member_commands;
+use crate::pages;
+use crate::permissions;
+use crate::{Context, Error};
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use serenity::ReactionType;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const UPVOTE: &str = "👍";
+pub const DOWNVOTE: &str = "👎";
+const LEADERBOARD_SIZE: i64 = 10;
+pub const EXPORT_DIR: &str = "data";
+const NO_VOTES: &str = "No one has voted on any quotes yet";
+
+#[poise::command(
+    prefix_command,
+    global_cooldown = 30,
+    category = "Based Todd",
+    broadcast_typing,
+    subcommands(
+        "top",
+        "worst",
+        "show",
+        "remove",
+        "pending",
+        "approve",
+        "export",
+        "import_legacy"
+    ),
+    subcommand_required
+)]
+pub async fn quote(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, global_cooldown = 30, broadcast_typing)]
+async fn top(ctx: Context<'_>, member: Option<String>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let owner = resolve_owner(ctx, &mut conn, member).await?;
+    let (title, lines) = leaderboard(&mut conn, guild, owner.as_deref(), true)?;
+    pages::reply_pages(ctx, &title, &lines, NO_VOTES).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, global_cooldown = 30, broadcast_typing)]
+async fn worst(ctx: Context<'_>, member: Option<String>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let owner = resolve_owner(ctx, &mut conn, member).await?;
+    let (title, lines) = leaderboard(&mut conn, guild, owner.as_deref(), false)?;
+    pages::reply_pages(ctx, &title, &lines, NO_VOTES).await?;
+    Ok(())
+}
+
+/// The primary name of the member a leaderboard was asked for, if any.
+async fn resolve_owner(
+    ctx: Context<'_>,
+    conn: &mut PgConnection,
+    member: Option<String>,
+) -> Result<Option<String>, Error> {
+    match member {
+        Some(m) => Ok(Some(
+            member_commands::resolve_member(ctx, conn, &m)
+                .await?
+                .primary_name,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// The leaderboard's title and one line per ranked quote.
+fn leaderboard(
+    conn: &mut PgConnection,
+    guild: i64,
+    owner: Option<&str>,
+    best: bool,
+) -> Result<(String, Vec<String>), Error> {
+    let ranked = databaser::get_quote_leaderboard(conn, guild, owner, best, LEADERBOARD_SIZE)?;
+
+    let title = format!(
+        "{} quotes{}",
+        if best { "Top" } else { "Worst" },
+        match &owner {
+            Some(o) => format!(" for {}", o),
+            None => "".to_string(),
+        }
+    );
+    let lines = ranked
+        .iter()
+        .enumerate()
+        .map(|(i, (q, score))| {
+            format!(
+                "{}. **{:+}** \"{}\" - {} (id: {})",
+                i + 1,
+                score,
+                q.quote,
+                q.quoted,
+                q.id
+            )
+        })
+        .collect();
+    Ok((title, lines))
+}
+
+#[poise::command(prefix_command, global_cooldown = 30, broadcast_typing)]
+async fn show(ctx: Context<'_>, id: i32) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let quote = databaser::get_quote_by_id(&mut conn, guild, id)?;
+    let score = databaser::get_quote_score(&mut conn, guild, quote.id)?;
+
+    ctx.reply(format!(
+        "### Quote {}{}\n\"{}\"\n- quoted: {}\n- added: {}{}\n- score: **{:+}** ({} {} / {} {})",
+        quote.id,
+        if quote.approved {
+            ""
+        } else {
+            " (waiting for approval)"
+        },
+        quote.quote,
+        quote.quoted,
+        quote.created_at.format("%D"),
+        match quote.submitted_by {
+            Some(s) => format!(" by <@{}>", s),
+            None => "".to_string(),
+        },
+        score.total(),
+        UPVOTE,
+        score.upvotes,
+        DOWNVOTE,
+        score.downvotes,
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Whoever added the quote and whoever it quotes can remove it, as can
+/// moderators.
+#[poise::command(prefix_command, global_cooldown = 10)]
+async fn remove(ctx: Context<'_>, id: i32) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let quote = databaser::get_quote_by_id(&mut conn, guild, id)?;
+    let mut owners: Vec<i64> = quote.submitted_by.into_iter().collect();
+    if let Ok(quoted) = databaser::get_member_from_name(&mut conn, guild, &quote.quoted) {
+        owners.push(quoted.id);
+    }
+    permissions::require_owner(ctx, &owners, &format!("quote {}", quote.id)).await?;
+
+    databaser::delete_quote_by_id(&mut conn, &Actor::from_ctx(ctx), guild, quote.id)?;
+    ctx.reply(format!(
+        "Removed quote {} from {}'s quotes:\n\"{}\"",
+        quote.id, quote.quoted, quote.quote
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Lists the quotes waiting for a moderator to approve them, when the server
+/// has quote approval on. Turn them down with `!quote remove`.
+#[poise::command(prefix_command, global_cooldown = 10, check = "permissions::moderator")]
+async fn pending(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let lines: Vec<String> = databaser::get_pending_quotes(&mut conn, guild)?
+        .iter()
+        .map(|q| format!("{}: {} - \"{}\"", q.id, q.quoted, q.quote))
+        .collect();
+    pages::reply_pages(
+        ctx,
+        "Quotes waiting for approval",
+        &lines,
+        "No quotes are waiting for approval",
+    )
+    .await
+}
+
+#[poise::command(prefix_command, global_cooldown = 10, check = "permissions::moderator")]
+async fn approve(ctx: Context<'_>, id: i32) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let quote = databaser::approve_quote(&mut conn, &Actor::from_ctx(ctx), guild, id)?;
+    ctx.reply(format!(
+        "Approved quote {} for {}'s quotes:\n\"{}\"",
+        quote.id, quote.quoted, quote.quote
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, global_cooldown = 60, check = "permissions::admin")]
+async fn export(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let dir = Path::new(EXPORT_DIR).join(guild.to_string());
+    let exported = export_quotes(&mut conn, guild, &dir)?;
+
+    let title = format!("Exported quotes to {}/", dir.display());
+    let lines: Vec<String> = exported
+        .iter()
+        .map(|(name, count)| format!("- {}: {} quotes", name, count))
+        .collect();
+    pages::reply_pages(ctx, &title, &lines, "There are no members to export").await?;
+    Ok(())
+}
+
+/// Regenerates `{dir}/{primary_name}.quotes.txt` for every member of `guild`
+/// with quotes, one quote per line. The database is the source of truth;
+/// these files are only a convenience copy and are overwritten each time.
+pub fn export_quotes(
+    conn: &mut PgConnection,
+    guild: i64,
+    dir: &Path,
+) -> Result<Vec<(String, usize)>, Error> {
+    let mut by_member: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for q in databaser::get_all_quotes(conn, guild)? {
+        by_member.entry(q.quoted).or_default().push(q.quote);
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut output = vec![];
+    for (name, quotes) in by_member {
+        let path = dir.join(format!("{}.quotes.txt", name.replace(['/', '\\'], "_")));
+        let tmp_path = path.with_extension("txt.tmp");
+        let contents: String = quotes.iter().map(|q| format!("{}\n", q)).collect();
+        // Write then rename so a failed export never leaves a half written file
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &path)?;
+        output.push((name, quotes.len()));
+    }
+    Ok(output)
+}
+
+#[poise::command(prefix_command, global_cooldown = 60, check = "permissions::admin")]
+async fn import_legacy(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let dir = ctx.data().config.legacy_quotes_dir()?;
+    let mut conn = databaser::establish_connection()?;
+    let report = import_legacy_quotes(&mut conn, &Actor::from_ctx(ctx), guild, dir)?;
+    ctx.reply(report.to_string()).await?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct LegacyImport {
+    /// Member primary name, quotes added, duplicates skipped.
+    pub imported: Vec<(String, usize, usize)>,
+    /// Files that didn't match any member.
+    pub unknown: Vec<String>,
+}
+
+impl std::fmt::Display for LegacyImport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Imported legacy quotes:")?;
+        for (name, added, skipped) in &self.imported {
+            write!(
+                f,
+                "\n- {}: {} added, {} already there",
+                name, added, skipped
+            )?;
+        }
+        if !self.unknown.is_empty() {
+            write!(f, "\nNo member found for: {}", self.unknown.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports the old Python bot's roast files, `{dir}/{name}.txt` with one
+/// quote per line, as legacy quotes of `guild`'s members. Safe to run more
+/// than once.
+pub fn import_legacy_quotes(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    dir: &Path,
+) -> Result<LegacyImport, Error> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "txt") {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut output = LegacyImport::default();
+    for path in files {
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(n) => n.to_lowercase(),
+            None => continue,
+        };
+        let member = match databaser::get_member(conn, guild, &name) {
+            Ok(m) => m,
+            Err(_) => {
+                output.unknown.push(name);
+                continue;
+            }
+        };
+        let lines: Vec<String> = String::from_utf8_lossy(&fs::read(&path)?)
+            .lines()
+            .map(String::from)
+            .collect();
+        let (added, skipped) =
+            databaser::import_legacy_quotes(conn, actor, guild, &member.primary_name, &lines)?;
+        output.imported.push((member.primary_name, added, skipped));
+    }
+    Ok(output)
+}
+
+pub fn vote_reaction(emoji: &str) -> ReactionType {
+    ReactionType::Unicode(emoji.to_string())
+}
+
+// Votes are cast by reacting to the message `!todd` replied with.
+// The message is looked up in `served_quotes` to find which quote it was.
+pub async fn handle_vote_event(
+    ctx: &serenity::Context,
+    event: &poise::Event<'_>,
+) -> Result<(), Error> {
+    let (reaction, added) = match event {
+        poise::Event::ReactionAdd { add_reaction } => (add_reaction, true),
+        poise::Event::ReactionRemove { removed_reaction } => (removed_reaction, false),
+        _ => return Ok(()),
+    };
+    let vote: i16 = if reaction.emoji.unicode_eq(UPVOTE) {
+        1
+    } else if reaction.emoji.unicode_eq(DOWNVOTE) {
+        -1
+    } else {
+        return Ok(());
+    };
+    let user = match reaction.user_id {
+        Some(u) if u != ctx.cache.current_user_id() => i64::from(u),
+        _ => return Ok(()),
+    };
+    let Some(guild) = reaction.guild_id.map(i64::from) else {
+        return Ok(());
+    };
+
+    let mut conn = databaser::establish_connection()?;
+    let served = match databaser::get_served_quote_from_message(
+        &mut conn,
+        i64::from(reaction.message_id),
+    )? {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+    if added {
+        databaser::set_quote_vote(&mut conn, guild, served.quote_id, user, vote)?;
+    } else {
+        databaser::remove_quote_vote(&mut conn, guild, served.quote_id, user, vote)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod quote_commands_tests {
+    use super::*;
+    use diesel::prelude::*;
+
+    #[test]
+    fn test_export_quotes() -> Result<(), Error> {
+        use crate::schema::quotes;
+
+        let mut conn = databaser::establish_connection()?;
+        let owner = "sample_export";
+        let guild = -1;
+        databaser::create_quote(&mut conn, &Actor::bot("test"), guild, owner, "first")?;
+        databaser::create_quote(&mut conn, &Actor::bot("test"), guild, owner, "second")?;
+        let dir = std::env::temp_dir().join("todd_export_test");
+
+        let exported = export_quotes(&mut conn, guild, &dir);
+        diesel::delete(quotes::table.filter(quotes::quoted.eq(owner))).execute(&mut conn)?;
+
+        assert!(exported?.contains(&(owner.to_string(), 2)));
+        let contents = fs::read_to_string(dir.join(format!("{}.quotes.txt", owner)))?;
+        assert_eq!(contents, "first\nsecond\n");
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
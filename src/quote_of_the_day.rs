@@ -0,0 +1,298 @@
+// quote_of_the_day.rs
+
+use crate::audit::Actor;
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::models::{QuoteOfTheDaySettings, UpdateQuoteOfTheDaySettings};
+use crate::permissions;
+use crate::quote_commands;
+use crate::settings::{self, Feature};
+use crate::{Context, Error};
+use chrono::prelude::*;
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use serenity::ChannelId;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// The last day each guild ran out of quotes to pick from.
+static MISSED: Mutex<BTreeMap<i64, NaiveDate>> = Mutex::new(BTreeMap::new());
+
+#[poise::command(
+    prefix_command,
+    global_cooldown = 10,
+    category = "Based Todd",
+    broadcast_typing,
+    subcommands("on", "off", "time", "window", "status"),
+    subcommand_required
+)]
+pub async fn qotd(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn on(ctx: Context<'_>, channel: Option<String>) -> Result<(), Error> {
+    let channel_id = match channel {
+        Some(c) => serenity::utils::parse_channel(&c).ok_or_else(|| {
+            Error::from(ToddError::InvalidInput(format!(
+                "Error: *{}* is not a channel",
+                c
+            )))
+        })?,
+        None => ctx.channel_id().0,
+    };
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let settings = databaser::update_quote_of_the_day_settings(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        guild,
+        &UpdateQuoteOfTheDaySettings {
+            enabled: Some(true),
+            channel_id: Some(Some(channel_id as i64)),
+            ..Default::default()
+        },
+    )?;
+    ctx.reply(format!(
+        "Quote of the day will be posted in <#{}> every day at {}",
+        channel_id,
+        settings.post_time.format("%I:%M %P")
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    databaser::update_quote_of_the_day_settings(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        guild,
+        &UpdateQuoteOfTheDaySettings {
+            enabled: Some(false),
+            ..Default::default()
+        },
+    )?;
+    ctx.reply("Quote of the day turned off").await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn time(ctx: Context<'_>, #[rest] input: String) -> Result<(), Error> {
+    let post_time = parse_post_time(&input)?;
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let settings = databaser::update_quote_of_the_day_settings(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        guild,
+        &UpdateQuoteOfTheDaySettings {
+            post_time: Some(post_time),
+            ..Default::default()
+        },
+    )?;
+    ctx.reply(format!(
+        "Quote of the day will be posted at {}",
+        settings.post_time.format("%I:%M %P")
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn window(ctx: Context<'_>, days: i32) -> Result<(), Error> {
+    if days < 1 {
+        return Err(Error::from(ToddError::InvalidInput(
+            "Error: window must be at least 1 day".to_string(),
+        )));
+    }
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    databaser::update_quote_of_the_day_settings(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        guild,
+        &UpdateQuoteOfTheDaySettings {
+            repeat_window_days: Some(days),
+            ..Default::default()
+        },
+    )?;
+    ctx.reply(format!(
+        "Quotes of the day won't repeat within {} days",
+        days
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command)]
+async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let settings = databaser::get_quote_of_the_day_settings(&mut conn, guild)?;
+    // The guild's day, which is the one quotes are posted for
+    let guild_settings = settings::for_guild(ctx.data(), guild).await?;
+    let date = settings::local_now(&guild_settings).date();
+    let today = databaser::get_daily_quote_on(&mut conn, guild, date)?;
+    ctx.reply(format!(
+        "### Quote of the day\n- enabled: {}\n- channel: {}\n- time: {}\n- no repeats within: {} days\n- posted today: {}",
+        settings.enabled,
+        match settings.channel_id {
+            Some(c) => format!("<#{}>", c),
+            None => "default channel".to_string(),
+        },
+        settings.post_time.format("%I:%M %P"),
+        settings.repeat_window_days,
+        match today {
+            Some(d) if d.message_id.is_none() => format!("quote {}, not posted yet", d.quote_id),
+            Some(d) => format!("quote {}", d.quote_id),
+            None => "no".to_string(),
+        }
+    ))
+    .await?;
+    Ok(())
+}
+
+fn parse_post_time(input: &str) -> Result<NaiveTime, Error> {
+    let formats_to_try: Vec<&str> = vec!["%I:%M %P", "%I:%M %p", "%I:%M%P", "%I:%M%p", "%R"];
+    for f in formats_to_try {
+        if let Ok(t) = NaiveTime::parse_from_str(input.trim(), f) {
+            return Ok(t);
+        }
+    }
+    Err(Error::from(ToddError::InvalidInput(format!(
+        "Error: *{}* isn't a time like 9:00 am",
+        input
+    ))))
+}
+
+// Called from `calendar::check_events_loop` every tick. Posts at most once a
+// day per guild, on the first tick after the configured time, so a restart or
+// a missed tick doesn't skip the day.
+pub async fn post_quote_of_the_day(
+    ctx: &serenity::Context,
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    for settings in databaser::get_enabled_quote_of_the_day_settings(conn)? {
+        if let Err(err) = post_for_guild(ctx, conn, &settings).await {
+            tracing::error!(
+                guild = settings.guild_id,
+                "Error posting quote of the day: {}",
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn post_for_guild(
+    ctx: &serenity::Context,
+    conn: &mut PgConnection,
+    settings: &QuoteOfTheDaySettings,
+) -> Result<(), Error> {
+    let guild = settings.guild_id;
+    let guild_settings = databaser::get_guild_settings(conn, guild)?;
+    if !settings::feature_enabled(&guild_settings, Feature::QuoteOfTheDay) {
+        return Ok(());
+    }
+    let now = settings::local_now(&guild_settings);
+    if now.time() < settings.post_time {
+        return Ok(());
+    }
+    let today = now.date();
+    if databaser::get_daily_quote_on(conn, guild, today)?.is_some() {
+        return Ok(());
+    }
+    let Some(quote) =
+        databaser::pick_quote_of_the_day(conn, guild, today, settings.repeat_window_days)?
+    else {
+        // Checked every tick until a quote comes free, but only worth
+        // logging the first time each day
+        if !first_miss_today(guild, today) {
+            return Ok(());
+        }
+        return Err(Error::from(ToddError::NotFound(format!(
+            "Every quote has been the quote of the day in the last {} days",
+            settings.repeat_window_days
+        ))));
+    };
+
+    let channel = match settings.channel_id {
+        Some(c) => ChannelId(c as u64),
+        None => guilds::announcement_channel(ctx, guild).await?,
+    };
+    let channel_id = i64::from(channel);
+    // Claimed before it's posted, so no one else posts one in the meantime
+    // and a post that went out is never forgotten
+    let Some(daily_quote) = databaser::create_daily_quote(conn, &quote, today, channel_id)? else {
+        return Ok(());
+    };
+    let message = match channel
+        .say(
+            &ctx.http,
+            format!(
+                "# Quote of the day\n\"{}\"\n- {}",
+                quote.quote, quote.quoted
+            ),
+        )
+        .await
+    {
+        Ok(message) => message,
+        Err(err) => {
+            // Nothing went out, so the next tick can try again
+            databaser::delete_daily_quote(conn, &daily_quote)?;
+            return Err(err.into());
+        }
+    };
+    let message_id = i64::from(message.id);
+    databaser::set_daily_quote_message(conn, &daily_quote, message_id)?;
+    databaser::record_served_quote(conn, &quote, channel_id, Some(message_id))?;
+    for emoji in [quote_commands::UPVOTE, quote_commands::DOWNVOTE] {
+        message
+            .react(ctx, quote_commands::vote_reaction(emoji))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Whether this is the first time `today` that `guild` had no quote left to
+/// pick.
+fn first_miss_today(guild: i64, today: NaiveDate) -> bool {
+    let mut missed = MISSED.lock().unwrap_or_else(|e| e.into_inner());
+    missed.insert(guild, today) != Some(today)
+}
+
+#[cfg(test)]
+mod quote_of_the_day_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_post_time() {
+        let valid_inputs = vec![
+            ("9:00 am", (9, 0)),
+            ("09:30 PM", (21, 30)),
+            ("17:45", (17, 45)),
+        ];
+        for (input, (h, m)) in valid_inputs {
+            assert_eq!(
+                parse_post_time(input).ok(),
+                NaiveTime::from_hms_opt(h, m, 0),
+                "Failed for input: {}",
+                input
+            );
+        }
+        assert!(parse_post_time("noon").is_err());
+    }
+
+    #[test]
+    fn test_running_out_of_quotes_is_logged_once_a_day() {
+        let guild = -42;
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert!(first_miss_today(guild, today));
+        assert!(!first_miss_today(guild, today));
+        assert!(first_miss_today(guild, today.succ_opt().unwrap()));
+    }
+}
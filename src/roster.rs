@@ -0,0 +1,124 @@
+// roster.rs
+
+use crate::audit::Actor;
+use crate::config;
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::Error;
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use serenity::{GuildId, User};
+
+const PAGE_SIZE: u64 = 1000;
+const TASK: &str = "roster sync";
+
+/// Set `sync_members = true` to keep `members` in step with the guild roster.
+/// Needs the privileged server members intent enabled for the bot.
+pub fn sync_enabled() -> bool {
+    config::get().sync_members
+}
+
+pub async fn handle_roster_event(
+    ctx: &serenity::Context,
+    event: &poise::Event<'_>,
+) -> Result<(), Error> {
+    if !sync_enabled() {
+        return Ok(());
+    }
+    match event {
+        poise::Event::GuildCreate { guild, .. } => sync_guild(ctx, guild.id).await,
+        poise::Event::GuildMemberAddition { new_member } => {
+            let mut conn = databaser::establish_connection()?;
+            register_user(
+                &mut conn,
+                &Actor::bot(TASK),
+                i64::from(new_member.guild_id),
+                &new_member.user,
+            )?;
+            Ok(())
+        }
+        poise::Event::GuildMemberRemoval { guild_id, user, .. } => {
+            let mut conn = databaser::establish_connection()?;
+            let guild = i64::from(*guild_id);
+            if databaser::set_member_presence(
+                &mut conn,
+                &Actor::bot(TASK),
+                guild,
+                i64::from(user.id),
+                false,
+            )? {
+                tracing::info!(guild, "{} left, marked as no longer a member", user.name);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Registers everyone in the guild and marks members who aren't in it any
+/// more as having left.
+async fn sync_guild(ctx: &serenity::Context, guild_id: GuildId) -> Result<(), Error> {
+    let guild = i64::from(guild_id);
+    let actor = Actor::bot(TASK);
+    let mut conn = databaser::establish_connection()?;
+    let mut present = vec![];
+    let mut added = 0;
+    let mut after = None;
+    loop {
+        let page = guild_id.members(&ctx.http, Some(PAGE_SIZE), after).await?;
+        for m in &page {
+            if m.user.bot {
+                continue;
+            }
+            present.push(i64::from(m.user.id));
+            if register_user(&mut conn, &actor, guild, &m.user)? {
+                added += 1;
+            }
+        }
+        match page.last() {
+            Some(last) if page.len() as u64 == PAGE_SIZE => after = Some(last.user.id),
+            _ => break,
+        }
+    }
+    let departed = databaser::mark_departed_members(&mut conn, &actor, guild, &present)?;
+    tracing::info!(
+        guild,
+        "Synced roster: {} members, {} added, {} marked as left",
+        present.len(),
+        added,
+        departed
+    );
+    Ok(())
+}
+
+/// Adds the user as a member of `guild` named after their username, or marks
+/// them as back if they already are one. Returns whether they were newly added.
+fn register_user(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    user: &User,
+) -> Result<bool, Error> {
+    if user.bot {
+        return Ok(false);
+    }
+    let id = i64::from(user.id);
+    if databaser::get_member(conn, guild, &id.to_string()).is_ok() {
+        databaser::set_member_presence(conn, actor, guild, id, true)?;
+        return Ok(false);
+    }
+    let name = user.name.to_lowercase();
+    let created = match databaser::create_member(conn, actor, guild, id, &name, true) {
+        Err(ToddError::Name(_)) => {
+            // Someone already goes by their username, so tell them apart by id
+            databaser::create_member(conn, actor, guild, id, &fallback_name(&name, id), true)?
+        }
+        other => other?,
+    };
+    tracing::info!(guild, "Registered {} as a member", created.primary_name);
+    Ok(true)
+}
+
+fn fallback_name(name: &str, id: i64) -> String {
+    format!("{}-{}", name, id % 10000)
+}
@@ -1,5 +1,32 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        actor_id -> Nullable<Int8>,
+        command -> Text,
+        action -> Text,
+        entity_type -> Text,
+        entity_id -> Text,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+        mirrored -> Bool,
+    }
+}
+
+diesel::table! {
+    daily_quotes (id) {
+        id -> Int4,
+        quote_id -> Int4,
+        posted_on -> Date,
+        channel_id -> Int8,
+        message_id -> Nullable<Int8>,
+        guild_id -> Int8,
+    }
+}
+
 diesel::table! {
     events (id) {
         id -> Int4,
@@ -9,14 +36,34 @@ diesel::table! {
         is_recuring -> Bool,
         owned_by -> Int8,
         recurring_by -> Nullable<Int2>,
+        guild_id -> Int8,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    guild_settings (guild_id) {
+        guild_id -> Int8,
+        announcement_channel_id -> Nullable<Int8>,
+        prefixes -> Array<Text>,
+        admin_role_id -> Nullable<Int8>,
+        timezone -> Nullable<Text>,
+        quote_approval -> Bool,
+        disabled_features -> Array<Text>,
+        updated_at -> Timestamp,
+        moderator_role_id -> Nullable<Int8>,
+        audit_channel_id -> Nullable<Int8>,
     }
 }
 
 diesel::table! {
-    members (id) {
+    members (guild_id, id) {
         id -> Int8,
         primary_name -> Varchar,
         is_member -> Bool,
+        created_at -> Nullable<Timestamp>,
+        guild_id -> Int8,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -25,6 +72,36 @@ diesel::table! {
         id -> Int4,
         nickname -> Varchar,
         primary_name -> Int8,
+        guild_id -> Int8,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    quote_generations (guild_id, quoted) {
+        guild_id -> Int8,
+        quoted -> Varchar,
+        generation -> Int8,
+    }
+}
+
+diesel::table! {
+    quote_of_the_day_settings (id) {
+        id -> Int4,
+        enabled -> Bool,
+        post_time -> Time,
+        channel_id -> Nullable<Int8>,
+        repeat_window_days -> Int4,
+        guild_id -> Int8,
+    }
+}
+
+diesel::table! {
+    quote_votes (quote_id, user_id) {
+        quote_id -> Int4,
+        user_id -> Int8,
+        vote -> Int2,
+        voted_at -> Timestamp,
     }
 }
 
@@ -33,6 +110,12 @@ diesel::table! {
         id -> Int4,
         quoted -> Varchar,
         quote -> Text,
+        created_at -> Timestamp,
+        legacy -> Bool,
+        guild_id -> Int8,
+        submitted_by -> Nullable<Int8>,
+        deleted_at -> Nullable<Timestamp>,
+        approved -> Bool,
     }
 }
 
@@ -41,11 +124,38 @@ diesel::table! {
         id -> Int4,
         time_before -> Timestamp,
         event_id -> Int4,
+        guild_id -> Int8,
+        deleted_at -> Nullable<Timestamp>,
+        attempts -> Int4,
+    }
+}
+
+diesel::table! {
+    served_quotes (id) {
+        id -> Int4,
+        quote_id -> Int4,
+        channel_id -> Int8,
+        served_at -> Timestamp,
+        message_id -> Nullable<Int8>,
     }
 }
 
-diesel::joinable!(events -> members (owned_by));
-diesel::joinable!(nicknames -> members (primary_name));
+diesel::joinable!(daily_quotes -> quotes (quote_id));
+diesel::joinable!(quote_votes -> quotes (quote_id));
 diesel::joinable!(reminders -> events (event_id));
+diesel::joinable!(served_quotes -> quotes (quote_id));
 
-diesel::allow_tables_to_appear_in_same_query!(events, members, nicknames, quotes, reminders,);
+diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    daily_quotes,
+    events,
+    guild_settings,
+    members,
+    nicknames,
+    quote_generations,
+    quote_of_the_day_settings,
+    quote_votes,
+    quotes,
+    reminders,
+    served_quotes,
+);
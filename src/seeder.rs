@@ -0,0 +1,131 @@
+// seeder.rs
+
+use crate::audit::Actor;
+use crate::databaser;
+use crate::Error;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_SEED_FILE: &str = "seeds/members.toml";
+
+#[derive(Debug, Deserialize)]
+pub struct Seed {
+    #[serde(default)]
+    pub members: Vec<SeedMember>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedMember {
+    pub id: i64,
+    pub primary_name: String,
+    #[serde(default)]
+    pub nicknames: Vec<String>,
+    #[serde(default = "default_is_member")]
+    pub is_member: bool,
+}
+
+fn default_is_member() -> bool {
+    true
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SeedReport {
+    pub members_added: usize,
+    pub nicknames_added: usize,
+}
+
+impl Seed {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::from(format!("Failed to read {}: {}", path.display(), e)))?;
+        let output = toml::from_str(&contents)
+            .map_err(|e| Error::from(format!("Failed to parse {}: {}", path.display(), e)))?;
+        Ok(output)
+    }
+}
+
+/// Loads the seed into `guild` in one transaction. Members that already
+/// exist (by id) and nicknames they already have are left as they are, so
+/// seeding twice changes nothing.
+pub fn seed(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    guild: i64,
+    seed: &Seed,
+) -> Result<SeedReport, Error> {
+    conn.transaction(|conn| {
+        let mut report = SeedReport::default();
+        for m in &seed.members {
+            let primary_name = m.primary_name.to_lowercase();
+            if databaser::create_member_if_missing(
+                conn,
+                actor,
+                guild,
+                m.id,
+                &primary_name,
+                m.is_member,
+            )? {
+                report.members_added += 1;
+            }
+            let member = databaser::get_member(conn, guild, &m.id.to_string())?;
+            let mut existing = databaser::get_member_nicknames(conn, &member)?;
+            for nickname in &m.nicknames {
+                let nickname = nickname.to_lowercase();
+                if nickname == member.primary_name
+                    || existing.iter().any(|n| n.nickname == nickname)
+                {
+                    continue;
+                }
+                existing.push(databaser::create_nickname(conn, actor, &member, &nickname)?);
+                report.nicknames_added += 1;
+            }
+        }
+        Ok(report)
+    })
+}
+
+#[cfg(test)]
+mod seeder_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_seed_file_parses() -> Result<(), Error> {
+        let seed = Seed::from_file(Path::new(DEFAULT_SEED_FILE))?;
+        assert_eq!(seed.members.len(), 14);
+        assert!(seed.members.iter().all(|m| m.is_member));
+        Ok(())
+    }
+
+    #[test]
+    fn test_seed_is_idempotent() -> Result<(), Error> {
+        let sample: Seed = toml::from_str(
+            r#"
+            [[members]]
+            primary_name = "Sample_Seed"
+            id = 1001
+            nicknames = ["seedy", "SEEDY", "sample_seed"]
+            is_member = false
+            "#,
+        )?;
+        let mut conn = databaser::establish_connection()?;
+        let guild = -1;
+        let actor = Actor::bot("test");
+        let first = seed(&mut conn, &actor, guild, &sample);
+        let second = seed(&mut conn, &actor, guild, &sample);
+        let member = databaser::get_member(&mut conn, guild, "seedy")?;
+        databaser::remove_member_and_data(&mut conn, &actor, &member, None)?;
+        assert_eq!(
+            first?,
+            SeedReport {
+                members_added: 1,
+                nicknames_added: 1
+            }
+        );
+        assert_eq!(second?, SeedReport::default());
+        assert_eq!(member.primary_name, "sample_seed");
+        Ok(())
+    }
+}
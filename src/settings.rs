@@ -0,0 +1,530 @@
+// settings.rs
+
+use crate::audit::Actor;
+use crate::databaser;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::models::{GuildSettings, UpdateGuildSettings};
+use crate::pages;
+use crate::permissions;
+use crate::{Context, Data, Error};
+use chrono::prelude::*;
+use diesel::pg::PgConnection;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Prefixes for DMs, which have no guild settings. Guilds start out with the
+/// same ones.
+pub const DEFAULT_PREFIXES: [&str; 3] = ["!", "Todd!", "todd!"];
+
+/// Settings of every guild a command has been run in, so the prefix and
+/// feature checks don't hit the database on every message. Entries are
+/// replaced when `!config` changes them and reloaded after [`CACHE_TTL`].
+pub type SettingsCache = HashMap<i64, (Instant, GuildSettings)>;
+
+/// How long cached settings are used before they're read again.
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Parts of the bot a guild can turn off with `!config set feature.<name> off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Quotes,
+    Markov,
+    Calendar,
+    QuoteOfTheDay,
+    Shitposts,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::Quotes,
+        Feature::Markov,
+        Feature::Calendar,
+        Feature::QuoteOfTheDay,
+        Feature::Shitposts,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Quotes => "quotes",
+            Feature::Markov => "markov",
+            Feature::Calendar => "calendar",
+            Feature::QuoteOfTheDay => "qotd",
+            Feature::Shitposts => "shitposts",
+        }
+    }
+
+    /// The feature a command belongs to, by its qualified name. Commands that
+    /// manage the bot itself don't belong to one and can't be turned off.
+    pub fn for_command(qualified_name: &str) -> Option<Feature> {
+        let mut words = qualified_name.split_whitespace();
+        match (words.next()?, words.next()) {
+            ("todd", Some("imitate")) => Some(Feature::Markov),
+            ("todd" | "old_quotes" | "quote", _) | ("add", Some("quote")) => Some(Feature::Quotes),
+            ("calendar", _) => Some(Feature::Calendar),
+            ("qotd", _) => Some(Feature::QuoteOfTheDay),
+            ("nerd", _) => Some(Feature::Shitposts),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                Error::from(ToddError::InvalidInput(format!(
+                    "Error: *{}* is not a feature, try one of: {}",
+                    s,
+                    feature_names()
+                )))
+            })
+    }
+}
+
+fn feature_names() -> String {
+    Feature::ALL.map(|f| f.name()).join(", ")
+}
+
+/// The settings `!config` knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    AnnouncementChannel,
+    AuditChannel,
+    Prefixes,
+    AdminRole,
+    ModeratorRole,
+    Timezone,
+    QuoteApproval,
+    Feature(Feature),
+}
+
+impl Setting {
+    pub fn all() -> Vec<Setting> {
+        let mut output = vec![
+            Setting::AnnouncementChannel,
+            Setting::AuditChannel,
+            Setting::Prefixes,
+            Setting::AdminRole,
+            Setting::ModeratorRole,
+            Setting::Timezone,
+            Setting::QuoteApproval,
+        ];
+        output.extend(Feature::ALL.map(Setting::Feature));
+        output
+    }
+
+    /// How the setting currently looks for `settings`.
+    pub fn show(&self, settings: &GuildSettings) -> String {
+        match self {
+            Setting::AnnouncementChannel => match settings.announcement_channel_id {
+                Some(c) => format!("<#{}>", c),
+                None => "default".to_string(),
+            },
+            Setting::AuditChannel => match settings.audit_channel_id {
+                Some(c) => format!("<#{}>", c),
+                None => "none".to_string(),
+            },
+            Setting::Prefixes => settings
+                .prefixes
+                .iter()
+                .map(|p| format!("`{}`", p))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Setting::AdminRole => show_role(settings.admin_role_id),
+            Setting::ModeratorRole => show_role(settings.moderator_role_id),
+            Setting::Timezone => settings
+                .timezone
+                .clone()
+                .unwrap_or_else(|| "server time".to_string()),
+            Setting::QuoteApproval => on_off(settings.quote_approval).to_string(),
+            Setting::Feature(f) => on_off(feature_enabled(settings, *f)).to_string(),
+        }
+    }
+
+    /// Turns `value` into the change `!config set` should make. `none`
+    /// clears the optional settings.
+    pub fn parse_change(
+        &self,
+        settings: &GuildSettings,
+        value: &str,
+    ) -> Result<UpdateGuildSettings, Error> {
+        let value = value.trim();
+        let cleared = value.eq_ignore_ascii_case("none");
+        let mut changes = UpdateGuildSettings::default();
+        match self {
+            Setting::AnnouncementChannel => {
+                changes.announcement_channel_id = Some(if cleared {
+                    None
+                } else {
+                    Some(parse_channel(value)?)
+                });
+            }
+            Setting::AuditChannel => {
+                changes.audit_channel_id = Some(if cleared {
+                    None
+                } else {
+                    Some(parse_channel(value)?)
+                });
+            }
+            Setting::Prefixes => {
+                let prefixes: Vec<String> = value.split_whitespace().map(String::from).collect();
+                if prefixes.is_empty() {
+                    return Err(Error::from(ToddError::InvalidInput(
+                        "Error: give at least one prefix".to_string(),
+                    )));
+                }
+                changes.prefixes = Some(prefixes);
+            }
+            Setting::AdminRole => {
+                changes.admin_role_id = Some(if cleared {
+                    None
+                } else {
+                    Some(parse_role(value)?)
+                });
+            }
+            Setting::ModeratorRole => {
+                changes.moderator_role_id = Some(if cleared {
+                    None
+                } else {
+                    Some(parse_role(value)?)
+                });
+            }
+            Setting::Timezone => {
+                changes.timezone = Some(if cleared {
+                    None
+                } else {
+                    parse_timezone(value)?;
+                    Some(value.to_string())
+                });
+            }
+            Setting::QuoteApproval => changes.quote_approval = Some(parse_on_off(value)?),
+            Setting::Feature(f) => {
+                let mut disabled: Vec<String> = settings
+                    .disabled_features
+                    .iter()
+                    .filter(|name| *name != f.name())
+                    .cloned()
+                    .collect();
+                if !parse_on_off(value)? {
+                    disabled.push(f.name().to_string());
+                }
+                changes.disabled_features = Some(disabled);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Setting::AnnouncementChannel => write!(f, "announcement_channel"),
+            Setting::AuditChannel => write!(f, "audit_channel"),
+            Setting::Prefixes => write!(f, "prefixes"),
+            Setting::AdminRole => write!(f, "admin_role"),
+            Setting::ModeratorRole => write!(f, "moderator_role"),
+            Setting::Timezone => write!(f, "timezone"),
+            Setting::QuoteApproval => write!(f, "quote_approval"),
+            Setting::Feature(feature) => write!(f, "feature.{}", feature),
+        }
+    }
+}
+
+impl FromStr for Setting {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        if let Some(feature) = s.strip_prefix("feature.") {
+            return Ok(Setting::Feature(feature.parse()?));
+        }
+        Setting::all()
+            .into_iter()
+            .find(|setting| setting.to_string() == s)
+            .ok_or_else(|| {
+                Error::from(ToddError::InvalidInput(format!(
+                    "Error: *{}* is not a setting, see `!config list`",
+                    s
+                )))
+            })
+    }
+}
+
+fn parse_channel(value: &str) -> Result<i64, Error> {
+    let c = serenity::utils::parse_channel(value).ok_or_else(|| {
+        Error::from(ToddError::InvalidInput(format!(
+            "Error: *{}* is not a channel",
+            value
+        )))
+    })?;
+    Ok(c as i64)
+}
+
+fn show_role(role: Option<i64>) -> String {
+    match role {
+        Some(r) => format!("<@&{}>", r),
+        None => "none".to_string(),
+    }
+}
+
+/// A role mention or a bare role id.
+fn parse_role(value: &str) -> Result<i64, Error> {
+    let r = serenity::utils::parse_role(value)
+        .or_else(|| value.parse::<u64>().ok())
+        .ok_or_else(|| {
+            Error::from(ToddError::InvalidInput(format!(
+                "Error: *{}* is not a role",
+                value
+            )))
+        })?;
+    Ok(r as i64)
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn parse_on_off(value: &str) -> Result<bool, Error> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "yes" | "enabled" => Ok(true),
+        "off" | "false" | "no" | "disabled" => Ok(false),
+        _ => Err(Error::from(ToddError::InvalidInput(format!(
+            "Error: *{}* is not on or off",
+            value
+        )))),
+    }
+}
+
+/// Timezones are fixed UTC offsets like `+02:00` or `-05:00`.
+fn parse_timezone(value: &str) -> Result<FixedOffset, Error> {
+    value.parse::<FixedOffset>().map_err(|_| {
+        Error::from(ToddError::InvalidInput(format!(
+            "Error: *{}* is not a UTC offset like +02:00",
+            value
+        )))
+    })
+}
+
+pub fn feature_enabled(settings: &GuildSettings, feature: Feature) -> bool {
+    !settings
+        .disabled_features
+        .iter()
+        .any(|f| f == feature.name())
+}
+
+/// The current time in the guild's timezone, or the server's if it hasn't
+/// set one.
+pub fn local_now(settings: &GuildSettings) -> NaiveDateTime {
+    match settings.timezone.as_deref().map(parse_timezone) {
+        Some(Ok(offset)) => Utc::now().with_timezone(&offset).naive_local(),
+        _ => Local::now().naive_local(),
+    }
+}
+
+/// The current time in `guild`, for when there's a connection at hand but
+/// not the cache.
+pub fn guild_now(conn: &mut PgConnection, guild: i64) -> Result<NaiveDateTime, ToddError> {
+    let settings = databaser::get_guild_settings(conn, guild)?;
+    Ok(local_now(&settings))
+}
+
+/// `guild`'s settings, from the cache when they've been loaded before.
+pub async fn for_guild(data: &Data, guild: i64) -> Result<GuildSettings, Error> {
+    if let Some((loaded, settings)) = data.guild_settings.lock().await.get(&guild) {
+        if loaded.elapsed() < CACHE_TTL {
+            return Ok(settings.clone());
+        }
+    }
+    let mut conn = databaser::establish_connection()?;
+    let settings = databaser::get_guild_settings(&mut conn, guild)?;
+    data.guild_settings
+        .lock()
+        .await
+        .insert(guild, (Instant::now(), settings.clone()));
+    Ok(settings)
+}
+
+async fn update(ctx: Context<'_>, changes: &UpdateGuildSettings) -> Result<GuildSettings, Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let mut conn = databaser::establish_connection()?;
+    let settings =
+        databaser::update_guild_settings(&mut conn, &Actor::from_ctx(ctx), guild, changes)?;
+    ctx.data()
+        .guild_settings
+        .lock()
+        .await
+        .insert(guild, (Instant::now(), settings.clone()));
+    Ok(settings)
+}
+
+/// Runs before every command and refuses the ones whose feature the guild has
+/// turned off.
+pub async fn check_feature(ctx: Context<'_>) -> Result<bool, Error> {
+    let (Some(guild), Some(feature)) = (
+        ctx.guild_id(),
+        Feature::for_command(&ctx.command().qualified_name),
+    ) else {
+        return Ok(true);
+    };
+    let settings = for_guild(ctx.data(), i64::from(guild)).await?;
+    if !feature_enabled(&settings, feature) {
+        return Err(Error::from(ToddError::PermissionDenied(format!(
+            "Error: *{}* is turned off in this server",
+            feature
+        ))));
+    }
+    Ok(true)
+}
+
+/// Strips whichever of the guild's prefixes the message starts with.
+pub fn strip_prefix<'a>(
+    _: &'a serenity::Context,
+    msg: &'a serenity::Message,
+    data: &'a Data,
+) -> poise::BoxFuture<'a, Result<Option<(&'a str, &'a str)>, Error>> {
+    Box::pin(async move {
+        let prefixes = match msg.guild_id {
+            Some(guild) => for_guild(data, i64::from(guild)).await?.prefixes,
+            None => DEFAULT_PREFIXES.map(String::from).to_vec(),
+        };
+        Ok(prefixes
+            .iter()
+            .find(|p| msg.content.starts_with(p.as_str()))
+            .map(|p| msg.content.split_at(p.len())))
+    })
+}
+
+#[poise::command(
+    prefix_command,
+    category = "Admin",
+    subcommands("get", "set", "list"),
+    subcommand_required
+)]
+pub async fn config(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn get(ctx: Context<'_>, setting: String) -> Result<(), Error> {
+    let setting = setting.parse::<Setting>()?;
+    let settings = for_guild(ctx.data(), guilds::guild_id(ctx)?).await?;
+    ctx.reply(format!("{}: {}", setting, setting.show(&settings)))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn set(ctx: Context<'_>, setting: String, #[rest] value: String) -> Result<(), Error> {
+    let setting = setting.parse::<Setting>()?;
+    let current = for_guild(ctx.data(), guilds::guild_id(ctx)?).await?;
+    let changes = setting.parse_change(&current, &value)?;
+    let settings = update(ctx, &changes).await?;
+    ctx.reply(format!("{} is now {}", setting, setting.show(&settings)))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, check = "permissions::admin")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let settings = for_guild(ctx.data(), guilds::guild_id(ctx)?).await?;
+    let lines: Vec<String> = Setting::all()
+        .iter()
+        .map(|setting| format!("- {}: {}", setting, setting.show(&settings)))
+        .collect();
+    pages::reply_pages(ctx, "Settings", &lines, "").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+
+    fn sample_settings() -> GuildSettings {
+        GuildSettings {
+            guild_id: -1,
+            announcement_channel_id: None,
+            prefixes: DEFAULT_PREFIXES.map(String::from).to_vec(),
+            admin_role_id: None,
+            timezone: None,
+            quote_approval: false,
+            disabled_features: vec![],
+            updated_at: Local::now().naive_local(),
+            moderator_role_id: None,
+            audit_channel_id: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        for setting in Setting::all() {
+            assert_eq!(setting.to_string().parse::<Setting>().ok(), Some(setting));
+        }
+        assert_eq!(
+            "Feature.Markov".parse::<Setting>().ok(),
+            Some(Setting::Feature(Feature::Markov))
+        );
+        assert!("feature.nothing".parse::<Setting>().is_err());
+        assert!("volume".parse::<Setting>().is_err());
+    }
+
+    #[test]
+    fn test_parse_change() -> Result<(), Error> {
+        let settings = sample_settings();
+        let off = Setting::Feature(Feature::Calendar).parse_change(&settings, "off")?;
+        assert_eq!(off.disabled_features, Some(vec!["calendar".to_string()]));
+
+        let channel = Setting::AnnouncementChannel.parse_change(&settings, "<#123>")?;
+        assert_eq!(channel.announcement_channel_id, Some(Some(123)));
+        let cleared = Setting::AnnouncementChannel.parse_change(&settings, "none")?;
+        assert_eq!(cleared.announcement_channel_id, Some(None));
+        let audit = Setting::AuditChannel.parse_change(&settings, "<#789>")?;
+        assert_eq!(audit.audit_channel_id, Some(Some(789)));
+        let moderators = Setting::ModeratorRole.parse_change(&settings, "<@&456>")?;
+        assert_eq!(moderators.moderator_role_id, Some(Some(456)));
+        assert!(Setting::ModeratorRole
+            .parse_change(&settings, "mods")
+            .is_err());
+
+        let timezone = Setting::Timezone.parse_change(&settings, "+02:00")?;
+        assert_eq!(timezone.timezone, Some(Some("+02:00".to_string())));
+        assert!(Setting::Timezone
+            .parse_change(&settings, "Mars/Olympus")
+            .is_err());
+        assert!(Setting::QuoteApproval
+            .parse_change(&settings, "maybe")
+            .is_err());
+        assert!(Setting::Prefixes.parse_change(&settings, " ").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_feature_for_command() {
+        assert_eq!(Feature::for_command("todd"), Some(Feature::Quotes));
+        assert_eq!(Feature::for_command("todd imitate"), Some(Feature::Markov));
+        assert_eq!(Feature::for_command("add quote"), Some(Feature::Quotes));
+        assert_eq!(
+            Feature::for_command("calendar add event"),
+            Some(Feature::Calendar)
+        );
+        assert_eq!(Feature::for_command("add member"), None);
+        assert_eq!(Feature::for_command("config set"), None);
+    }
+}
@@ -1,6 +1,8 @@
 // shitpost.rs
 
+use crate::errors::ToddError;
 use crate::{Context, Error};
+use std::path::Path;
 // use poise::serenity_prelude as serenity;
 
 #[poise::command(
@@ -11,7 +13,7 @@ use crate::{Context, Error};
     member_cooldown = 600
 )]
 pub async fn nerd(ctx: Context<'_>) -> Result<(), Error> {
-    reply_to_reply(ctx, "data/gifs/nerd-emoji.gif").await?;
+    reply_to_reply(ctx, &ctx.data().config.gif_dir().join("nerd-emoji.gif")).await?;
 
     Ok(())
 }
@@ -23,16 +25,18 @@ pub async fn nerd(ctx: Context<'_>) -> Result<(), Error> {
     member_cooldown = 600
 )]
 pub async fn crumble(ctx: Context<'_>) -> Result<(), Error> {
-    reply_to_reply(ctx, "data/gifs/nerd-emoji.gif").await?;
+    reply_to_reply(ctx, &ctx.data().config.gif_dir().join("nerd-emoji.gif")).await?;
 
     Ok(())
 }
 
-async fn reply_to_reply(ctx: Context<'_>, file_location: &str) -> Result<(), Error> {
+async fn reply_to_reply(ctx: Context<'_>, file_location: &Path) -> Result<(), Error> {
     let message = if let Context::Prefix(p) = ctx {
         p.msg
     } else {
-        return Err(Error::from("Error: not prefix command"));
+        return Err(Error::from(ToddError::InvalidInput(
+            "Error: not prefix command".to_string(),
+        )));
     };
     if let Some(message_reply) = &message.referenced_message {
         ctx.channel_id()
@@ -42,7 +46,9 @@ async fn reply_to_reply(ctx: Context<'_>, file_location: &str) -> Result<(), Err
             })
             .await?;
     } else {
-        return Err(Error::from("Error: no referenced message"));
+        return Err(Error::from(ToddError::InvalidInput(
+            "Error: no referenced message".to_string(),
+        )));
     }
 
     Ok(())
@@ -0,0 +1,67 @@
+// shutdown.rs
+
+use poise::serenity_prelude as serenity;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{error, info};
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+/// Held for as long as a scheduler tick runs, so shutting down can wait for it.
+static TICK: Mutex<()> = Mutex::const_new(());
+
+/// Whether SIGINT or SIGTERM has been received.
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Starts a scheduler tick, or returns `None` when the bot is shutting down
+/// and no more should start. Hold on to the guard until the tick is done.
+pub async fn start_tick() -> Option<MutexGuard<'static, ()>> {
+    let guard = TICK.lock().await;
+    // Checked under the lock, so a tick can't start after `listen` has
+    // waited for the last one
+    (!is_shutting_down()).then_some(guard)
+}
+
+/// Waits in the background for SIGINT or SIGTERM, then shuts down in order:
+/// commands are turned away, the scheduler finishes the tick it's on and
+/// the shards disconnect, which lets `main` return and flush the logs.
+pub fn listen(shard_manager: Arc<Mutex<serenity::ShardManager>>) {
+    tokio::spawn(async move {
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(s) => s,
+            Err(err) => {
+                error!("Could not listen for SIGTERM: {}", err);
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down"),
+            _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+        }
+        SHUTTING_DOWN.store(true, Ordering::SeqCst);
+        // Waits out the tick in progress, later ones see the flag and don't
+        // start
+        drop(TICK.lock().await);
+        info!("Scheduler stopped, disconnecting");
+        shard_manager.lock().await.shutdown_all().await;
+    });
+}
+
+#[cfg(test)]
+mod shutdown_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_no_ticks_start_once_shutting_down() {
+        let tick = start_tick().await;
+        assert!(tick.is_some());
+        drop(tick);
+        SHUTTING_DOWN.store(true, Ordering::SeqCst);
+        let after = start_tick().await;
+        SHUTTING_DOWN.store(false, Ordering::SeqCst);
+        assert!(after.is_none());
+    }
+}
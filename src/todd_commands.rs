@@ -1,14 +1,17 @@
 // todd_commands.rs
 
+use crate::audit::Actor;
 use crate::databaser;
-use crate::errors;
-use crate::models::SchlonghouseMember;
-// use crate::helper::CommandHelp;
+use crate::errors::ToddError;
+use crate::guilds;
+use crate::markov;
+use crate::member_commands;
+use crate::pages;
+use crate::permissions;
+use crate::quote_card;
+use crate::quote_commands;
 use crate::{Context, Error};
 use poise::serenity_prelude as serenity;
-use serenity::SerenityError;
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, Write};
 
 #[poise::command(
     prefix_command,
@@ -29,29 +32,29 @@ pub async fn add(_: Context<'_>) -> Result<(), Error> {
     // help_text_fn = "help::CommandHelp::Add.help()",
 )]
 pub async fn quote(ctx: Context<'_>, input: String, #[rest] message: String) -> Result<(), Error> {
-    let member_id = parse_member_or_return_lowercase(&input);
-
     let mut conn = databaser::establish_connection()?;
-    let schlonghouse_member = databaser::get_member(&mut conn, &member_id)?;
+    let resolved = member_commands::resolve_member(ctx, &mut conn, &input).await?;
+    let (schlonghouse_member, created) = databaser::create_quote_for_member(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        resolved.guild_id,
+        &resolved.id.to_string(),
+        &message,
+    )?;
     let member_primary_name = schlonghouse_member.primary_name;
     let schlong_id = schlonghouse_member.id;
-    let member_quote_file = format!("data/{}.quotes.txt", member_primary_name);
-
-    databaser::create_quote(&mut conn, &member_primary_name, &message)?;
-
-    let contents = format!("{}\n", message);
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(member_quote_file)?;
-
-    file.write_all(contents.as_bytes())?;
-
     let sender = ctx.author();
-    let response = format!(
-        "{} added message\n**{}**\nto {}'s quotes list\n<@{}>",
-        sender, message, member_primary_name, schlong_id
-    );
+    let response = if created.approved {
+        format!(
+            "{} added message\n**{}**\nto {}'s quotes list\n<@{}>",
+            sender, message, member_primary_name, schlong_id
+        )
+    } else {
+        format!(
+            "{} added message\n**{}**\nto {}'s quotes list once a moderator approves it (`!quote approve {}`)",
+            sender, message, member_primary_name, created.id
+        )
+    };
 
     ctx.reply(response).await?;
 
@@ -59,13 +62,16 @@ pub async fn quote(ctx: Context<'_>, input: String, #[rest] message: String) ->
 }
 #[poise::command(prefix_command, global_cooldown = 30, broadcast_typing)]
 pub async fn nickname(ctx: Context<'_>, member: String, nickname: String) -> Result<(), Error> {
-    let member_id = parse_member_or_return_lowercase(&member);
     let mut conn = databaser::establish_connection()?;
-    let schlonghouse_member = databaser::get_member(&mut conn, &member_id)?;
+    let schlonghouse_member = member_commands::resolve_member(ctx, &mut conn, &member).await?;
 
     let lowercase_nickname = nickname.to_lowercase();
-    let created_nickname =
-        databaser::create_nickname(&mut conn, &schlonghouse_member, &lowercase_nickname)?;
+    let created_nickname = databaser::create_nickname(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        &schlonghouse_member,
+        &lowercase_nickname,
+    )?;
 
     let resp = format!(
         "{} added a new nickname: **{}** to <@{}>'s nicknames. \nYou can now refer to them as {} in any commands",
@@ -82,41 +88,84 @@ pub async fn nickname(ctx: Context<'_>, member: String, nickname: String) -> Res
     global_cooldown = 60,
     member_cooldown = 300,
     category = "Based Todd",
-    broadcast_typing
+    broadcast_typing,
+    subcommands("markov::imitate")
 )]
-pub async fn todd(ctx: Context<'_>, input: String) -> Result<(), Error> {
-    let member_id = parse_member_or_return_lowercase(&input);
+pub async fn todd(
+    ctx: Context<'_>,
+    input: String,
+    #[rest] flags: Option<String>,
+) -> Result<(), Error> {
     let mut conn = databaser::establish_connection()?;
-
-    // Complicated mess that basically returns a SchlonghouseMember based on
-    // a few queries, trying first the id, then querying based on primary name
-    // finally trying nickname
-    let schlonghouse_member = databaser::get_member(&mut conn, &member_id)?;
-
-    let all_quotes =
-        databaser::get_all_members_quotes(&mut conn, &schlonghouse_member.primary_name)?;
-    let random_quote = databaser::get_random_quote_from_quotes(all_quotes)?;
-    let response = format!("\"{}\"", random_quote);
-    ctx.reply(response).await?;
+    let schlonghouse_member = member_commands::resolve_member(ctx, &mut conn, &input).await?;
+
+    let flags = flags.unwrap_or_default();
+    let has_flag = |flag: &str| flags.split_whitespace().any(|f| f == flag);
+    let selection = databaser::QuoteSelection {
+        channel_id: Some(i64::from(ctx.channel_id())),
+        weighting: if has_flag("--recent") {
+            databaser::QuoteWeighting::Recency
+        } else if has_flag("--uniform") {
+            databaser::QuoteWeighting::Uniform
+        } else {
+            databaser::QuoteWeighting::Votes
+        },
+        ..Default::default()
+    };
+    let random_quote = databaser::get_random_quote(&mut conn, &schlonghouse_member, &selection)?;
+    let message = if has_flag("--card") {
+        quote_card::reply_with_quote_card(ctx, &schlonghouse_member, &random_quote).await?
+    } else {
+        let response = format!("\"{}\"", random_quote.quote);
+        ctx.reply(response).await?.into_message().await?
+    };
+    databaser::record_served_quote(
+        &mut conn,
+        &random_quote,
+        i64::from(ctx.channel_id()),
+        Some(i64::from(message.id)),
+    )?;
+    for emoji in [quote_commands::UPVOTE, quote_commands::DOWNVOTE] {
+        message
+            .react(ctx, quote_commands::vote_reaction(emoji))
+            .await?;
+    }
 
     Ok(())
 }
 
-#[poise::command(prefix_command, global_cooldown = 60/*, member_cooldown = 300*/)]
-pub async fn member(ctx: Context<'_>, primary_name: String, id: String) -> Result<(), Error> {
-    let member_id = if let Some(member_at) = serenity::utils::parse_username(&id) {
-        member_at.to_string()
-    } else {
-        id.clone()
-    };
-
-    let checked_member: bool = serenity::utils::parse_username(&id).is_some();
+/// The Discord id `input` is or mentions, and whether it was a mention.
+fn parse_member_id(input: &str) -> Result<(i64, bool), Error> {
+    if let Some(member_at) = serenity::utils::parse_username(input) {
+        return Ok((member_at as i64, true));
+    }
+    input.parse::<i64>().map(|id| (id, false)).map_err(|_| {
+        Error::from(ToddError::InvalidInput(format!(
+            "Error: `{}` is not a Discord id or mention",
+            input
+        )))
+    })
+}
 
-    let member_id_parsed = member_id.parse::<i64>()?;
+#[poise::command(
+    prefix_command,
+    global_cooldown = 60,
+    // member_cooldown = 300,
+    check = "permissions::moderator"
+)]
+pub async fn member(ctx: Context<'_>, primary_name: String, id: String) -> Result<(), Error> {
+    let (member_id_parsed, checked_member) = parse_member_id(&id)?;
+    let guild = guilds::guild_id(ctx)?;
     let mut conn = databaser::establish_connection()?;
 
-    let created_member =
-        databaser::create_member(&mut conn, member_id_parsed, &primary_name, checked_member)?;
+    let created_member = databaser::create_member(
+        &mut conn,
+        &Actor::from_ctx(ctx),
+        guild,
+        member_id_parsed,
+        &primary_name,
+        checked_member,
+    )?;
 
     let author = ctx.author();
     let resp = format!(
@@ -139,43 +188,41 @@ pub fn parse_member_or_return_lowercase(value: &String) -> String {
     }
 }
 
-fn retrieve_old_quotes(person: &SchlonghouseMember) -> Result<Vec<String>, Error> {
-    let file_path = format!(
-        "/home/paddy/todd/cogs/files/roasts/{}.txt",
-        person.primary_name
-    );
-    println!("looking for quotes file at:\n{}", &file_path);
-    let file = OpenOptions::new().read(true).open(file_path)?;
-    let reader = BufReader::new(file);
-    let output: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
-
-    Ok(output)
-}
-
 #[poise::command(
     prefix_command,
     global_cooldown = 60,
-    member_cooldown = 86400,
+    member_cooldown = 60,
     category = "Based Todd",
     broadcast_typing
 )]
 pub async fn old_quotes(ctx: Context<'_>, input: String) -> Result<(), Error> {
-    let member_id = parse_member_or_return_lowercase(&input);
     let mut conn = databaser::establish_connection()?;
-    let schlonghouse_member = databaser::get_member(&mut conn, &member_id)?;
-
-    let old_quotes_vector = retrieve_old_quotes(&schlonghouse_member)?;
-    let old_quotes_string = old_quotes_vector.join("\n");
-    let response = format!(
-        "{}'s old quotes are:\n{}",
-        schlonghouse_member.primary_name, old_quotes_string
-    );
+    let schlonghouse_member = member_commands::resolve_member(ctx, &mut conn, &input).await?;
 
-    let ctx_resp = ctx.reply(&response).await;
-
-    if let Err(SerenityError::Model(_)) = ctx_resp {
-        errors::message_too_large_handler(ctx, &response).await?
-    };
+    let quotes: Vec<String> = databaser::get_legacy_quotes(&mut conn, &schlonghouse_member)?
+        .into_iter()
+        .map(|q| q.quote)
+        .collect();
+    let title = format!("{}'s old quotes", schlonghouse_member.primary_name);
+    let empty = format!("{} has no old quotes", schlonghouse_member.primary_name);
+    pages::reply_pages(ctx, &title, &quotes, &empty).await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod todd_commands_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_member_id() {
+        assert_eq!(parse_member_id("1234").ok(), Some((1234, false)));
+        assert_eq!(parse_member_id("<@1234>").ok(), Some((1234, true)));
+        assert_eq!(parse_member_id("<@!1234>").ok(), Some((1234, true)));
+        let error = parse_member_id("someone").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ToddError>(),
+            Some(ToddError::InvalidInput(_))
+        ));
+    }
+}
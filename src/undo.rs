@@ -0,0 +1,57 @@
+// undo.rs
+
+use crate::audit::Actor;
+use crate::calendar;
+use crate::databaser::{self, Restored};
+use crate::guilds;
+use crate::{Context, Error};
+use chrono::prelude::*;
+
+/// Brings back the last member, quote, event or reminder you deleted, as
+/// long as it was within the undo window. Run it again to go further back.
+#[poise::command(prefix_command, member_cooldown = 5, category = "Based Todd")]
+pub async fn undo(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = guilds::guild_id(ctx)?;
+    let window = ctx.data().config.undo_window();
+    let since = Local::now().naive_local() - window;
+    let mut conn = databaser::establish_connection()?;
+    let restored = databaser::undo_last_deletion(&mut conn, &Actor::from_ctx(ctx), guild, since)?;
+
+    let response = match restored {
+        Some(Restored::Quote(q)) => {
+            format!(
+                "Restored quote {} to {}'s quotes:\n\"{}\"",
+                q.id, q.quoted, q.quote
+            )
+        }
+        Some(Restored::Event(e, reminders)) => {
+            // Don't wait for the next fetch to start watching them again
+            *ctx.data().reminders.lock().await = calendar::fetch_reminders().await?;
+            format!(
+                "Restored the event *{}* and {} of its reminders",
+                e.title, reminders
+            )
+        }
+        Some(Restored::Reminder(r)) => {
+            *ctx.data().reminders.lock().await = calendar::fetch_reminders().await?;
+            format!(
+                "Restored reminder {} for {}",
+                r.id,
+                r.time_before.format("%D %I:%M %P")
+            )
+        }
+        Some(Restored::Member(m, quotes, events)) => {
+            *ctx.data().reminders.lock().await = calendar::fetch_reminders().await?;
+            format!(
+                "Restored **{}** along with {} quotes and {} events",
+                m.primary_name, quotes, events
+            )
+        }
+        None => format!(
+            "Nothing you deleted in the last {} minutes can be restored",
+            window.num_minutes()
+        ),
+    };
+    ctx.reply(response).await?;
+    Ok(())
+}